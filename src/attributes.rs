@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+/// How an attribute is stored, every kind takes one 32 bit word.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttributeKind {
+    Float,
+    /// RGBA with 8 bits per channel, unpacked with `unpack4x8unorm`.
+    Color,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Attribute {
+    pub name: &'static str,
+    pub kind: AttributeKind,
+    /// Bits of the initial value.
+    pub default: u32,
+}
+
+const fn float(name: &'static str, default: f32) -> Attribute {
+    Attribute {
+        name,
+        kind: AttributeKind::Float,
+        default: default.to_bits(),
+    }
+}
+
+/// Per-particle state besides position and velocity. The WGSL struct and the offsets used by the
+/// shaders and exporters are generated from this list, so adding an attribute here is enough.
+pub const ATTRIBUTES: &[Attribute] = &[
+    float("mass", 1.0),
+    float("charge", 0.0),
+    float("age", 0.0),
+    float("lifetime", f32::MAX),
+    float("size", 1.0),
+    Attribute {
+        name: "color",
+        kind: AttributeKind::Color,
+        default: u32::from_le_bytes([255; 4]),
+    },
+    float("user0", 0.0),
+    float("user1", 0.0),
+];
+
+/// Words per particle.
+pub fn stride() -> u32 {
+    ATTRIBUTES.len() as u32
+}
+
+/// Word offset of an attribute in a particle's record.
+pub fn offset(name: &str) -> Option<u32> {
+    ATTRIBUTES.iter().position(|a| a.name == name).map(|i| i as u32)
+}
+
+/// Attributes that can be mapped through a colormap.
+pub fn floats() -> impl Iterator<Item = &'static Attribute> {
+    ATTRIBUTES.iter().filter(|a| a.kind == AttributeKind::Float)
+}
+
+/// Initial records of `count` particles.
+pub fn default_data(count: u32) -> Vec<u32> {
+    let record: Vec<u32> = ATTRIBUTES.iter().map(|a| a.default).collect();
+    record.repeat(count as usize)
+}
+
+/// `ParticleAttributes`, its defaults and an `ATTRIBUTE_*` word offset per attribute for shaders that
+/// read the buffer as plain words.
+pub fn wgsl() -> String {
+    let mut out = String::from("// Generated by attributes.rs\n\nstruct ParticleAttributes {\n");
+    for a in ATTRIBUTES {
+        let ty = match a.kind {
+            AttributeKind::Float => "f32",
+            AttributeKind::Color => "u32",
+        };
+        writeln!(out, "    {}: {ty},", a.name).unwrap();
+    }
+    out.push_str("}\n\n");
+
+    writeln!(out, "const ATTRIBUTE_STRIDE: u32 = {}u;", stride()).unwrap();
+    for (i, a) in ATTRIBUTES.iter().enumerate() {
+        writeln!(out, "const ATTRIBUTE_{}: u32 = {i}u;", a.name.to_uppercase()).unwrap();
+    }
+
+    let defaults: Vec<String> = ATTRIBUTES.iter()
+        .map(|a| match a.kind {
+            AttributeKind::Float => format!("bitcast<f32>({}u)", a.default),
+            AttributeKind::Color => format!("{}u", a.default),
+        })
+        .collect();
+    writeln!(out, "\nfn default_attributes() -> ParticleAttributes {{").unwrap();
+    writeln!(out, "    return ParticleAttributes({});", defaults.join(", ")).unwrap();
+    out.push_str("}\n");
+    out
+}
@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use anyhow::Context;
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::timeline::Keyframe;
+
+/// Everything needed to restore a view, the orbit pivot is rebuilt from the view direction.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub pos: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl CameraPose {
+    /// Camera to world rotation, `orientation() * Vec3::NEG_Z` is the view direction.
+    pub fn orientation(&self) -> Quat {
+        (Quat::from_axis_angle(Vec3::X, self.pitch) * Quat::from_axis_angle(Vec3::Y, self.yaw)).inverse()
+    }
+
+    /// Inverse of [`CameraPose::orientation`], any roll is dropped.
+    pub fn from_orientation(pos: Vec3, orientation: Quat) -> Self {
+        let forward = orientation * Vec3::NEG_Z;
+        Self {
+            pos: pos.to_array(),
+            yaw: forward.x.atan2(-forward.z),
+            pitch: (-forward.y).clamp(-1.0, 1.0).asin(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub pose: CameraPose,
+}
+
+/// Bookmarks and the flythrough keyframes, saved together as JSON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CameraBookmarks {
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    #[serde(default)]
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraBookmarks {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, serde_json::to_string_pretty(self)?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
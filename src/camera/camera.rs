@@ -1,14 +1,59 @@
 use std::f32::consts::PI;
 
 use glam::{Mat3, Mat4, Quat, Vec3};
-use winit::keyboard::KeyCode;
-use winit_input_helper::WinitInputHelper;
+use crate::camera::CameraPose;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CameraMode {
+    /// WASD free flight, the mouse turns the camera in place.
+    Fly,
+    /// Rotates around `target` at `distance`, the scroll wheel zooms and the middle mouse button pans.
+    Orbit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    /// Undistorted, shows the same area as the perspective view does at `distance`.
+    Orthographic,
+}
+
+/// Axis aligned views around the pivot.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SnapView {
+    /// Looking down -Y.
+    Top,
+    /// Looking down -Z.
+    Front,
+    /// Looking down -X.
+    Side,
+    Isometric,
+}
+
+impl SnapView {
+    /// `(yaw, pitch)` of the view.
+    pub fn rotation(&self) -> (f32, f32) {
+        match self {
+            SnapView::Top => (0.0, PI / 2.0),
+            SnapView::Front => (0.0, 0.0),
+            SnapView::Side => (-PI / 2.0, 0.0),
+            // Equal angles to all three axes
+            SnapView::Isometric => (-PI / 4.0, (1.0 / 2f32.sqrt()).atan()),
+        }
+    }
+}
 
 pub struct Camera {
     pub pos: Vec3,
 
     pub rotation: (f32, f32),
 
+    pub mode: CameraMode,
+    pub projection: Projection,
+    /// Orbit pivot, always `distance` in front of the camera while orbiting.
+    pub target: Vec3,
+    pub distance: f32,
+
     pub up: Vec3,
     pub aspect_ratio: f32,
     pub fov_y: f32,
@@ -25,23 +70,131 @@ impl Camera {
 
         let view = Mat4::from_quat(quat_y * quat_x) * translation;
 
-        let proj = Mat4::perspective_infinite_rh(self.fov_y.to_radians(), self.aspect_ratio, self.z_near);
+        let proj = match self.projection {
+            Projection::Perspective => Mat4::perspective_infinite_rh(self.fov_y.to_radians(), self.aspect_ratio, self.z_near),
+            Projection::Orthographic => {
+                let half_height = self.distance * (0.5 * self.fov_y.to_radians()).tan();
+                let half_width = half_height * self.aspect_ratio;
+                // Keeps what is just behind the camera, orbiting close to the particles would clip them otherwise
+                let depth = 100.0 + self.distance;
+                Mat4::orthographic_rh(-half_width, half_width, -half_height, half_height, -depth, depth)
+            }
+        };
 
         return proj * view;
     }
 
+    /// World space view direction, positive pitch looks down.
+    pub fn forward(&self) -> Vec3 {
+        let (yaw, pitch) = self.rotation;
+        Vec3::new(pitch.cos() * yaw.sin(), -pitch.sin(), -pitch.cos() * yaw.cos())
+    }
+
+    pub fn right(&self) -> Vec3 {
+        Vec3::new(self.rotation.0.cos(), 0.0, self.rotation.0.sin())
+    }
+
+    pub fn view_up(&self) -> Vec3 {
+        self.right().cross(self.forward())
+    }
+
+    /// Switches the controller without moving the view, entering orbit puts the pivot `distance` ahead.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Orbit && self.mode != CameraMode::Orbit {
+            self.target = self.pos + self.forward() * self.distance;
+        }
+        self.mode = mode;
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            pos: self.pos.to_array(),
+            yaw: self.rotation.0,
+            pitch: self.rotation.1,
+        }
+    }
+
+    /// Jumps to `pose`, while orbiting the pivot stays `distance` ahead of the new view.
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.pos = Vec3::from_array(pose.pos);
+        self.rotation = (pose.yaw, pose.pitch.clamp(-PI / 2.0, PI / 2.0));
+        self.target = self.pos + self.forward() * self.distance;
+    }
+
+    /// Where the camera orbits around, or would when switching to orbit mode.
+    pub fn pivot(&self) -> Vec3 {
+        match self.mode {
+            CameraMode::Orbit => self.target,
+            CameraMode::Fly => self.pos + self.forward() * self.distance,
+        }
+    }
+
+    /// Turns to `view` around the pivot.
+    pub fn snap(&mut self, view: SnapView) {
+        let pivot = self.pivot();
+        self.rotation = view.rotation();
+        self.target = pivot;
+        self.pos = pivot - self.forward() * self.distance;
+    }
+
+    fn update_orbit(&mut self) {
+        self.pos = self.target - self.forward() * self.distance;
+    }
+
     pub fn process_mouse(&mut self, dx: f32, dy: f32) {
         self.rotation.0 += dx;
         self.rotation.1 = (dy + self.rotation.1).clamp(-PI / 2.0, PI / 2.0);
+        if self.mode == CameraMode::Orbit {
+            self.update_orbit();
+        }
+    }
+
+    /// Moves the pivot in the view plane, scaled by the distance so the scene follows the cursor.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.target += (self.view_up() * dy - self.right() * dx) * self.distance;
+        self.update_orbit();
+    }
+
+    /// Positive steps move towards the pivot, orthographic views shrink instead.
+    pub fn zoom(&mut self, steps: f32) {
+        self.distance = (self.distance * 0.9f32.powf(steps)).clamp(0.01, 1000.0);
+        if self.mode == CameraMode::Orbit {
+            self.update_orbit();
+        }
     }
 
-    pub fn process_keyboard_input(&mut self, input: &WinitInputHelper) {
-        let input_vector = Vec3::new((input.key_held(KeyCode::KeyD) as i8 - input.key_held(KeyCode::KeyA) as i8) as f32,
-                                     0.0,
-                                     (input.key_held(KeyCode::KeyS) as i8 - input.key_held(KeyCode::KeyW) as i8) as f32);
-        let mut input_vector = Mat3::from_axis_angle(Vec3::Y, -self.rotation.0) * input_vector;
-        input_vector.y = (input.key_held(KeyCode::Space) as i8 - input.key_held(KeyCode::ShiftLeft) as i8) as f32;
+    /// Orbits around `point` from the current position, turning the camera to face it.
+    /// Orthographic views keep their direction and center on it instead.
+    pub fn focus(&mut self, point: Vec3) {
+        if self.projection == Projection::Orthographic {
+            self.target = point;
+            self.mode = CameraMode::Orbit;
+            self.update_orbit();
+            return;
+        }
 
-        self.pos += input_vector * 0.1;
+        let offset = point - self.pos;
+        let distance = offset.length();
+        if distance < 1e-4 {
+            return;
+        }
+        let direction = offset / distance;
+        self.rotation = (direction.x.atan2(-direction.z), (-direction.y).asin());
+        self.target = point;
+        self.distance = distance;
+        self.mode = CameraMode::Orbit;
+        self.update_orbit();
     }
-}
\ No newline at end of file
+
+    /// Moves by `input` scaled by `distance`, x is right and z is back relative to the heading, y is straight up.
+    pub fn fly(&mut self, input: Vec3, distance: f32) {
+        let mut input_vector = Mat3::from_axis_angle(Vec3::Y, -self.rotation.0) * Vec3::new(input.x, 0.0, input.z);
+        input_vector.y = input.y;
+
+        self.pos += input_vector * distance;
+        // Flying while orbiting drags the pivot along
+        if self.mode == CameraMode::Orbit {
+            self.target += input_vector * distance;
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::{Key, KeyCode};
+use winit_input_helper::WinitInputHelper;
+
+/// A key by its position on the keyboard, e.g. `"KeyW"`, or by the character it types, e.g. `"z"`.
+/// Positions stay put across layouts, characters follow the printed labels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyBinding {
+    Code(KeyCode),
+    Character(String),
+}
+
+impl KeyBinding {
+    pub fn held(&self, input: &WinitInputHelper) -> bool {
+        match self {
+            KeyBinding::Code(code) => input.key_held(*code),
+            KeyBinding::Character(c) => input.key_held_logical(Key::Character(c.as_str())),
+        }
+    }
+
+    pub fn pressed(&self, input: &WinitInputHelper) -> bool {
+        match self {
+            KeyBinding::Code(code) => input.key_pressed(*code),
+            KeyBinding::Character(c) => input.key_pressed_logical(Key::Character(c.as_str())),
+        }
+    }
+}
+
+pub fn any_held(bindings: &[KeyBinding], input: &WinitInputHelper) -> bool {
+    bindings.iter().any(|b| b.held(input))
+}
+
+pub fn any_pressed(bindings: &[KeyBinding], input: &WinitInputHelper) -> bool {
+    bindings.iter().any(|b| b.pressed(input))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub forward: Vec<KeyBinding>,
+    pub back: Vec<KeyBinding>,
+    pub left: Vec<KeyBinding>,
+    pub right: Vec<KeyBinding>,
+    pub up: Vec<KeyBinding>,
+    pub down: Vec<KeyBinding>,
+    pub sprint: Vec<KeyBinding>,
+    pub slow: Vec<KeyBinding>,
+    pub toggle_orbit: Vec<KeyBinding>,
+    pub toggle_projection: Vec<KeyBinding>,
+    pub snap_top: Vec<KeyBinding>,
+    pub snap_front: Vec<KeyBinding>,
+    pub snap_side: Vec<KeyBinding>,
+    pub snap_isometric: Vec<KeyBinding>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let keys = |codes: &[KeyCode]| codes.iter().map(|c| KeyBinding::Code(*c)).collect();
+        Self {
+            forward: keys(&[KeyCode::KeyW, KeyCode::ArrowUp]),
+            back: keys(&[KeyCode::KeyS, KeyCode::ArrowDown]),
+            left: keys(&[KeyCode::KeyA, KeyCode::ArrowLeft]),
+            right: keys(&[KeyCode::KeyD, KeyCode::ArrowRight]),
+            up: keys(&[KeyCode::Space]),
+            down: keys(&[KeyCode::ShiftLeft]),
+            sprint: keys(&[KeyCode::ControlLeft]),
+            slow: keys(&[KeyCode::AltLeft]),
+            toggle_orbit: keys(&[KeyCode::KeyO]),
+            toggle_projection: keys(&[KeyCode::Numpad5]),
+            snap_top: keys(&[KeyCode::Numpad7]),
+            snap_front: keys(&[KeyCode::Numpad1]),
+            snap_side: keys(&[KeyCode::Numpad3]),
+            snap_isometric: keys(&[KeyCode::Numpad9]),
+        }
+    }
+}
+
+/// Key bindings and tuning for the camera controllers, loaded from a JSON file. Missing entries keep their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    pub bindings: Bindings,
+    /// Radians per mouse count while flying.
+    pub mouse_sensitivity: f32,
+    /// Radians per mouse count while dragging in orbit mode.
+    pub orbit_sensitivity: f32,
+    pub invert_y: bool,
+    /// Flying speed in units per second.
+    pub speed: f32,
+    pub sprint_multiplier: f32,
+    pub slow_multiplier: f32,
+    /// Radians per second at full stick deflection.
+    pub gamepad_look_speed: f32,
+    /// Stick deflections below this are ignored.
+    pub gamepad_deadzone: f32,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            bindings: Bindings::default(),
+            mouse_sensitivity: 0.001,
+            orbit_sensitivity: 0.005,
+            invert_y: false,
+            speed: 6.0,
+            sprint_multiplier: 4.0,
+            slow_multiplier: 0.25,
+            gamepad_look_speed: 2.5,
+            gamepad_deadzone: 0.15,
+        }
+    }
+}
+
+impl Controls {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, serde_json::to_string_pretty(self)?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Speed multiplier from the sprint and slow keys.
+    pub fn speed_modifier(&self, input: &WinitInputHelper) -> f32 {
+        let mut modifier = 1.0;
+        if any_held(&self.bindings.sprint, input) {
+            modifier *= self.sprint_multiplier;
+        }
+        if any_held(&self.bindings.slow, input) {
+            modifier *= self.slow_multiplier;
+        }
+        modifier
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn dead_zone(&self, value: f32) -> f32 {
+        if value.abs() < self.gamepad_deadzone { 0.0 } else { value }
+    }
+}
@@ -1,14 +1,24 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use egui_wgpu::wgpu;
 use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsages, ShaderStages, SurfaceConfiguration};
 use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
 use glam::Vec3;
+use winit::event::MouseButton;
 use winit_input_helper::WinitInputHelper;
 
-pub use camera::Camera;
+use controls::{any_held, any_pressed, KeyBinding};
 
+pub use bookmarks::{Bookmark, CameraBookmarks, CameraPose};
+pub use camera::{Camera, CameraMode, Projection, SnapView};
+pub use controls::Controls;
+pub use timeline::{Keyframe, Timeline};
+
+pub mod bookmarks;
 pub mod camera;
+pub mod controls;
+pub mod timeline;
 
 pub(crate) struct CameraBundle {
     camera: Camera,
@@ -19,8 +29,16 @@ pub(crate) struct CameraBundle {
     queue: Arc<wgpu::Queue>,
 
     pub winit_input_helper: WinitInputHelper,
+    pub controls: Controls,
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+
+    last_click: Option<Instant>,
+    focus_request: Option<(f32, f32)>,
 }
 
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(300);
+
 impl CameraBundle {
     pub fn new(camera: Camera, device: &wgpu::Device, queue: Arc<wgpu::Queue>) -> (Self, BindGroupLayout) {
         // let camera = Camera {
@@ -69,6 +87,11 @@ impl CameraBundle {
             camera_bind_group: Arc::new(camera_bind_group),
             queue,
             winit_input_helper: Default::default(),
+            controls: Controls::default(),
+            #[cfg(feature = "gamepad")]
+            gilrs: gilrs::Gilrs::new().ok(),
+            last_click: None,
+            focus_request: None,
         }, camera_bind_group_layout)
     }
 
@@ -81,24 +104,184 @@ impl CameraBundle {
         );
     }
 
+    pub fn view_projection(&self) -> glam::Mat4 {
+        self.camera.build_view_projection_matrix()
+    }
+
     pub fn get_gpu_side(&self) -> (Arc<Buffer>, Arc<BindGroup>){
         return (self.camera_buffer.clone(), self.camera_bind_group.clone());
     }
 
-    fn process_keyboard_input(&mut self) {
-        self.camera.process_keyboard_input(&self.winit_input_helper);
+    fn process_keyboard_input(&mut self, dt: f32) {
+        let input = &self.winit_input_helper;
+        let bindings = &self.controls.bindings;
+        let axis = |positive: &[KeyBinding], negative: &[KeyBinding]| {
+            (any_held(positive, input) as i8 - any_held(negative, input) as i8) as f32
+        };
+        let direction = Vec3::new(
+            axis(&bindings.right, &bindings.left),
+            axis(&bindings.up, &bindings.down),
+            axis(&bindings.back, &bindings.forward),
+        );
+        self.camera.fly(direction, self.controls.speed * self.controls.speed_modifier(input) * dt);
+
+        if any_pressed(&bindings.toggle_orbit, input) {
+            let mode = if self.camera.mode == CameraMode::Orbit { CameraMode::Fly } else { CameraMode::Orbit };
+            self.camera.set_mode(mode);
+        }
+        if any_pressed(&bindings.toggle_projection, input) {
+            let projection = if self.camera.projection == Projection::Orthographic { Projection::Perspective } else { Projection::Orthographic };
+            self.camera.projection = projection;
+        }
+        let snap = [
+            (&bindings.snap_top, SnapView::Top),
+            (&bindings.snap_front, SnapView::Front),
+            (&bindings.snap_side, SnapView::Side),
+            (&bindings.snap_isometric, SnapView::Isometric),
+        ].into_iter().find(|(keys, _)| any_pressed(keys, input));
+        if let Some((_, view)) = snap {
+            self.camera.snap(view);
+        }
+    }
+
+    fn process_mouse(&mut self, pointer_over_ui: bool){
+        let input = &self.winit_input_helper;
+        let (dx, dy) = input.mouse_diff();
+        let dy = if self.controls.invert_y { -dy } else { dy };
+        match self.camera.mode {
+            CameraMode::Fly => self.camera.process_mouse(dx * self.controls.mouse_sensitivity, dy * self.controls.mouse_sensitivity),
+            CameraMode::Orbit if !pointer_over_ui => {
+                if input.mouse_held(MouseButton::Middle) {
+                    self.camera.pan(dx * self.controls.mouse_sensitivity, dy * self.controls.mouse_sensitivity);
+                } else if input.mouse_held(MouseButton::Left) {
+                    self.camera.process_mouse(dx * self.controls.orbit_sensitivity, dy * self.controls.orbit_sensitivity);
+                }
+            }
+            CameraMode::Orbit => {}
+        }
+
+        let zooms = self.camera.mode == CameraMode::Orbit || self.camera.projection == Projection::Orthographic;
+        let (_, scroll) = input.scroll_diff();
+        if zooms && !pointer_over_ui && scroll != 0.0 {
+            self.camera.zoom(scroll);
+        }
+
+        if !pointer_over_ui && input.mouse_pressed(MouseButton::Left) {
+            let now = Instant::now();
+            if self.last_click.is_some_and(|last| now - last < DOUBLE_CLICK_TIME) {
+                self.focus_request = input.cursor();
+                self.last_click = None;
+            } else {
+                self.last_click = Some(now);
+            }
+        }
+    }
+
+    /// Left stick moves, right stick looks and the triggers fly up and down. While orbiting the
+    /// left stick zooms and pans instead.
+    #[cfg(feature = "gamepad")]
+    fn process_gamepad(&mut self, dt: f32) {
+        use gilrs::{Axis, Button};
+
+        let Some(gilrs) = &mut self.gilrs else { return };
+        // Events only need draining, the gamepad state is kept up to date by gilrs
+        while gilrs.next_event().is_some() {}
+
+        let controls = &self.controls;
+        for (_, gamepad) in gilrs.gamepads() {
+            let left = (controls.dead_zone(gamepad.value(Axis::LeftStickX)), controls.dead_zone(gamepad.value(Axis::LeftStickY)));
+            let right = (controls.dead_zone(gamepad.value(Axis::RightStickX)), controls.dead_zone(gamepad.value(Axis::RightStickY)));
+            let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+
+            let look = controls.gamepad_look_speed * dt;
+            let look_y = if controls.invert_y { right.1 } else { -right.1 };
+            self.camera.process_mouse(right.0 * look, look_y * look);
+
+            match self.camera.mode {
+                CameraMode::Fly => {
+                    let sprint = if gamepad.is_pressed(Button::LeftThumb) { controls.sprint_multiplier } else { 1.0 };
+                    let vertical = trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+                    self.camera.fly(Vec3::new(left.0, vertical, -left.1), controls.speed * sprint * dt);
+                }
+                CameraMode::Orbit => {
+                    self.camera.zoom(5.0 * left.1 * dt);
+                    self.camera.pan(-left.0 * dt, 0.0);
+                }
+            }
+        }
+    }
+
+    /// `pointer_over_ui` keeps clicks and scrolling on the egui windows from moving the orbit camera.
+    /// Motion is scaled by the time since the last input step so it doesn't depend on the frame rate.
+    pub fn handle_inputs(&mut self, pointer_over_ui: bool){
+        let dt = self.winit_input_helper.delta_time().map_or(1.0 / 60.0, |dt| dt.as_secs_f32()).min(0.1);
+        self.process_keyboard_input(dt);
+        self.process_mouse(pointer_over_ui);
+        #[cfg(feature = "gamepad")]
+        self.process_gamepad(dt);
         self.update();
     }
 
-    fn process_mouse(&mut self){
-        let (dx, dy) = self.winit_input_helper.mouse_diff();
-        self.camera.process_mouse(dx * 0.001, dy * 0.001);
+    pub fn mode(&self) -> CameraMode {
+        self.camera.mode
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.camera.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.camera.projection = projection;
+        self.update();
+    }
+
+    pub fn snap(&mut self, view: SnapView) {
+        self.camera.snap(view);
         self.update();
     }
 
-    pub fn handle_inputs(&mut self){
-        self.process_keyboard_input();
-        self.process_mouse();
+    pub fn pose(&self) -> CameraPose {
+        self.camera.pose()
+    }
+
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.camera.set_pose(pose);
+        self.update();
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.camera.set_mode(mode);
+        self.update();
+    }
+
+    /// Cursor position of the last double click, in physical pixels.
+    pub fn take_focus_request(&mut self) -> Option<(f32, f32)> {
+        self.focus_request.take()
+    }
+
+    /// Focuses on the surface under the cursor given its depth buffer value. Clicking the background
+    /// moves the pivot across the plane it currently sits in instead.
+    pub fn focus_at(&mut self, cursor: (f32, f32), depth: Option<f32>, config: &SurfaceConfiguration) {
+        let ndc_x = 2.0 * (cursor.0 + 0.5) / config.width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * (cursor.1 + 0.5) / config.height as f32;
+        let inverse = self.camera.build_view_projection_matrix().inverse();
+
+        let point = match depth {
+            Some(depth) if depth < 1.0 => inverse.project_point3(Vec3::new(ndc_x, ndc_y, depth)),
+            _ => {
+                // Works for both projections, orthographic rays are all parallel to the view
+                let origin = inverse.project_point3(Vec3::new(ndc_x, ndc_y, 0.0));
+                let ray = (inverse.project_point3(Vec3::new(ndc_x, ndc_y, 0.5)) - origin).normalize();
+                let forward = self.camera.forward();
+                let t = (self.camera.pivot() - origin).dot(forward) / ray.dot(forward);
+                if !t.is_finite() || t <= 0.0 {
+                    return;
+                }
+                origin + ray * t
+            }
+        };
+        self.camera.focus(point);
+        self.update();
     }
 
     pub fn resize(&mut self, config: &SurfaceConfiguration){
@@ -0,0 +1,110 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::bookmarks::CameraPose;
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// Seconds from the start of the flythrough.
+    pub time: f32,
+    pub pose: CameraPose,
+}
+
+/// Keyframed flythrough. Positions follow a Catmull-Rom spline through the keyframes and the orientation
+/// is slerped between neighbors, so playback is smooth without overshooting the view direction.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    keyframes: Vec<Keyframe>,
+    pub time: f32,
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl Timeline {
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn set_keyframes(&mut self, mut keyframes: Vec<Keyframe>) {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.keyframes = keyframes;
+        self.time = self.time.min(self.duration());
+    }
+
+    /// Inserts in time order, a keyframe at an existing time replaces it.
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        match self.keyframes.binary_search_by(|k| k.time.total_cmp(&keyframe.time)) {
+            Ok(i) => self.keyframes[i] = keyframe,
+            Err(i) => self.keyframes.insert(i, keyframe),
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.keyframes.remove(index);
+        self.time = self.time.min(self.duration());
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    pub fn play(&mut self) {
+        if self.time >= self.duration() {
+            self.time = 0.0;
+        }
+        self.playing = self.keyframes.len() > 1;
+    }
+
+    /// Steps playback by `dt` seconds and returns the pose to show, `None` while stopped.
+    pub fn advance(&mut self, dt: f32) -> Option<CameraPose> {
+        if !self.playing {
+            return None;
+        }
+        let duration = self.duration();
+        self.time += dt;
+        if self.time >= duration {
+            if self.looping && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+            }
+        }
+        self.sample(self.time)
+    }
+
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let keys = &self.keyframes;
+        let (first, last) = (keys.first()?, keys.last()?);
+        if keys.len() == 1 || time <= first.time {
+            return Some(first.pose);
+        }
+        if time >= last.time {
+            return Some(last.pose);
+        }
+
+        let i = keys.partition_point(|k| k.time <= time) - 1;
+        let (k0, k1) = (&keys[i], &keys[i + 1]);
+        let h = k1.time - k0.time;
+        if h <= 0.0 {
+            return Some(k1.pose);
+        }
+        let s = (time - k0.time) / h;
+
+        let position = |k: &Keyframe| Vec3::from_array(k.pose.pos);
+        // Catmull-Rom tangents from the neighbors, one sided at the ends
+        let tangent = |j: usize| {
+            let (a, b) = (&keys[j.saturating_sub(1)], &keys[(j + 1).min(keys.len() - 1)]);
+            let dt = b.time - a.time;
+            if dt > 0.0 { (position(b) - position(a)) / dt } else { Vec3::ZERO }
+        };
+
+        let (s2, s3) = (s * s, s * s * s);
+        let pos = (2.0 * s3 - 3.0 * s2 + 1.0) * position(k0)
+            + (s3 - 2.0 * s2 + s) * h * tangent(i)
+            + (-2.0 * s3 + 3.0 * s2) * position(k1)
+            + (s3 - s2) * h * tangent(i + 1);
+        let orientation = k0.pose.orientation().slerp(k1.pose.orientation(), s);
+        Some(CameraPose::from_orientation(pos, orientation))
+    }
+}
@@ -0,0 +1,285 @@
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, Extent3d, PipelineLayoutDescriptor, Queue, ShaderModuleDescriptor, ShaderSource, ShaderStages, StorageTextureAccess, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension};
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+
+/// Voxels along each axis of the collision field.
+pub const SDF_RESOLUTION: u32 = 64;
+/// Triangles tested per bake dispatch, keeps every submission short enough for the GPU watchdog.
+const BAKE_CHUNK: u32 = 1024;
+
+/// Where the collision field sits and how particles bounce off it, mirrors `SdfParams` in sdf.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SdfParams {
+    pub bounds_min: [f32; 4],
+    pub bounds_max: [f32; 4],
+    pub dims: [u32; 4],
+    /// Fraction of the normal speed kept when bouncing.
+    pub restitution: f32,
+    /// Coulomb friction coefficient of the surfaces.
+    pub friction: f32,
+    /// Distance particles are kept away from the surfaces.
+    pub thickness: f32,
+    pub enabled: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakeRange {
+    first: u32,
+    count: u32,
+    _padding: [u32; 2],
+}
+
+/// Signed distance field of the scene's meshes that the particles collide with, baked on the GPU.
+pub struct Colliders {
+    pub params: SdfParams,
+    /// Whether particles collide, only takes effect once something has been baked.
+    pub collide: bool,
+    baked: bool,
+    params_buffer: Buffer,
+    view: TextureView,
+
+    bake_pipeline: ComputePipeline,
+    bake_bind_group_layout: BindGroupLayout,
+    range_buffer: Buffer,
+    distance_buffer: Buffer,
+    winding_buffer: Buffer,
+
+    /// Bound as group 2 of the simulation.
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl Colliders {
+    pub fn new(device: &Device) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Collision SDF Texture"),
+            size: Extent3d {
+                width: SDF_RESOLUTION,
+                height: SDF_RESOLUTION,
+                depth_or_array_layers: SDF_RESOLUTION,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let params = SdfParams {
+            bounds_min: [-1.0, -1.0, -1.0, 0.0],
+            bounds_max: [1.0, 1.0, 1.0, 0.0],
+            dims: [SDF_RESOLUTION, SDF_RESOLUTION, SDF_RESOLUTION, 0],
+            restitution: 0.3,
+            friction: 0.2,
+            thickness: 0.01,
+            enabled: 0,
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("SDF Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let uniform = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Collision Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                uniform(1),
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Collision Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let bake_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("SDF Bake Bind Group Layout"),
+            entries: &[
+                uniform(0),
+                uniform(1),
+                storage(2, true),
+                storage(3, false),
+                storage(4, false),
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: TextureFormat::R32Float,
+                        view_dimension: TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("sdf_bake.wgsl"),
+            source: ShaderSource::Wgsl(format!("{}\n{}", include_str!("sdf.wgsl"), include_str!("sdf_bake.wgsl")).into()),
+        });
+        let bake_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("SDF Bake Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("SDF Bake Pipeline Layout"),
+                bind_group_layouts: &[&bake_bind_group_layout],
+                push_constant_ranges: &[],
+            })),
+            module: &shader,
+            entry_point: "bake",
+            compilation_options: Default::default(),
+        });
+
+        let voxels = (SDF_RESOLUTION * SDF_RESOLUTION * SDF_RESOLUTION) as u64;
+        let voxel_buffer = |label| device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: voxels * std::mem::size_of::<f32>() as u64,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let range_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("SDF Bake Range Buffer"),
+            size: std::mem::size_of::<BakeRange>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            params,
+            collide: true,
+            baked: false,
+            params_buffer,
+            view,
+            bake_pipeline,
+            bake_bind_group_layout,
+            range_buffer,
+            distance_buffer: voxel_buffer("SDF Bake Distance Buffer"),
+            winding_buffer: voxel_buffer("SDF Bake Winding Buffer"),
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn update_params(&mut self, queue: &Queue) {
+        self.params.enabled = (self.collide && self.baked) as u32;
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+    }
+
+    /// Rebakes the field around `triangles`, given in world space. Every chunk of triangles is its own
+    /// submission. An empty list turns collisions off.
+    pub fn bake(&mut self, device: &Device, queue: &Queue, triangles: &[[Vec3; 3]]) {
+        self.baked = !triangles.is_empty();
+        if !self.baked {
+            self.update_params(queue);
+            return;
+        }
+
+        let (min, max) = triangles.iter()
+            .flatten()
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), v| (min.min(*v), max.max(*v)));
+        // Room for the field to turn positive around the mesh, flat meshes still get a few voxels of depth
+        let padding = 0.1 * (max - min).max_element() + 4.0 * self.params.thickness;
+        self.params.bounds_min = (min - padding).extend(0.0).to_array();
+        self.params.bounds_max = (max + padding).extend(0.0).to_array();
+        self.update_params(queue);
+
+        let vertices: Vec<[f32; 4]> = triangles.iter().flatten().map(|v| v.extend(1.0).to_array()).collect();
+        let triangle_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("SDF Bake Triangle Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::STORAGE,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("SDF Bake Bind Group"),
+            layout: &self.bake_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.range_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: triangle_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.distance_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: self.winding_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: BindingResource::TextureView(&self.view),
+                },
+            ],
+        });
+
+        let count = triangles.len() as u32;
+        let workgroups = SDF_RESOLUTION.div_ceil(4);
+        for first in (0..count).step_by(BAKE_CHUNK as usize) {
+            let range = BakeRange {
+                first,
+                count: BAKE_CHUNK.min(count - first),
+                _padding: [0; 2],
+            };
+            queue.write_buffer(&self.range_buffer, 0, bytemuck::cast_slice(&[range]));
+
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("SDF Bake Encoder") });
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+                compute_pass.set_pipeline(&self.bake_pipeline);
+                compute_pass.set_bind_group(0, &bind_group, &[]);
+                compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+            }
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// Collisions with the meshes placed in the scene. Shares `positions` and `velocities` with
+// compute_shader.wgsl, sdf.wgsl has to be included.
+
+@group(2) @binding(0)
+var sdf: texture_3d<f32>;
+@group(2) @binding(1)
+var<uniform> collision: SdfParams;
+
+// Pushes a particle that got closer than `thickness` to a mesh back out along the field's gradient and
+// bounces its velocity off the surface
+fn collide(i: u32) {
+    if (collision.enabled == 0u) {
+        return;
+    }
+    let p = positions[i].xyz;
+    let d = sample_sdf(sdf, collision, p);
+    if (d >= collision.thickness) {
+        return;
+    }
+    let gradient = sdf_gradient(sdf, collision, p);
+    if (dot(gradient, gradient) < 1e-12) {
+        return;
+    }
+    let n = normalize(gradient);
+    positions[i] = vec4<f32>(p + (collision.thickness - d) * n, 1.0);
+
+    let v = velocities[i].xyz;
+    let vn = dot(v, n);
+    if (vn >= 0.0) {
+        return;
+    }
+    // Coulomb friction takes tangential speed in proportion to the normal impulse
+    let vt = v - vn * n;
+    let impulse = (1.0 + collision.restitution) * -vn;
+    let slide = max(1.0 - collision.friction * impulse / max(length(vt), 1e-6), 0.0);
+    velocities[i] = vec4<f32>(vt * slide - collision.restitution * vn * n, 1.0);
+}
@@ -4,16 +4,63 @@ use std::sync::Arc;
 // use egui_wgpu::wgpu::ComputePipeline;
 use egui_wgpu::wgpu::*;
 use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+
+use crate::colliders::Colliders;
+use crate::emitter::ParticleEmitters;
+use crate::expr::Expr;
+use crate::rng::Rng;
+use crate::sampling::Samples;
+use crate::field_texture::FieldTexture;
 
 pub struct Compute {
-    pub pipeline: ComputePipeline,
+    pipelines: Pipelines,
     pub point_buffer: Arc<Buffer>,
     pub points: u32,
     pub input_bind_group: BindGroup,
+    input_bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    velocities_buffer: Buffer,
+    /// Records laid out by [`crate::attributes`], one per particle.
+    attribute_buffer: Buffer,
 
     pub inputs: Inputs,
+    /// One [`Inputs`] per substep, bound with a dynamic offset.
     pub inputs_buffer: Buffer,
+    inputs_stride: u64,
+
+    pub emitters: ParticleEmitters,
+    pub colliders: Colliders,
+}
+
+/// Entry points of compute_shader.wgsl and emitter.wgsl, rebuilt together whenever the expression changes.
+struct Pipelines {
+    simulate: ComputePipeline,
+    scatter: ComputePipeline,
+    emit: ComputePipeline,
+    clear: ComputePipeline,
+}
+
+/// Substeps that fit into one frame's inputs buffer.
+pub const MAX_SUBSTEPS: u32 = 16;
+/// Particles simulated when the GPU's buffer limits allow it.
+pub const MAX_PARTICLES: u32 = 8_388_608;
+/// Simulation time one step advances by.
+pub const TIME_STEP: f32 = 0.01;
+
+/// Particles fall towards the attractor at the origin.
+pub const MODE_ATTRACTOR: u32 = 0;
+/// Particles are passive tracers advected through the field texture.
+pub const MODE_TRACER: u32 = 1;
+/// Particles are advected through the field typed in as an [`Expr`].
+pub const MODE_EXPRESSION: u32 = 2;
+
+/// Stands in for the user's expression until one is set.
+pub(crate) const DEFAULT_FIELD_EXPR: &str = "
+fn field_expr(p: vec3<f32>, t: f32) -> vec3<f32> {
+    return vec3<f32>(0.0);
 }
+";
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -22,10 +69,53 @@ pub struct Inputs {
     pub iterations: u32,
     pub c1: f32,
     pub c2: f32,
+    pub mode: u32,
+    /// Multiplier applied to field velocities in tracer mode.
+    pub field_scale: f32,
+    /// Seeds every random draw of the simulation, see rng.wgsl.
+    pub seed: u32,
+    /// Strength of the Brownian motion added to the particles, 0 turns it off.
+    pub diffusion: f32,
+    /// Steps taken so far, keys the random draws together with the seed.
+    pub step: u32,
+    /// Emitters spawning in this step, see [`ParticleEmitters::schedule`].
+    pub emitter_offset: u32,
+    pub emitter_count: u32,
+    /// Simulation time the step advances by, negative while running backwards. Particles age by it
+    /// going forwards and hold their age going backwards.
+    pub time_step: f32,
+    /// Strength of a uniform magnetic field along y, bends charged particles in attractor mode.
+    pub magnetic_field: f32,
+    pub _padding: [u32; 3],
+}
+
+/// Where the `scatter` entry point of compute_shader.wgsl puts particle `i`: position on the unit sphere,
+/// velocity around y and charge. Starts the simulation the way a reset with `seed` does.
+pub fn scatter(i: u32, seed: u32) -> (Vec3, Vec3, f32) {
+    let mut rng = Rng::from_keys(i, 0, seed);
+    let p = rng.on_unit_sphere();
+    let charge = if rng.next_f32() < 0.5 { 1.0 } else { -1.0 };
+    (p, Vec3::new(-p.z, 0.0, p.x), charge)
+}
+
+/// Size of the largest per-particle buffer, the attribute records or the positions.
+pub fn particle_buffer_size(points: u32) -> u64 {
+    points as u64 * (crate::attributes::stride() as u64 * 4).max(std::mem::size_of::<[f32; 4]>() as u64)
+}
+
+/// [`MAX_PARTICLES`], halved until every per-particle buffer fits into `limits` and can be bound whole.
+pub fn particle_count(limits: &Limits) -> u32 {
+    let max_size = limits.max_buffer_size.min(limits.max_storage_buffer_binding_size as u64);
+    let mut points = MAX_PARTICLES;
+    while points > 1 && particle_buffer_size(points) > max_size {
+        points /= 2;
+    }
+    points
 }
 
 impl Compute {
-    pub fn new(device: &Device, point_buffer: Arc<Buffer>, points: u32) -> Self {
+    /// `point_buffer` should hold the positions [`scatter`] gives for `seed`.
+    pub fn new(device: &Device, point_buffer: Arc<Buffer>, points: u32, seed: u32, field: &FieldTexture) -> Self {
         let input_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("Input bind group layout"),
             entries: &[
@@ -44,8 +134,8 @@ impl Compute {
                     visibility: ShaderStages::COMPUTE,
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                        has_dynamic_offset: true,
+                        min_binding_size: BufferSize::new(std::mem::size_of::<Inputs>() as u64),
                     },
                     count: None,
                 },
@@ -58,7 +148,37 @@ impl Compute {
                         min_binding_size: None,
                     },
                     count: None,
-                }
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -67,101 +187,276 @@ impl Compute {
             iterations: 8,
             c1: 0.25,
             c2: 0.5,
+            mode: MODE_ATTRACTOR,
+            field_scale: 1.0,
+            seed,
+            diffusion: 0.0,
+            step: 0,
+            emitter_offset: 0,
+            emitter_count: 0,
+            time_step: TIME_STEP,
+            magnetic_field: 0.0,
+            _padding: [0; 3],
         };
 
-        let inputs_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        let inputs_stride = (std::mem::size_of::<Inputs>() as u64).next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let inputs_buffer = device.create_buffer(&BufferDescriptor {
             label: Some("Inputs Buffer"),
-            contents: bytemuck::cast_slice(&[inputs]),
+            size: inputs_stride * MAX_SUBSTEPS as u64,
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
 
-        let velocities_buffer_rust: Vec<[f32; 4]> = (0..8_388_608).map(|i| {
-            let phi = std::f32::consts::PI * (3.0 - (5.0f32).sqrt()); // Golden angle
-            let y = 1.0 - (2.0 * i as f32 / 8_388_608.0); // y-coordinate from -1 to 1
-            let radius = (1.0 - y * y).sqrt(); // radius at this y level
-            let theta = phi * i as f32; // azimuthal angle
-
-            let x = radius * theta.cos();
-            let z = radius * theta.sin();
-
-            // Perpendicular vector to the radius
-            let perp_x = -z;
-            let perp_z = x;
-
-            [perp_x, 0.0, perp_z, 1.0]
-        }).collect::<Vec<_>>();
+        let scattered: Vec<(Vec3, Vec3, f32)> = (0..points).map(|i| scatter(i, seed)).collect();
+        let velocities_buffer_rust: Vec<[f32; 4]> = scattered.iter().map(|(_, v, _)| v.extend(1.0).to_array()).collect();
 
         let velocities_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Inputs Buffer"),
             contents: bytemuck::cast_slice(velocities_buffer_rust.as_slice()),
-            usage: BufferUsages::STORAGE,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
             // size: point_buffer.size(),
             // mapped_at_creation: false,
         });
 
-        let input_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Input Bind Group"),
-            layout: &input_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: point_buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: inputs_buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: velocities_buffer.as_entire_binding(),
-                }
-            ],
+        // Half the particles carry a positive and half a negative charge
+        let mut attributes = crate::attributes::default_data(points);
+        let charge = crate::attributes::offset("charge").unwrap() as usize;
+        for (record, (_, _, q)) in attributes.chunks_exact_mut(crate::attributes::stride() as usize).zip(&scattered) {
+            record[charge] = q.to_bits();
+        }
+        let attribute_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Particle Attribute Buffer"),
+            contents: bytemuck::cast_slice(&attributes),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
         });
 
+        let input_bind_group = Self::create_bind_group(device, &input_bind_group_layout, &point_buffer, &inputs_buffer, &velocities_buffer, &attribute_buffer, field);
+
+        let emitters = ParticleEmitters::new(device, points);
+        let colliders = Colliders::new(device);
+
         let compute_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Simulation pipeline layout"),
             bind_group_layouts: &[
-                &input_bind_group_layout
+                &input_bind_group_layout,
+                &emitters.bind_group_layout,
+                &colliders.bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
 
-        let shader = device.create_shader_module(include_wgsl!("compute_shader.wgsl"));
-
-        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &shader,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        let pipelines = Self::create_pipelines(device, &compute_pipeline_layout, DEFAULT_FIELD_EXPR);
 
         Self {
-            pipeline,
+            pipelines,
             point_buffer,
             input_bind_group,
+            input_bind_group_layout,
+            pipeline_layout: compute_pipeline_layout,
+            velocities_buffer,
+            attribute_buffer,
             inputs,
             inputs_buffer,
+            inputs_stride,
             points,
+            emitters,
+            colliders,
         }
     }
 
-    pub fn compute(&mut self, encoder: &mut CommandEncoder, queue: &Queue) {
-        {
-            self.inputs.time += 0.01;
-            queue.write_buffer(&self.inputs_buffer, 0, bytemuck::cast_slice(&[self.inputs]));
+    /// The simulation shader with `field_expr` spliced in at the end.
+    fn create_pipelines(device: &Device, layout: &PipelineLayout, field_expr: &str) -> Pipelines {
+        let source = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            include_str!("field.wgsl"),
+            include_str!("sdf.wgsl"),
+            include_str!("rng.wgsl"),
+            crate::attributes::wgsl(),
+            include_str!("compute_shader.wgsl"),
+            include_str!("emitter.wgsl"),
+            include_str!("collision.wgsl"),
+            field_expr,
+        );
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("compute_shader.wgsl"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
 
-            let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+        let pipeline = |label, entry_point| device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module: &shader,
+            entry_point,
+            compilation_options: Default::default(),
+        });
+        Pipelines {
+            simulate: pipeline("Compute Pipeline", "main"),
+            scatter: pipeline("Scatter Pipeline", "scatter"),
+            emit: pipeline("Emit Pipeline", "emit"),
+            clear: pipeline("Clear Particles Pipeline", "clear"),
+        }
+    }
 
-            compute_pass.set_bind_group(0, &self.input_bind_group, &[]);
-            compute_pass.set_pipeline(&self.pipeline);
-            compute_pass.dispatch_workgroups((self.points as f32 / 256_f32).ceil() as u32, 1, 1);
+    /// Recompiles the simulation shader around `expr`, keeping the old pipeline if that fails.
+    pub fn set_expression(&mut self, device: &Device, expr: &Expr) -> anyhow::Result<()> {
+        device.push_error_scope(ErrorFilter::Validation);
+        let pipelines = Self::create_pipelines(device, &self.pipeline_layout, &expr.to_wgsl());
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            anyhow::bail!("{error}");
+        }
+        self.pipelines = pipelines;
+        Ok(())
+    }
 
-            drop(compute_pass);
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        point_buffer: &Buffer,
+        inputs_buffer: &Buffer,
+        velocities_buffer: &Buffer,
+        attribute_buffer: &Buffer,
+        field: &FieldTexture,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Input Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: point_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Buffer(BufferBinding {
+                        buffer: inputs_buffer,
+                        offset: 0,
+                        size: BufferSize::new(std::mem::size_of::<Inputs>() as u64),
+                    }),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: velocities_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&field.view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: field.params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: attribute_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn velocity_buffer(&self) -> &Buffer {
+        &self.velocities_buffer
+    }
+
+    pub fn attribute_buffer(&self) -> &Buffer {
+        &self.attribute_buffer
+    }
+
+    /// Points the tracers at a different field texture, e.g. after loading one with other dimensions.
+    pub fn set_field(&mut self, device: &Device, field: &FieldTexture) {
+        self.input_bind_group = Self::create_bind_group(
+            device,
+            &self.input_bind_group_layout,
+            &self.point_buffer,
+            &self.inputs_buffer,
+            &self.velocities_buffer,
+            &self.attribute_buffer,
+            field,
+        );
+    }
+
+    /// Runs `steps` substeps, each with its own time. A negative `direction` runs them backwards.
+    pub fn compute(&mut self, encoder: &mut CommandEncoder, queue: &Queue, steps: u32, direction: f32) {
+        let steps = steps.min(MAX_SUBSTEPS);
+        if steps == 0 {
+            return;
+        }
+        let mut spawns = Vec::with_capacity(steps as usize);
+        for step in 0..steps {
+            self.inputs.time += TIME_STEP * direction;
+            self.inputs.step = if direction < 0.0 { self.inputs.step.wrapping_sub(1) } else { self.inputs.step.wrapping_add(1) };
+            let emit = self.emitters.schedule(queue, step, TIME_STEP * direction);
+            self.inputs.emitter_offset = emit.emitter_offset;
+            self.inputs.emitter_count = emit.emitter_count;
+            spawns.push(emit.spawns);
+
+            let mut inputs = self.inputs;
+            // c1 is the integration step in the shader
+            inputs.c1 *= direction;
+            inputs.time_step *= direction;
+            queue.write_buffer(&self.inputs_buffer, step as u64 * self.inputs_stride, bytemuck::cast_slice(&[inputs]));
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+        compute_pass.set_bind_group(1, &self.emitters.bind_group, &[]);
+        compute_pass.set_bind_group(2, &self.colliders.bind_group, &[]);
+        for (step, spawns) in spawns.into_iter().enumerate() {
+            compute_pass.set_bind_group(0, &self.input_bind_group, &[(step as u64 * self.inputs_stride) as u32]);
+            compute_pass.set_pipeline(&self.pipelines.simulate);
+            compute_pass.dispatch_workgroups((self.points as f32 / 256_f32).ceil() as u32, 1, 1);
+            if spawns > 0 {
+                compute_pass.set_pipeline(&self.pipelines.emit);
+                compute_pass.dispatch_workgroups(spawns.div_ceil(64), 1, 1);
+            }
         }
     }
 
-    pub fn update_inputs(&mut self, queue: &Queue){
+    /// Restarts the clock from `seed`, the same seed always gives the same run. Without emitters the
+    /// particles are scattered over the unit sphere, with them every particle starts out dead.
+    pub fn reset(&mut self, encoder: &mut CommandEncoder, queue: &Queue, seed: u32) {
+        self.inputs.seed = seed;
+        self.inputs.time = 0.0;
+        self.inputs.step = 0;
         queue.write_buffer(&self.inputs_buffer, 0, bytemuck::cast_slice(&[self.inputs]));
+        self.emitters.reset(queue);
+
+        let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+        compute_pass.set_pipeline(if self.emitters.enabled { &self.pipelines.clear } else { &self.pipelines.scatter });
+        compute_pass.set_bind_group(0, &self.input_bind_group, &[0]);
+        compute_pass.set_bind_group(1, &self.emitters.bind_group, &[]);
+        compute_pass.set_bind_group(2, &self.colliders.bind_group, &[]);
+        compute_pass.dispatch_workgroups((self.points as f32 / 256_f32).ceil() as u32, 1, 1);
+    }
+
+    /// Resets like [`Compute::reset`], then starts the particles from `samples`, e.g. drawn from the
+    /// scene's meshes by [`crate::sampling`]. The reset pass is submitted on its own first, buffer
+    /// writes run before any commands of the submission they go with and it would overwrite them.
+    pub fn reset_to(&mut self, device: &Device, queue: &Queue, seed: u32, samples: &Samples) {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("Particle Reset Encoder") });
+        self.reset(&mut encoder, queue, seed);
+        queue.submit(Some(encoder.finish()));
+
+        let count = samples.positions.len().min(self.points as usize);
+        queue.write_buffer(&self.point_buffer, 0, bytemuck::cast_slice(&samples.positions[..count]));
+        queue.write_buffer(&self.velocities_buffer, 0, bytemuck::cast_slice(&samples.velocities[..count]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn particle_count_fits_the_binding_limit() {
+        let limits = Limits {
+            max_buffer_size: 1 << 30,
+            max_storage_buffer_binding_size: 1 << 30,
+            ..Limits::default()
+        };
+        assert_eq!(particle_count(&limits), MAX_PARTICLES);
+        // The wgpu defaults only bind 128 MiB
+        let points = particle_count(&Limits::default());
+        assert!(particle_buffer_size(points) <= Limits::default().max_storage_buffer_binding_size as u64);
+        assert_eq!(particle_buffer_size(points * 2), particle_buffer_size(points) * 2);
+        assert!(particle_buffer_size(points * 2) > Limits::default().max_storage_buffer_binding_size as u64);
     }
 }
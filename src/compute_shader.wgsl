@@ -11,13 +11,71 @@ struct Inputs {
     iterations: u32,
     DT: f32,
     chance_2: f32,
+    mode: u32,
+    field_scale: f32,
+    seed: u32,
+    diffusion: f32,
+    step: u32,
+    emitter_offset: u32,
+    emitter_count: u32,
+    time_step: f32,
+    magnetic_field: f32,
 }
 
 @group(0)
 @binding(1)
 var<uniform> inputs: Inputs;
 
-fn rand11(n: f32) -> f32 { return fract(sin(n) * 43758.5453123); }
+@group(0)
+@binding(3)
+var field: texture_3d<f32>;
+
+@group(0)
+@binding(4)
+var<uniform> field_params: FieldParams;
+
+// `ParticleAttributes` is generated by attributes.rs
+@group(0)
+@binding(5)
+var<storage, read_write> attributes: array<ParticleAttributes>;
+
+const MODE_ATTRACTOR: u32 = 0;
+const MODE_TRACER: u32 = 1;
+const MODE_EXPRESSION: u32 = 2;
+
+fn sample_field(p: vec3f) -> vec3f {
+    return sample_field_texture(field, field_params, p) * inputs.field_scale;
+}
+
+// `field_expr` is generated from the expression typed into the UI and appended to this file
+fn velocity_at(p: vec3f, t: f32) -> vec3f {
+    if (inputs.mode == MODE_EXPRESSION) {
+        return field_expr(p, t) * inputs.field_scale;
+    }
+    return sample_field(p);
+}
+
+// Brownian displacement over one step, keyed by particle, step and seed so a run repeats exactly
+fn diffuse(i: u32) -> vec3f {
+    if (inputs.diffusion <= 0.0) {
+        return vec3f(0.0);
+    }
+    var state = rng_seed(i, inputs.step, inputs.seed);
+    return sqrt(2.0 * inputs.diffusion * abs(inputs.DT)) * rng_gaussian3(&state);
+}
+
+fn advect_tracer(i: u32) {
+    let pos = positions[i].xyz;
+    let h = inputs.DT;
+    let t = inputs.time;
+
+    // RK2 midpoint step
+    let v1 = velocity_at(pos, t);
+    let v2 = velocity_at(pos + 0.5 * h * v1, t + 0.5 * h);
+
+    velocities[i] = vec4<f32>(v2, 1.0);
+    positions[i] = vec4<f32>(pos + h * v2 + diffuse(i), 1.0);
+}
 
 const DT: f32 = 0.0001;
 
@@ -25,7 +83,15 @@ const DT: f32 = 0.0001;
 @workgroup_size(256, 1, 1)
 fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
     let i = global_invocation_id.x;
+    if (!age_particle(i)) {
+        return;
+    }
 
+    if (inputs.mode == MODE_TRACER || inputs.mode == MODE_EXPRESSION) {
+        advect_tracer(i);
+        collide(i);
+        return;
+    }
 
     var pos = positions[i].xyz;
 
@@ -43,12 +109,34 @@ fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
     }
 
     let velocity = velocities[i].xyz;
+    // Lorentz force of a uniform magnetic field along y
+    let magnetic = vec3<f32>(0.0, inputs.magnetic_field, 0.0);
+    acceleration += attributes[i].charge / attributes[i].mass * cross(velocity, magnetic);
     let new_velocity = velocity + acceleration * inputs.DT;
 
     velocities[i] = vec4<f32>(new_velocity, 1.0);
 
-    let new_pos = pos + new_velocity * inputs.DT;
+    let new_pos = pos + new_velocity * inputs.DT + diffuse(i);
     positions[i] = vec4<f32>(new_pos, 1.0);
+    collide(i);
+}
+
+// Seeded initial conditions: uniform on the unit sphere, circling the y axis like the default spiral
+@compute
+@workgroup_size(256, 1, 1)
+fn scatter(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let i = global_invocation_id.x;
+    if (i >= arrayLength(&positions)) {
+        return;
+    }
+
+    var state = rng_seed(i, 0u, inputs.seed);
+    let p = rng_on_unit_sphere(&state);
+    positions[i] = vec4<f32>(p, 1.0);
+    velocities[i] = vec4<f32>(-p.z, 0.0, p.x, 1.0);
+    var a = default_attributes();
+    a.charge = select(-1.0, 1.0, rng_float(&state) < 0.5);
+    attributes[i] = a;
 }
 
 
@@ -0,0 +1,83 @@
+// Compacts the indices of the points inside the view frustum, optionally thinning out distant ones,
+// and counts them straight into the arguments of the indirect draw.
+
+struct CullParams {
+    num_points: u32,
+    lod: u32,
+    // Points closer than lod_near are all kept, past lod_far only lod_min_fraction of them
+    lod_near: f32,
+    lod_far: f32,
+    lod_min_fraction: f32,
+}
+
+struct DrawIndexedIndirectArgs {
+    index_count: atomic<u32>,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> view_proj: mat4x4<f32>;
+@group(0) @binding(1)
+var<uniform> params: CullParams;
+@group(0) @binding(2)
+var<storage, read> points: array<vec4<f32>>;
+@group(0) @binding(3)
+var<storage, read_write> indices: array<u32>;
+@group(0) @binding(4)
+var<storage, read_write> args: DrawIndexedIndirectArgs;
+
+var<workgroup> local_count: atomic<u32>;
+var<workgroup> local_base: u32;
+
+fn is_visible(i: u32) -> bool {
+    if (i >= params.num_points) {
+        return false;
+    }
+    // Dead particles are parked with w = 0
+    let point = points[i];
+    if (point.w == 0.0) {
+        return false;
+    }
+    let clip = view_proj * point;
+    // Same test the rasterizer does, the depth range is [0, w]
+    if (clip.w <= 0.0 || any(abs(clip.xy) > vec2<f32>(clip.w)) || clip.z < 0.0 || clip.z > clip.w) {
+        return false;
+    }
+    if (params.lod != 0u) {
+        // w is the view depth for perspective projections and 1 for orthographic ones
+        let keep = mix(1.0, params.lod_min_fraction, smoothstep(params.lod_near, params.lod_far, clip.w));
+        // Keyed by the index alone so the decimation doesn't flicker from frame to frame
+        return rng_unit_float(pcg_hash(i)) < keep;
+    }
+    return true;
+}
+
+@compute
+@workgroup_size(256, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let i = global_invocation_id.x;
+    if (i == 0u) {
+        args.instance_count = 1u;
+    }
+
+    // One global atomic per workgroup instead of one per point
+    let visible = is_visible(i);
+    var slot = 0u;
+    if (visible) {
+        slot = atomicAdd(&local_count, 1u);
+    }
+    workgroupBarrier();
+    if (local_index == 0u) {
+        local_base = atomicAdd(&args.index_count, atomicLoad(&local_count));
+    }
+    let base = workgroupUniformLoad(&local_base);
+    if (visible) {
+        indices[base + slot] = i;
+    }
+}
@@ -0,0 +1,380 @@
+use std::path::Path;
+
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages, Device, Queue, ShaderStages};
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+
+/// Emitters that can spawn in the same step.
+pub const MAX_EMITTERS: usize = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EmitterShape {
+    Point,
+    /// Uniformly inside a ball of `radius`.
+    Sphere,
+    /// Uniformly on a disk of `radius` facing `axis`.
+    Disk,
+    /// Area weighted on the triangles of the emitter's mesh, scaled by `radius`.
+    Mesh,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VelocityDistribution {
+    /// Inside a cone of half angle `spread` around `axis`.
+    Directional,
+    /// Away from the center, or along the surface normal for disks and meshes.
+    Normal,
+    /// Uniformly in all directions.
+    Random,
+}
+
+/// Triangles to emit from, e.g. loaded from an OBJ or glTF file.
+#[derive(Debug, Clone)]
+pub struct EmitterMesh {
+    pub name: String,
+    pub triangles: Vec<[Vec3; 3]>,
+}
+
+impl EmitterMesh {
+    /// Reads the triangles of every shape in an OBJ or glTF file.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let triangles = crate::models::load_triangles(path)?;
+        Ok(Self {
+            name: path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned()),
+            triangles,
+        })
+    }
+
+    /// Running sum of the triangle areas normalized to end at 1, searched on the GPU to pick triangles.
+    fn area_cdf(&self) -> Vec<f32> {
+        let mut total = 0.0;
+        let mut cdf: Vec<f32> = self.triangles.iter()
+            .map(|[a, b, c]| {
+                total += 0.5 * (*b - *a).cross(*c - *a).length();
+                total
+            })
+            .collect();
+        if total > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= total);
+        }
+        cdf
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    pub enabled: bool,
+    pub shape: EmitterShape,
+    pub position: Vec3,
+    pub axis: Vec3,
+    pub radius: f32,
+    pub mesh: Option<EmitterMesh>,
+    /// Particles per unit of simulation time.
+    pub rate: f32,
+    pub velocity: VelocityDistribution,
+    pub speed: f32,
+    /// Relative random variation of the speed.
+    pub speed_jitter: f32,
+    /// Cone half angle in radians for [`VelocityDistribution::Directional`].
+    pub spread: f32,
+    /// Lifetimes are uniform between the two, in simulation time.
+    pub lifetime: [f32; 2],
+    /// Color attribute of the spawned particles.
+    pub color: [f32; 4],
+
+    /// Fractional particle carried over to the next step.
+    accumulator: f32,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shape: EmitterShape::Point,
+            position: Vec3::ZERO,
+            axis: Vec3::Y,
+            radius: 0.1,
+            mesh: None,
+            rate: 100_000.0,
+            velocity: VelocityDistribution::Directional,
+            speed: 1.0,
+            speed_jitter: 0.1,
+            spread: 0.3,
+            lifetime: [1.0, 2.0],
+            color: [1.0, 0.6, 0.2, 1.0],
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl Emitter {
+    /// Editor for one emitter, mesh loading is left to the caller.
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.shape, EmitterShape::Point, "Point");
+            ui.radio_value(&mut self.shape, EmitterShape::Sphere, "Sphere");
+            ui.radio_value(&mut self.shape, EmitterShape::Disk, "Disk");
+            ui.add_enabled_ui(self.mesh.is_some(), |ui| {
+                ui.radio_value(&mut self.shape, EmitterShape::Mesh, "Mesh");
+            });
+        });
+        let vec3 = |ui: &mut egui::Ui, label: &str, v: &mut Vec3| {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.add(egui::DragValue::new(&mut v.x).speed(0.01));
+                ui.add(egui::DragValue::new(&mut v.y).speed(0.01));
+                ui.add(egui::DragValue::new(&mut v.z).speed(0.01));
+            });
+        };
+        vec3(ui, "Position", &mut self.position);
+        vec3(ui, "Axis", &mut self.axis);
+        let radius_label = if self.shape == EmitterShape::Mesh { "Scale" } else { "Radius" };
+        ui.add(egui::Slider::new(&mut self.radius, 0.0..=2.0).text(radius_label));
+        ui.add(egui::Slider::new(&mut self.rate, 0.0..=10_000_000.0).logarithmic(true).text("Particles per second"));
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.velocity, VelocityDistribution::Directional, "Along axis");
+            ui.radio_value(&mut self.velocity, VelocityDistribution::Normal, "Outward");
+            ui.radio_value(&mut self.velocity, VelocityDistribution::Random, "Random");
+        });
+        ui.add(egui::Slider::new(&mut self.speed, 0.0..=10.0).text("Speed"));
+        ui.add(egui::Slider::new(&mut self.speed_jitter, 0.0..=1.0).text("Speed variation"));
+        if self.velocity == VelocityDistribution::Directional {
+            ui.add(egui::Slider::new(&mut self.spread, 0.0..=std::f32::consts::PI).text("Spread"));
+        }
+        ui.add(egui::Slider::new(&mut self.lifetime[0], 0.01..=20.0).logarithmic(true).text("Shortest life"));
+        ui.add(egui::Slider::new(&mut self.lifetime[1], 0.01..=20.0).logarithmic(true).text("Longest life"));
+        self.lifetime[1] = self.lifetime[1].max(self.lifetime[0]);
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            ui.color_edit_button_rgba_unmultiplied(&mut self.color);
+        });
+    }
+}
+
+/// Mirrors `EmitterParams` in emitter.wgsl, one per emitter and step.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EmitterParams {
+    position: [f32; 3],
+    shape: u32,
+    axis: [f32; 3],
+    radius: f32,
+    velocity: u32,
+    speed: f32,
+    speed_jitter: f32,
+    spread: f32,
+    lifetime: [f32; 2],
+    /// Spawns of this step before this emitter's.
+    first: u32,
+    count: u32,
+    triangle_offset: u32,
+    triangle_count: u32,
+    /// Packed like the color attribute.
+    color: u32,
+    _padding: u32,
+}
+
+/// Which emitters spawn in one step, see [`ParticleEmitters::schedule`].
+pub struct EmitStep {
+    pub emitter_offset: u32,
+    pub emitter_count: u32,
+    pub spawns: u32,
+}
+
+/// Spawns particles from a list of emitters and recycles the ones that die. Particles age until they
+/// reach their lifetime attribute, then go on a free list on the GPU which the emit pass takes them from.
+/// While disabled every particle lives forever, like before emitters existed.
+pub struct ParticleEmitters {
+    pub emitters: Vec<Emitter>,
+    pub enabled: bool,
+
+    free_list_buffer: Buffer,
+    counter_buffer: Buffer,
+    params_buffer: Buffer,
+    triangle_buffer: Buffer,
+    cdf_buffer: Buffer,
+    /// Triangle range of every emitter's mesh in the triangle buffer, rebuilt by [`ParticleEmitters::upload_meshes`].
+    mesh_ranges: Vec<Option<(u32, u32)>>,
+
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+
+    capacity: u32,
+}
+
+impl ParticleEmitters {
+    pub fn new(device: &Device, capacity: u32) -> Self {
+        let storage = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Emitter Bind Group Layout"),
+            entries: &[
+                storage(0, false),
+                storage(1, false),
+                storage(2, true),
+                storage(3, true),
+                storage(4, true),
+            ],
+        });
+
+        let free_list_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Particle Free List Buffer"),
+            size: capacity as u64 * 4,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let counter_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Particle Counter Buffer"),
+            contents: bytemuck::cast_slice(&[0i32; 4]),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        });
+        let params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Emitter Params Buffer"),
+            size: (crate::compute::MAX_SUBSTEPS as usize * MAX_EMITTERS * std::mem::size_of::<EmitterParams>()) as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (triangle_buffer, cdf_buffer) = Self::create_mesh_buffers(device, &[[0.0; 4]; 3], &[1.0]);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, [&free_list_buffer, &counter_buffer, &params_buffer, &triangle_buffer, &cdf_buffer]);
+
+        Self {
+            emitters: vec![Emitter::default()],
+            enabled: false,
+            free_list_buffer,
+            counter_buffer,
+            params_buffer,
+            triangle_buffer,
+            cdf_buffer,
+            mesh_ranges: Vec::new(),
+            bind_group_layout,
+            bind_group,
+            capacity,
+        }
+    }
+
+    fn create_mesh_buffers(device: &Device, vertices: &[[f32; 4]], cdf: &[f32]) -> (Buffer, Buffer) {
+        let triangle_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Emitter Triangle Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: BufferUsages::STORAGE,
+        });
+        let cdf_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Emitter Area Buffer"),
+            contents: bytemuck::cast_slice(cdf),
+            usage: BufferUsages::STORAGE,
+        });
+        (triangle_buffer, cdf_buffer)
+    }
+
+    fn create_bind_group(device: &Device, layout: &BindGroupLayout, buffers: [&Buffer; 5]) -> BindGroup {
+        let entries: Vec<BindGroupEntry> = buffers.iter()
+            .enumerate()
+            .map(|(binding, buffer)| BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Emitter Bind Group"),
+            layout,
+            entries: &entries,
+        })
+    }
+
+    /// Copies the meshes of all emitters to the GPU, call after setting or removing one.
+    pub fn upload_meshes(&mut self, device: &Device) {
+        let mut vertices = Vec::new();
+        let mut cdf = Vec::new();
+        self.mesh_ranges = self.emitters.iter()
+            .map(|emitter| {
+                let mesh = emitter.mesh.as_ref()?;
+                let offset = cdf.len() as u32;
+                vertices.extend(mesh.triangles.iter().flatten().map(|v| v.extend(1.0).to_array()));
+                cdf.extend(mesh.area_cdf());
+                Some((offset, mesh.triangles.len() as u32))
+            })
+            .collect();
+        if cdf.is_empty() {
+            vertices = vec![[0.0; 4]; 3];
+            cdf = vec![1.0];
+        }
+
+        (self.triangle_buffer, self.cdf_buffer) = Self::create_mesh_buffers(device, &vertices, &cdf);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, [
+            &self.free_list_buffer,
+            &self.counter_buffer,
+            &self.params_buffer,
+            &self.triangle_buffer,
+            &self.cdf_buffer,
+        ]);
+    }
+
+    /// Works out how many particles each emitter spawns in substep `step` and writes their parameters.
+    /// Nothing spawns while disabled or running backwards.
+    pub fn schedule(&mut self, queue: &Queue, step: u32, time_step: f32) -> EmitStep {
+        let emitter_offset = step * MAX_EMITTERS as u32;
+        let mut params = Vec::new();
+        let mut spawns = 0u32;
+        if self.enabled && time_step > 0.0 {
+            for (index, emitter) in self.emitters.iter_mut().enumerate().filter(|(_, e)| e.enabled).take(MAX_EMITTERS) {
+                let shape = match (emitter.shape, self.mesh_ranges.get(index).copied().flatten()) {
+                    (EmitterShape::Mesh, None) => continue,
+                    (shape, _) => shape,
+                };
+                emitter.accumulator += emitter.rate * time_step;
+                let count = (emitter.accumulator.floor() as u32).min(self.capacity - spawns);
+                emitter.accumulator -= emitter.accumulator.floor();
+
+                let (triangle_offset, triangle_count) = self.mesh_ranges.get(index).copied().flatten().unwrap_or((0, 0));
+                params.push(EmitterParams {
+                    position: emitter.position.to_array(),
+                    shape: shape as u32,
+                    axis: emitter.axis.try_normalize().unwrap_or(Vec3::Y).to_array(),
+                    radius: emitter.radius,
+                    velocity: emitter.velocity as u32,
+                    speed: emitter.speed,
+                    speed_jitter: emitter.speed_jitter,
+                    spread: emitter.spread,
+                    lifetime: emitter.lifetime,
+                    first: spawns,
+                    count,
+                    triangle_offset,
+                    triangle_count,
+                    color: u32::from_le_bytes(emitter.color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)),
+                    _padding: 0,
+                });
+                spawns += count;
+            }
+        }
+        if !params.is_empty() {
+            let offset = emitter_offset as u64 * std::mem::size_of::<EmitterParams>() as u64;
+            queue.write_buffer(&self.params_buffer, offset, bytemuck::cast_slice(&params));
+        }
+
+        EmitStep {
+            emitter_offset,
+            emitter_count: params.len() as u32,
+            spawns,
+        }
+    }
+
+    /// Marks every particle as free, the clear pass has to run before the next step.
+    pub fn reset(&mut self, queue: &Queue) {
+        for emitter in &mut self.emitters {
+            emitter.accumulator = 0.0;
+        }
+        let free = if self.enabled { self.capacity as i32 } else { 0 };
+        queue.write_buffer(&self.counter_buffer, 0, bytemuck::cast_slice(&[free]));
+    }
+}
@@ -0,0 +1,190 @@
+// Particle lifecycle: aging, death onto the free list and spawning from emitters. Shares `positions`,
+// `velocities`, `attributes` and `inputs` with compute_shader.wgsl.
+
+struct EmitterParams {
+    position: vec3<f32>,
+    shape: u32,
+    axis: vec3<f32>,
+    radius: f32,
+    velocity: u32,
+    speed: f32,
+    speed_jitter: f32,
+    spread: f32,
+    lifetime: vec2<f32>,
+    first: u32,
+    count: u32,
+    triangle_offset: u32,
+    triangle_count: u32,
+    color: u32,
+}
+
+struct Counters {
+    // Signed so spawns can overdraw it and put back what they couldn't take
+    free_count: atomic<i32>,
+}
+
+@group(1) @binding(0)
+var<storage, read_write> free_list: array<u32>;
+@group(1) @binding(1)
+var<storage, read_write> counters: Counters;
+@group(1) @binding(2)
+var<storage, read> emitters: array<EmitterParams>;
+// Three vertices per triangle
+@group(1) @binding(3)
+var<storage, read> triangles: array<vec4<f32>>;
+@group(1) @binding(4)
+var<storage, read> triangle_cdf: array<f32>;
+
+const SHAPE_POINT: u32 = 0;
+const SHAPE_SPHERE: u32 = 1;
+const SHAPE_DISK: u32 = 2;
+const SHAPE_MESH: u32 = 3;
+
+const VELOCITY_DIRECTIONAL: u32 = 0;
+const VELOCITY_NORMAL: u32 = 1;
+const VELOCITY_RANDOM: u32 = 2;
+
+fn kill_particle(i: u32) {
+    positions[i] = vec4<f32>(0.0);
+    let slot = atomicAdd(&counters.free_count, 1);
+    free_list[slot] = i;
+}
+
+// Ages a live particle by one step, returns whether it's still alive afterwards. A particle is dead
+// once its age reaches its lifetime. Nothing ages while the clock runs backwards, the emitters can't
+// bring dead particles back so the living ones mustn't die either.
+fn age_particle(i: u32) -> bool {
+    if (i >= arrayLength(&attributes)) {
+        return false;
+    }
+    let lifetime = attributes[i].lifetime;
+    if (attributes[i].age >= lifetime) {
+        return false;
+    }
+    let age = attributes[i].age + max(inputs.time_step, 0.0);
+    attributes[i].age = age;
+    if (age >= lifetime) {
+        kill_particle(i);
+        return false;
+    }
+    return true;
+}
+
+// Duff et al., "Building an Orthonormal Basis, Revisited"
+fn orthonormal_basis(n: vec3<f32>) -> mat3x3<f32> {
+    let s = select(-1.0, 1.0, n.z >= 0.0);
+    let a = -1.0 / (s + n.z);
+    let b = n.x * n.y * a;
+    return mat3x3<f32>(
+        vec3<f32>(1.0 + s * n.x * n.x * a, s * b, -s * n.x),
+        vec3<f32>(b, s + n.y * n.y * a, -n.y),
+        n,
+    );
+}
+
+// Area weighted point on the emitter's mesh, returns the normal through `normal`
+fn sample_mesh(emitter: EmitterParams, state: ptr<function, u32>, normal: ptr<function, vec3<f32>>) -> vec3<f32> {
+    // First triangle whose running area reaches u
+    let u = rng_float(state);
+    var lo = emitter.triangle_offset;
+    var hi = emitter.triangle_offset + emitter.triangle_count - 1u;
+    while (lo < hi) {
+        let mid = (lo + hi) / 2u;
+        if (triangle_cdf[mid] < u) {
+            lo = mid + 1u;
+        } else {
+            hi = mid;
+        }
+    }
+    let a = triangles[3u * lo].xyz;
+    let b = triangles[3u * lo + 1u].xyz;
+    let c = triangles[3u * lo + 2u].xyz;
+    *normal = normalize(cross(b - a, c - a));
+
+    let r = sqrt(rng_float(state));
+    let s = rng_float(state);
+    return (1.0 - r) * a + r * (1.0 - s) * b + r * s * c;
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn emit(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let n = global_invocation_id.x;
+
+    var e = inputs.emitter_offset;
+    let end = inputs.emitter_offset + inputs.emitter_count;
+    loop {
+        if (e >= end) {
+            return;
+        }
+        if (n < emitters[e].first + emitters[e].count) {
+            break;
+        }
+        e += 1u;
+    }
+    let emitter = emitters[e];
+
+    let slot = atomicSub(&counters.free_count, 1) - 1;
+    if (slot < 0) {
+        atomicAdd(&counters.free_count, 1);
+        return;
+    }
+    let i = free_list[slot];
+
+    // Keyed by the spawn rather than the slot, which depends on the order particles died in
+    var state = rng_seed(n, inputs.step, inputs.seed ^ 0x9e3779b9u);
+    let basis = orthonormal_basis(emitter.axis);
+    var p = vec3<f32>(0.0);
+    var normal = rng_on_unit_sphere(&state);
+    switch (emitter.shape) {
+        case SHAPE_SPHERE: {
+            p = emitter.radius * normal * pow(rng_float(&state), 1.0 / 3.0);
+        }
+        case SHAPE_DISK: {
+            let r = emitter.radius * sqrt(rng_float(&state));
+            let phi = rng_range(&state, 0.0, 6.283185307);
+            p = basis * vec3<f32>(r * cos(phi), r * sin(phi), 0.0);
+            normal = emitter.axis;
+        }
+        case SHAPE_MESH: {
+            p = emitter.radius * sample_mesh(emitter, &state, &normal);
+        }
+        default: {}
+    }
+
+    var direction = rng_on_unit_sphere(&state);
+    if (emitter.velocity == VELOCITY_DIRECTIONAL) {
+        let cos_theta = mix(1.0, cos(emitter.spread), rng_float(&state));
+        let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+        let phi = rng_range(&state, 0.0, 6.283185307);
+        direction = basis * vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+    } else if (emitter.velocity == VELOCITY_NORMAL) {
+        direction = normal;
+    }
+    let speed = emitter.speed * (1.0 + emitter.speed_jitter * rng_range(&state, -1.0, 1.0));
+
+    var a = default_attributes();
+    a.lifetime = mix(emitter.lifetime.x, emitter.lifetime.y, rng_float(&state));
+    a.charge = select(-1.0, 1.0, rng_float(&state) < 0.5);
+    a.color = emitter.color;
+
+    positions[i] = vec4<f32>(emitter.position + p, 1.0);
+    velocities[i] = vec4<f32>(direction * speed, 1.0);
+    attributes[i] = a;
+}
+
+// Kills every particle and puts all of them on the free list, the counter is set from the CPU
+@compute
+@workgroup_size(256, 1, 1)
+fn clear(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let i = global_invocation_id.x;
+    if (i >= arrayLength(&attributes)) {
+        return;
+    }
+    var a = default_attributes();
+    a.lifetime = 0.0;
+    positions[i] = vec4<f32>(0.0);
+    velocities[i] = vec4<f32>(0.0);
+    attributes[i] = a;
+    free_list[i] = i;
+}
@@ -0,0 +1,750 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail};
+use glam::Vec3;
+
+use crate::vector_field::VectorField;
+
+/// A vector field typed in at runtime, e.g. `vec3(-y, x, 0.1*z)` or `curl(noise(p))`.
+///
+/// Variables are `x`, `y`, `z`, `p` (the position as a vector), `t` (time) and `pi`.
+/// The same expression can be turned into WGSL for the particles or evaluated on the CPU.
+/// `^` takes negative bases only with integer exponents, anything else is NaN on the CPU and
+/// undefined on the GPU.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    root: Node,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Type {
+    Scalar,
+    Vec3,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f32),
+    Var(Var),
+    Neg(Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+    Swizzle(Box<Node>, usize),
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Var {
+    X,
+    Y,
+    Z,
+    P,
+    T,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Func {
+    Vec3,
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Log,
+    Sqrt,
+    Abs,
+    Length,
+    Normalize,
+    Dot,
+    Cross,
+    Min,
+    Max,
+    Atan2,
+    Noise,
+    Curl,
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "vec3" => Func::Vec3,
+            "sin" => Func::Sin,
+            "cos" => Func::Cos,
+            "tan" => Func::Tan,
+            "exp" => Func::Exp,
+            "log" => Func::Log,
+            "sqrt" => Func::Sqrt,
+            "abs" => Func::Abs,
+            "length" => Func::Length,
+            "normalize" => Func::Normalize,
+            "dot" => Func::Dot,
+            "cross" => Func::Cross,
+            "min" => Func::Min,
+            "max" => Func::Max,
+            "atan2" => Func::Atan2,
+            "noise" => Func::Noise,
+            "curl" => Func::Curl,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Func::Vec3 => "vec3",
+            Func::Sin => "sin",
+            Func::Cos => "cos",
+            Func::Tan => "tan",
+            Func::Exp => "exp",
+            Func::Log => "log",
+            Func::Sqrt => "sqrt",
+            Func::Abs => "abs",
+            Func::Length => "length",
+            Func::Normalize => "normalize",
+            Func::Dot => "dot",
+            Func::Cross => "cross",
+            Func::Min => "min",
+            Func::Max => "max",
+            Func::Atan2 => "atan2",
+            Func::Noise => "noise",
+            Func::Curl => "curl",
+        }
+    }
+
+    fn wgsl_name(self) -> &'static str {
+        match self {
+            Func::Vec3 => "vec3<f32>",
+            Func::Sin => "sin",
+            Func::Cos => "cos",
+            Func::Tan => "tan",
+            Func::Exp => "exp",
+            Func::Log => "log",
+            Func::Sqrt => "sqrt",
+            Func::Abs => "abs",
+            Func::Length => "length",
+            Func::Normalize => "normalize",
+            Func::Dot => "dot",
+            Func::Cross => "cross",
+            Func::Min => "min",
+            Func::Max => "max",
+            Func::Atan2 => "atan2",
+            Func::Noise => "expr_noise",
+            Func::Curl => unreachable!("curl is generated as a helper function"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Value {
+    Scalar(f32),
+    Vec3(Vec3),
+}
+
+impl Value {
+    fn vec3(self) -> Vec3 {
+        match self {
+            Value::Scalar(s) => Vec3::splat(s),
+            Value::Vec3(v) => v,
+        }
+    }
+
+    fn scalar(self) -> f32 {
+        match self {
+            Value::Scalar(s) => s,
+            Value::Vec3(v) => v.x,
+        }
+    }
+
+    fn map(self, f: impl Fn(f32) -> f32) -> Value {
+        match self {
+            Value::Scalar(s) => Value::Scalar(f(s)),
+            Value::Vec3(v) => Value::Vec3(Vec3::new(f(v.x), f(v.y), f(v.z))),
+        }
+    }
+
+    fn zip(self, other: Value, f: impl Fn(f32, f32) -> f32) -> Value {
+        match (self, other) {
+            (Value::Scalar(a), Value::Scalar(b)) => Value::Scalar(f(a, b)),
+            (a, b) => {
+                let (a, b) = (a.vec3(), b.vec3());
+                Value::Vec3(Vec3::new(f(a.x, b.x), f(a.y, b.y), f(a.z, b.z)))
+            }
+        }
+    }
+}
+
+/// Step used for the central differences of `curl`, on the CPU and in WGSL.
+const CURL_EPSILON: f32 = 1e-3;
+/// Largest literal exponent turned into repeated multiplication in WGSL.
+const MAX_POWI: i32 = 16;
+
+impl Expr {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.expr()?;
+        if parser.pos != parser.tokens.len() {
+            bail!("unexpected {:?}", parser.tokens[parser.pos]);
+        }
+        if type_of(&root)? != Type::Vec3 {
+            bail!("the field has to be a vector, wrap it in vec3(...)");
+        }
+        Ok(Self { root })
+    }
+
+    pub fn eval(&self, p: Vec3, t: f32) -> Vec3 {
+        eval(&self.root, p, t).vec3()
+    }
+
+    /// Whether the field changes over time, i.e. `t` appears in it.
+    pub fn uses_time(&self) -> bool {
+        uses_time(&self.root)
+    }
+
+    /// WGSL for `fn field_expr(p: vec3<f32>, t: f32) -> vec3<f32>` plus the helpers it needs, the shader it
+    /// is spliced into must include rng.wgsl.
+    pub fn to_wgsl(&self) -> String {
+        let mut helpers = String::new();
+        let mut curls = 0;
+        let body = wgsl(&self.root, &mut helpers, &mut curls);
+
+        let mut out = String::new();
+        out.push_str(NOISE_WGSL);
+        out.push_str(&helpers);
+        writeln!(out, "fn field_expr(p: vec3<f32>, t: f32) -> vec3<f32> {{").unwrap();
+        writeln!(out, "    return {body};").unwrap();
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    /// Evaluates the expression on a grid, e.g. for arrow glyphs.
+    pub fn to_vector_field(&self, dims: [usize; 3], min: Vec3, max: Vec3, t: f32) -> VectorField {
+        let steps = Vec3::new(
+            dims[0].saturating_sub(1).max(1) as f32,
+            dims[1].saturating_sub(1).max(1) as f32,
+            dims[2].saturating_sub(1).max(1) as f32,
+        );
+        let spacing = (max - min) / steps;
+
+        let mut data = Vec::with_capacity(dims[0] * dims[1] * dims[2]);
+        for k in 0..dims[2] {
+            for j in 0..dims[1] {
+                for i in 0..dims[0] {
+                    let p = min + Vec3::new(i as f32, j as f32, k as f32) * spacing;
+                    data.push(self.eval(p, t));
+                }
+            }
+        }
+
+        VectorField {
+            dims,
+            origin: min,
+            spacing,
+            data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Op(char),
+}
+
+fn tokenize(source: &str) -> anyhow::Result<Vec<Token>> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Exponent, e.g. 1e-3
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '-' || chars[j] == '+') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text = chars[start..i].iter().collect::<String>();
+            tokens.push(Token::Number(text.parse().map_err(|_| anyhow!("invalid number {text}"))?));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*/^(),.".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            bail!("unexpected character '{c}'");
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self, op: char) -> bool {
+        self.tokens.get(self.pos) == Some(&Token::Op(op))
+    }
+
+    fn expect_op(&mut self, op: char) -> anyhow::Result<()> {
+        if !self.peek_op(op) {
+            bail!("expected '{op}'");
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expr(&mut self) -> anyhow::Result<Node> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = if self.peek_op('+') {
+                BinOp::Add
+            } else if self.peek_op('-') {
+                BinOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+            self.pos += 1;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> anyhow::Result<Node> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.peek_op('*') {
+                BinOp::Mul
+            } else if self.peek_op('/') {
+                BinOp::Div
+            } else {
+                return Ok(lhs);
+            };
+            self.pos += 1;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Node> {
+        if self.peek_op('-') {
+            self.pos += 1;
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.postfix()?;
+        if self.peek_op('^') {
+            self.pos += 1;
+            // Right associative, binds tighter than unary minus on the left: -x^2 == -(x^2)
+            return Ok(Node::Binary(BinOp::Pow, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> anyhow::Result<Node> {
+        let mut node = self.primary()?;
+        while self.peek_op('.') {
+            self.pos += 1;
+            let component = match self.tokens.get(self.pos) {
+                Some(Token::Ident(c)) if c == "x" => 0,
+                Some(Token::Ident(c)) if c == "y" => 1,
+                Some(Token::Ident(c)) if c == "z" => 2,
+                _ => bail!("expected .x, .y or .z"),
+            };
+            self.pos += 1;
+            node = Node::Swizzle(Box::new(node), component);
+        }
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> anyhow::Result<Node> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| anyhow!("unexpected end of expression"))?;
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Ok(Node::Number(n)),
+            Token::Op('(') => {
+                let node = self.expr()?;
+                self.expect_op(')')?;
+                Ok(node)
+            }
+            Token::Ident(name) => {
+                if self.peek_op('(') {
+                    self.pos += 1;
+                    let func = Func::from_name(&name).ok_or_else(|| anyhow!("unknown function {name}"))?;
+                    let mut args = Vec::new();
+                    if !self.peek_op(')') {
+                        args.push(self.expr()?);
+                        while self.peek_op(',') {
+                            self.pos += 1;
+                            args.push(self.expr()?);
+                        }
+                    }
+                    self.expect_op(')')?;
+                    return Ok(Node::Call(func, args));
+                }
+                Ok(match name.as_str() {
+                    "x" => Node::Var(Var::X),
+                    "y" => Node::Var(Var::Y),
+                    "z" => Node::Var(Var::Z),
+                    "p" => Node::Var(Var::P),
+                    "t" => Node::Var(Var::T),
+                    "pi" => Node::Number(std::f32::consts::PI),
+                    _ => bail!("unknown variable {name}"),
+                })
+            }
+            Token::Op(op) => bail!("unexpected '{op}'"),
+        }
+    }
+}
+
+fn type_of(node: &Node) -> anyhow::Result<Type> {
+    Ok(match node {
+        Node::Number(_) => Type::Scalar,
+        Node::Var(Var::P) => Type::Vec3,
+        Node::Var(_) => Type::Scalar,
+        Node::Neg(inner) => type_of(inner)?,
+        Node::Swizzle(inner, _) => {
+            if type_of(inner)? != Type::Vec3 {
+                bail!("swizzles only work on vectors");
+            }
+            Type::Scalar
+        }
+        Node::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (type_of(lhs)?, type_of(rhs)?);
+            match op {
+                BinOp::Pow if lhs != Type::Scalar || rhs != Type::Scalar => bail!("^ only works on scalars"),
+                _ if lhs == Type::Vec3 || rhs == Type::Vec3 => Type::Vec3,
+                _ => Type::Scalar,
+            }
+        }
+        Node::Call(func, args) => {
+            let types = args.iter().map(type_of).collect::<anyhow::Result<Vec<_>>>()?;
+            let arity = |n: usize| -> anyhow::Result<()> {
+                if types.len() != n {
+                    bail!("{} takes {n} argument(s), got {}", func.name(), types.len());
+                }
+                Ok(())
+            };
+            let all = |t: Type| types.iter().all(|a| *a == t);
+            match func {
+                Func::Vec3 => {
+                    arity(3)?;
+                    if !all(Type::Scalar) {
+                        bail!("vec3 takes scalars");
+                    }
+                    Type::Vec3
+                }
+                Func::Sin | Func::Cos | Func::Tan | Func::Exp | Func::Log | Func::Sqrt | Func::Abs => {
+                    arity(1)?;
+                    types[0]
+                }
+                Func::Min | Func::Max => {
+                    arity(2)?;
+                    if types[0] != types[1] {
+                        bail!("{} needs arguments of the same type", func.name());
+                    }
+                    types[0]
+                }
+                Func::Atan2 => {
+                    arity(2)?;
+                    if !all(Type::Scalar) {
+                        bail!("atan2 takes scalars");
+                    }
+                    Type::Scalar
+                }
+                Func::Length | Func::Normalize | Func::Noise | Func::Curl => {
+                    arity(1)?;
+                    if types[0] != Type::Vec3 {
+                        bail!("{} takes a vector", func.name());
+                    }
+                    if *func == Func::Length { Type::Scalar } else { Type::Vec3 }
+                }
+                Func::Dot | Func::Cross => {
+                    arity(2)?;
+                    if !all(Type::Vec3) {
+                        bail!("{} takes vectors", func.name());
+                    }
+                    if *func == Func::Dot { Type::Scalar } else { Type::Vec3 }
+                }
+            }
+        }
+    })
+}
+
+fn eval(node: &Node, p: Vec3, t: f32) -> Value {
+    match node {
+        Node::Number(n) => Value::Scalar(*n),
+        Node::Var(Var::X) => Value::Scalar(p.x),
+        Node::Var(Var::Y) => Value::Scalar(p.y),
+        Node::Var(Var::Z) => Value::Scalar(p.z),
+        Node::Var(Var::P) => Value::Vec3(p),
+        Node::Var(Var::T) => Value::Scalar(t),
+        Node::Neg(inner) => eval(inner, p, t).map(|v| -v),
+        Node::Swizzle(inner, c) => Value::Scalar(eval(inner, p, t).vec3()[*c]),
+        Node::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (eval(lhs, p, t), eval(rhs, p, t));
+            match op {
+                BinOp::Add => lhs.zip(rhs, |a, b| a + b),
+                BinOp::Sub => lhs.zip(rhs, |a, b| a - b),
+                BinOp::Mul => lhs.zip(rhs, |a, b| a * b),
+                BinOp::Div => lhs.zip(rhs, |a, b| a / b),
+                BinOp::Pow => lhs.zip(rhs, f32::powf),
+            }
+        }
+        Node::Call(Func::Curl, args) => {
+            let f = |q: Vec3| eval(&args[0], q, t).vec3();
+            let e = CURL_EPSILON;
+            let dx = (f(p + Vec3::X * e) - f(p - Vec3::X * e)) / (2.0 * e);
+            let dy = (f(p + Vec3::Y * e) - f(p - Vec3::Y * e)) / (2.0 * e);
+            let dz = (f(p + Vec3::Z * e) - f(p - Vec3::Z * e)) / (2.0 * e);
+            Value::Vec3(Vec3::new(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x))
+        }
+        Node::Call(func, args) => {
+            let args = args.iter().map(|a| eval(a, p, t)).collect::<Vec<_>>();
+            match func {
+                Func::Vec3 => Value::Vec3(Vec3::new(args[0].scalar(), args[1].scalar(), args[2].scalar())),
+                Func::Sin => args[0].map(f32::sin),
+                Func::Cos => args[0].map(f32::cos),
+                Func::Tan => args[0].map(f32::tan),
+                Func::Exp => args[0].map(f32::exp),
+                Func::Log => args[0].map(f32::ln),
+                Func::Sqrt => args[0].map(f32::sqrt),
+                Func::Abs => args[0].map(f32::abs),
+                Func::Min => args[0].zip(args[1], f32::min),
+                Func::Max => args[0].zip(args[1], f32::max),
+                Func::Atan2 => Value::Scalar(args[0].scalar().atan2(args[1].scalar())),
+                Func::Length => Value::Scalar(args[0].vec3().length()),
+                Func::Normalize => Value::Vec3(args[0].vec3().normalize_or_zero()),
+                Func::Dot => Value::Scalar(args[0].vec3().dot(args[1].vec3())),
+                Func::Cross => Value::Vec3(args[0].vec3().cross(args[1].vec3())),
+                Func::Noise => Value::Vec3(noise3(args[0].vec3())),
+                Func::Curl => unreachable!(),
+            }
+        }
+    }
+}
+
+fn uses_time(node: &Node) -> bool {
+    match node {
+        Node::Number(_) => false,
+        Node::Var(var) => matches!(var, Var::T),
+        Node::Neg(inner) | Node::Swizzle(inner, _) => uses_time(inner),
+        Node::Binary(_, a, b) => uses_time(a) || uses_time(b),
+        Node::Call(_, args) => args.iter().any(uses_time),
+    }
+}
+
+/// Whole numbers written into the expression, e.g. the `2` and `-1` of `x^2` and `x^-1`.
+fn integer_literal(node: &Node) -> Option<i32> {
+    match node {
+        Node::Number(n) if n.fract() == 0.0 && n.abs() <= i32::MAX as f32 => Some(*n as i32),
+        Node::Neg(inner) => integer_literal(inner).map(|n| -n),
+        _ => None,
+    }
+}
+
+fn wgsl(node: &Node, helpers: &mut String, curls: &mut usize) -> String {
+    match node {
+        Node::Number(n) => format!("{n:?}"),
+        Node::Var(Var::X) => "p.x".to_string(),
+        Node::Var(Var::Y) => "p.y".to_string(),
+        Node::Var(Var::Z) => "p.z".to_string(),
+        Node::Var(Var::P) => "p".to_string(),
+        Node::Var(Var::T) => "t".to_string(),
+        Node::Neg(inner) => format!("(-{})", wgsl(inner, helpers, curls)),
+        Node::Swizzle(inner, c) => format!("({}).{}", wgsl(inner, helpers, curls), ["x", "y", "z"][*c]),
+        Node::Binary(op, lhs, rhs) => {
+            let (a, b) = (wgsl(lhs, helpers, curls), wgsl(rhs, helpers, curls));
+            match op {
+                BinOp::Add => format!("({a} + {b})"),
+                BinOp::Sub => format!("({a} - {b})"),
+                BinOp::Mul => format!("({a} * {b})"),
+                BinOp::Div => format!("({a} / {b})"),
+                // WGSL's pow is undefined for negative bases, small integer powers multiply instead
+                BinOp::Pow => match integer_literal(rhs) {
+                    Some(n) if n.abs() <= MAX_POWI => format!("expr_powi({a}, {n})"),
+                    _ => format!("expr_pow({a}, {b})"),
+                },
+            }
+        }
+        Node::Call(Func::Curl, args) => {
+            // The curl argument becomes its own function of p so it can be differentiated numerically
+            let inner = wgsl(&args[0], helpers, curls);
+            let id = *curls;
+            *curls += 1;
+            writeln!(helpers, "fn curl_arg_{id}(p: vec3<f32>, t: f32) -> vec3<f32> {{ return {inner}; }}").unwrap();
+            writeln!(helpers, "fn curl_{id}(p: vec3<f32>, t: f32) -> vec3<f32> {{").unwrap();
+            writeln!(helpers, "    let e = {CURL_EPSILON:?};").unwrap();
+            writeln!(helpers, "    let dx = (curl_arg_{id}(p + vec3(e, 0.0, 0.0), t) - curl_arg_{id}(p - vec3(e, 0.0, 0.0), t)) / (2.0 * e);").unwrap();
+            writeln!(helpers, "    let dy = (curl_arg_{id}(p + vec3(0.0, e, 0.0), t) - curl_arg_{id}(p - vec3(0.0, e, 0.0), t)) / (2.0 * e);").unwrap();
+            writeln!(helpers, "    let dz = (curl_arg_{id}(p + vec3(0.0, 0.0, e), t) - curl_arg_{id}(p - vec3(0.0, 0.0, e), t)) / (2.0 * e);").unwrap();
+            writeln!(helpers, "    return vec3(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x);").unwrap();
+            writeln!(helpers, "}}").unwrap();
+            format!("curl_{id}(p, t)")
+        }
+        Node::Call(func, args) => {
+            let args = args.iter().map(|a| wgsl(a, helpers, curls)).collect::<Vec<_>>();
+            format!("{}({})", func.wgsl_name(), args.join(", "))
+        }
+    }
+}
+
+fn hash(x: i32, y: i32, z: i32, channel: u32) -> f32 {
+    let h = crate::rng::pcg_hash(channel ^ crate::rng::hash3(x as u32, y as u32, z as u32));
+    crate::rng::unit_float(h) * 2.0 - 1.0
+}
+
+fn value_noise(p: Vec3, channel: u32) -> f32 {
+    let c = p.floor();
+    let f = p - c;
+    let s = f * f * (Vec3::splat(3.0) - 2.0 * f);
+    let (x, y, z) = (c.x as i32, c.y as i32, c.z as i32);
+
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let x00 = lerp(hash(x, y, z, channel), hash(x + 1, y, z, channel), s.x);
+    let x10 = lerp(hash(x, y + 1, z, channel), hash(x + 1, y + 1, z, channel), s.x);
+    let x01 = lerp(hash(x, y, z + 1, channel), hash(x + 1, y, z + 1, channel), s.x);
+    let x11 = lerp(hash(x, y + 1, z + 1, channel), hash(x + 1, y + 1, z + 1, channel), s.x);
+    lerp(lerp(x00, x10, s.y), lerp(x01, x11, s.y), s.z)
+}
+
+fn noise3(p: Vec3) -> Vec3 {
+    Vec3::new(value_noise(p, 0), value_noise(p, 1), value_noise(p, 2))
+}
+
+/// WGSL twin of [`noise3`] and the `^` helpers, keep them in sync. Needs rng.wgsl in front of it.
+const NOISE_WGSL: &str = r#"
+fn expr_hash(c: vec3<i32>, channel: u32) -> f32 {
+    let h = pcg_hash(channel ^ rng_seed(bitcast<u32>(c.x), bitcast<u32>(c.y), bitcast<u32>(c.z)));
+    return rng_unit_float(h) * 2.0 - 1.0;
+}
+
+fn expr_value_noise(p: vec3<f32>, channel: u32) -> f32 {
+    let c = floor(p);
+    let f = p - c;
+    let s = f * f * (3.0 - 2.0 * f);
+    let i = vec3<i32>(c);
+
+    let x00 = mix(expr_hash(i, channel), expr_hash(i + vec3(1, 0, 0), channel), s.x);
+    let x10 = mix(expr_hash(i + vec3(0, 1, 0), channel), expr_hash(i + vec3(1, 1, 0), channel), s.x);
+    let x01 = mix(expr_hash(i + vec3(0, 0, 1), channel), expr_hash(i + vec3(1, 0, 1), channel), s.x);
+    let x11 = mix(expr_hash(i + vec3(0, 1, 1), channel), expr_hash(i + vec3(1, 1, 1), channel), s.x);
+    return mix(mix(x00, x10, s.y), mix(x01, x11, s.y), s.z);
+}
+
+fn expr_noise(p: vec3<f32>) -> vec3<f32> {
+    return vec3(expr_value_noise(p, 0u), expr_value_noise(p, 1u), expr_value_noise(p, 2u));
+}
+
+fn expr_powi(x: f32, n: i32) -> f32 {
+    let base = select(x, 1.0 / x, n < 0);
+    var result = 1.0;
+    for (var i = 0; i < abs(n); i++) {
+        result *= base;
+    }
+    return result;
+}
+
+// Same as f32::powf for negative bases with integer exponents, odd ones keep the sign
+fn expr_pow(x: f32, y: f32) -> f32 {
+    if (x >= 0.0 || y != trunc(y)) {
+        return pow(x, y);
+    }
+    let magnitude = pow(-x, y);
+    return select(magnitude, -magnitude, abs(y) % 2.0 == 1.0);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        Expr::parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_precedence_and_associativity() {
+        let expr = Expr::parse("vec3(1 + 2 * 3, -2^2, 2^3^2)").unwrap();
+        assert_eq!(expr.eval(Vec3::ZERO, 0.0), Vec3::new(7.0, -4.0, 512.0));
+        let expr = Expr::parse("vec3(1e-3, .5, (1 - 2) - 3)").unwrap();
+        assert_eq!(expr.eval(Vec3::ZERO, 0.0), Vec3::new(1e-3, 0.5, -4.0));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(error("vec3(x, y, z"), "expected ')'");
+        assert_eq!(error("vec3(x, y, z) z"), "unexpected Ident(\"z\")");
+        assert_eq!(error("vec3(x, y, $)"), "unexpected character '$'");
+        assert_eq!(error("p.w"), "expected .x, .y or .z");
+        assert_eq!(error(""), "unexpected end of expression");
+    }
+
+    #[test]
+    fn rejects_unknown_identifiers() {
+        assert_eq!(error("vec3(w, 0, 0)"), "unknown variable w");
+        assert_eq!(error("grad(p)"), "unknown function grad");
+    }
+
+    #[test]
+    fn type_checks() {
+        assert_eq!(error("p ^ 2"), "^ only works on scalars");
+        assert_eq!(error("x ^ p"), "^ only works on scalars");
+        assert_eq!(error("x + y"), "the field has to be a vector, wrap it in vec3(...)");
+        assert_eq!(error("vec3(x, y)"), "vec3 takes 3 argument(s), got 2");
+        assert_eq!(error("vec3(p, y, z)"), "vec3 takes scalars");
+        assert_eq!(error("vec3(x.x, 0, 0)"), "swizzles only work on vectors");
+        assert_eq!(error("cross(p, x)"), "cross takes vectors");
+        assert_eq!(error("min(p, x)"), "min needs arguments of the same type");
+        assert!(Expr::parse("p * x + normalize(cross(p, vec3(0, 0, 1)))").is_ok());
+    }
+
+    #[test]
+    fn evaluates_on_the_cpu() {
+        let p = Vec3::new(-2.0, 3.0, 0.5);
+        let expr = Expr::parse("vec3(x^2 - y^2, (-x)^3, p.z * t)").unwrap();
+        assert_eq!(expr.eval(p, 4.0), Vec3::new(-5.0, 8.0, 2.0));
+        assert!(expr.uses_time());
+        let expr = Expr::parse("vec3(length(p), dot(p, p), atan2(1, 1))").unwrap();
+        let v = expr.eval(p, 0.0);
+        assert!(!expr.uses_time());
+        assert!((v.x - p.length()).abs() < 1e-6);
+        assert_eq!(v.y, p.length_squared());
+        assert!((v.z - std::f32::consts::FRAC_PI_4).abs() < 1e-6);
+        // The curl of a rigid rotation about z is twice its angular velocity
+        let curl = Expr::parse("curl(vec3(-y, x, 0))").unwrap().eval(p, 0.0);
+        assert!((curl - Vec3::new(0.0, 0.0, 2.0)).length() < 1e-2);
+    }
+
+    #[test]
+    fn integer_powers_multiply_in_wgsl() {
+        let wgsl = Expr::parse("vec3(x^2, x^-1, x^y)").unwrap().to_wgsl();
+        assert!(wgsl.contains("expr_powi(p.x, 2)"));
+        assert!(wgsl.contains("expr_powi(p.x, -1)"));
+        assert!(wgsl.contains("expr_pow(p.x, p.y)"));
+        assert!(!wgsl.contains(" pow(p"));
+    }
+}
@@ -0,0 +1,41 @@
+// Sampling of a FieldTexture, concatenated in front of the shaders that read one.
+
+struct FieldParams {
+    bounds_min: vec4f,
+    bounds_max: vec4f,
+    dims: vec4u,
+}
+
+fn field_load(field: texture_3d<f32>, dims: vec3u, c: vec3i) -> vec3f {
+    return textureLoad(field, clamp(c, vec3i(0), vec3i(dims) - 1), 0).xyz;
+}
+
+// Trilinear interpolation of the field texture, zero outside of its bounds
+fn sample_field_texture(field: texture_3d<f32>, params: FieldParams, p: vec3f) -> vec3f {
+    let extent = params.bounds_max.xyz - params.bounds_min.xyz;
+    let uvw = (p - params.bounds_min.xyz) / extent;
+    if (any(uvw < vec3f(0.0)) || any(uvw > vec3f(1.0))) {
+        return vec3f(0.0);
+    }
+
+    // Samples sit on cell centers, the same convention as FluidSim::velocity_field_data
+    let dims = params.dims.xyz;
+    let g = uvw * vec3f(dims) - 0.5;
+    let c0 = vec3i(floor(g));
+    let t = g - floor(g);
+
+    let c000 = field_load(field, dims, c0);
+    let c100 = field_load(field, dims, c0 + vec3i(1, 0, 0));
+    let c010 = field_load(field, dims, c0 + vec3i(0, 1, 0));
+    let c110 = field_load(field, dims, c0 + vec3i(1, 1, 0));
+    let c001 = field_load(field, dims, c0 + vec3i(0, 0, 1));
+    let c101 = field_load(field, dims, c0 + vec3i(1, 0, 1));
+    let c011 = field_load(field, dims, c0 + vec3i(0, 1, 1));
+    let c111 = field_load(field, dims, c0 + vec3i(1, 1, 1));
+
+    let x00 = mix(c000, c100, t.x);
+    let x10 = mix(c010, c110, t.x);
+    let x01 = mix(c001, c101, t.x);
+    let x11 = mix(c011, c111, t.x);
+    return mix(mix(x00, x10, t.y), mix(x01, x11, t.y), t.z);
+}
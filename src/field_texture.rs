@@ -0,0 +1,93 @@
+use egui_wgpu::wgpu::{Buffer, BufferUsages, Device, Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, Queue, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor};
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+
+/// Where the field texture sits in world space, mirrors `FieldParams` in the shaders.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FieldParams {
+    pub bounds_min: [f32; 4],
+    pub bounds_max: [f32; 4],
+    pub dims: [u32; 4],
+}
+
+/// A velocity field on a regular grid, stored in a 3D texture so shaders can sample it.
+/// Values are read with `textureLoad` and interpolated by hand, `Rgba32Float` is not filterable.
+pub struct FieldTexture {
+    pub texture: Texture,
+    pub view: TextureView,
+    pub params: FieldParams,
+    pub params_buffer: Buffer,
+}
+
+impl FieldTexture {
+    pub fn new(device: &Device, dims: [u32; 3], bounds_min: Vec3, bounds_max: Vec3) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Velocity Field Texture"),
+            size: Extent3d {
+                width: dims[0],
+                height: dims[1],
+                depth_or_array_layers: dims[2],
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let params = FieldParams {
+            bounds_min: bounds_min.extend(0.0).to_array(),
+            bounds_max: bounds_max.extend(0.0).to_array(),
+            dims: [dims[0], dims[1], dims[2], 0],
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Field Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        Self {
+            texture,
+            view,
+            params,
+            params_buffer,
+        }
+    }
+
+    pub fn dims(&self) -> [u32; 3] {
+        [self.params.dims[0], self.params.dims[1], self.params.dims[2]]
+    }
+
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        (Vec3::from_slice(&self.params.bounds_min[..3]), Vec3::from_slice(&self.params.bounds_max[..3]))
+    }
+
+    /// Uploads `data` laid out x fastest, then y, then z. `data.len()` must match the texture size.
+    pub fn write(&self, queue: &Queue, data: &[[f32; 4]]) {
+        let [w, h, d] = self.dims();
+        assert_eq!(data.len(), (w * h * d) as usize, "field data does not match texture size");
+
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(data),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(w * std::mem::size_of::<[f32; 4]>() as u32),
+                rows_per_image: Some(h),
+            },
+            Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: d,
+            },
+        );
+    }
+}
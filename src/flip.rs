@@ -0,0 +1,368 @@
+use std::sync::Arc;
+
+use egui_wgpu::wgpu::{Buffer, Queue};
+use glam::Vec2;
+
+use crate::fluid_vec::{FluidSim, AIR_CELL, FLUID_CELL, SOLID_CELL};
+
+/// Free-surface liquid solver: particles carry the velocity, the MAC grid of a [`FluidSim`]
+/// is only used to make the flow incompressible. FLIP/PIC blending is controlled by `flip_ratio`.
+pub struct FlipSim {
+    grid: FluidSim,
+    solid: Vec<bool>,
+
+    pub positions: Vec<Vec2>,
+    pub velocities: Vec<Vec2>,
+    particle_radius: f32,
+
+    prev_u: Vec<f32>,
+    prev_v: Vec<f32>,
+    weight_u: Vec<f32>,
+    weight_v: Vec<f32>,
+    particle_density: Vec<f32>,
+    rest_density: f32,
+
+    /// 0.0 is pure PIC (stable, viscous), 1.0 is pure FLIP (lively, noisy).
+    pub flip_ratio: f32,
+    pub pressure_iters: usize,
+    pub separation_iters: usize,
+}
+
+impl FlipSim {
+    /// Creates a closed box of `grid_size` x `grid_size` cells with a block of liquid
+    /// filling `fill` (fraction of width, fraction of height) of the lower left corner.
+    pub fn new(grid_size: usize, cell_size: f32, density: f32, fill: Vec2) -> Self {
+        let mut grid = FluidSim::new(grid_size, grid_size, cell_size, density);
+        let n = grid.grid_width;
+
+        let mut solid = vec![false; grid_size * grid_size];
+        for i in 0..grid_size {
+            for j in 0..grid_size {
+                if i == 0 || j == 0 || i == grid_size - 1 || j == grid_size - 1 {
+                    solid[i * n + j] = true;
+                }
+            }
+        }
+        for (kind, solid) in grid.cell_kind.iter_mut().zip(&solid) {
+            *kind = if *solid { SOLID_CELL } else { AIR_CELL };
+        }
+
+        // Hexagonal packing of particles inside the initial block
+        let particle_radius = 0.3 * cell_size;
+        let dx = 2.0 * particle_radius;
+        let dy = 3.0_f32.sqrt() / 2.0 * dx;
+        let extent = (grid_size - 2) as f32 * cell_size;
+        let num_x = ((fill.x * extent - 2.0 * particle_radius) / dx).max(0.0) as usize;
+        let num_y = ((fill.y * extent - 2.0 * particle_radius) / dy).max(0.0) as usize;
+
+        let mut positions = Vec::with_capacity(num_x * num_y);
+        for i in 0..num_x {
+            for j in 0..num_y {
+                let offset = if j % 2 == 0 { 0.0 } else { particle_radius };
+                positions.push(Vec2::new(
+                    cell_size + particle_radius + dx * i as f32 + offset,
+                    cell_size + particle_radius + dy * j as f32,
+                ));
+            }
+        }
+        let velocities = vec![Vec2::ZERO; positions.len()];
+
+        let cells = grid_size * grid_size;
+        Self {
+            grid,
+            solid,
+            positions,
+            velocities,
+            particle_radius,
+            prev_u: vec![0.0; cells],
+            prev_v: vec![0.0; cells],
+            weight_u: vec![0.0; cells],
+            weight_v: vec![0.0; cells],
+            particle_density: vec![0.0; cells],
+            rest_density: 0.0,
+            flip_ratio: 0.9,
+            pressure_iters: 50,
+            separation_iters: 2,
+        }
+    }
+
+    pub fn simulate(&mut self, dt: f32, gravity: Vec2) {
+        self.integrate_particles(dt, gravity);
+        for _ in 0..self.separation_iters {
+            self.push_particles_apart();
+        }
+        self.handle_collisions();
+
+        self.transfer_to_grid();
+        self.update_particle_density();
+        self.grid.solve_pressure(self.pressure_iters, Some((&self.particle_density, self.rest_density)));
+        self.transfer_to_particles();
+    }
+
+    fn integrate_particles(&mut self, dt: f32, gravity: Vec2) {
+        for (pos, vel) in self.positions.iter_mut().zip(self.velocities.iter_mut()) {
+            *vel += gravity * dt;
+            *pos += *vel * dt;
+        }
+    }
+
+    fn push_particles_apart(&mut self) {
+        // Bucket particles into cells of size 2r so only neighbouring buckets need checking
+        let spacing = 2.0 * self.particle_radius;
+        let extent = self.grid.grid_width as f32 * self.grid.h;
+        let buckets_per_side = (extent / spacing).ceil() as usize + 1;
+        let bucket_of = |p: Vec2| {
+            let x = ((p.x / spacing) as usize).min(buckets_per_side - 1);
+            let y = ((p.y / spacing) as usize).min(buckets_per_side - 1);
+            (x, y)
+        };
+
+        let mut counts = vec![0usize; buckets_per_side * buckets_per_side + 1];
+        for p in &self.positions {
+            let (x, y) = bucket_of(*p);
+            counts[x * buckets_per_side + y + 1] += 1;
+        }
+        for k in 1..counts.len() {
+            counts[k] += counts[k - 1];
+        }
+        let mut sorted = vec![0usize; self.positions.len()];
+        let mut fill = counts.clone();
+        for (k, p) in self.positions.iter().enumerate() {
+            let (x, y) = bucket_of(*p);
+            let bucket = x * buckets_per_side + y;
+            sorted[fill[bucket]] = k;
+            fill[bucket] += 1;
+        }
+
+        let min_dist = 2.0 * self.particle_radius;
+        for a in 0..self.positions.len() {
+            let (bx, by) = bucket_of(self.positions[a]);
+            for x in bx.saturating_sub(1)..=(bx + 1).min(buckets_per_side - 1) {
+                for y in by.saturating_sub(1)..=(by + 1).min(buckets_per_side - 1) {
+                    let bucket = x * buckets_per_side + y;
+                    for &b in &sorted[counts[bucket]..counts[bucket + 1]] {
+                        if b == a {
+                            continue;
+                        }
+                        let d = self.positions[b] - self.positions[a];
+                        let dist = d.length();
+                        if dist > min_dist || dist == 0.0 {
+                            continue;
+                        }
+                        let push = d * (0.5 * (min_dist - dist) / dist);
+                        self.positions[a] -= push;
+                        self.positions[b] += push;
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_collisions(&mut self) {
+        let h = self.grid.h;
+        let r = self.particle_radius;
+        let min = h + r;
+        let max_x = (self.grid.grid_width - 1) as f32 * h - r;
+        let max_y = (self.grid.grid_height - 1) as f32 * h - r;
+
+        for (pos, vel) in self.positions.iter_mut().zip(self.velocities.iter_mut()) {
+            if pos.x < min {
+                pos.x = min;
+                vel.x = 0.0;
+            }
+            if pos.x > max_x {
+                pos.x = max_x;
+                vel.x = 0.0;
+            }
+            if pos.y < min {
+                pos.y = min;
+                vel.y = 0.0;
+            }
+            if pos.y > max_y {
+                pos.y = max_y;
+                vel.y = 0.0;
+            }
+        }
+    }
+
+    /// Bilinear weights of a staggered sample, `(dx, dy)` is the offset of the field inside a cell.
+    fn stencil(&self, pos: Vec2, dx: f32, dy: f32) -> ([usize; 4], [f32; 4]) {
+        let n = self.grid.grid_width;
+        let h = self.grid.h;
+        let h1 = 1.0 / h;
+        let num_x = self.grid.grid_width;
+        let num_y = self.grid.grid_height;
+
+        let x = pos.x.clamp(h, (num_x - 1) as f32 * h);
+        let y = pos.y.clamp(h, (num_y - 1) as f32 * h);
+
+        let x0 = (((x - dx) * h1).floor() as usize).min(num_x - 2);
+        let tx = ((x - dx) - x0 as f32 * h) * h1;
+        let x1 = (x0 + 1).min(num_x - 2);
+
+        let y0 = (((y - dy) * h1).floor() as usize).min(num_y - 2);
+        let ty = ((y - dy) - y0 as f32 * h) * h1;
+        let y1 = (y0 + 1).min(num_y - 2);
+
+        let sx = 1.0 - tx;
+        let sy = 1.0 - ty;
+
+        (
+            [x0 * n + y0, x1 * n + y0, x1 * n + y1, x0 * n + y1],
+            [sx * sy, tx * sy, tx * ty, sx * ty],
+        )
+    }
+
+    fn classify_cells(&mut self) {
+        let n = self.grid.grid_width;
+        let h1 = 1.0 / self.grid.h;
+
+        for (kind, solid) in self.grid.cell_kind.iter_mut().zip(&self.solid) {
+            *kind = if *solid { SOLID_CELL } else { AIR_CELL };
+        }
+        for pos in &self.positions {
+            let i = ((pos.x * h1) as usize).min(self.grid.grid_width - 1);
+            let j = ((pos.y * h1) as usize).min(self.grid.grid_height - 1);
+            let index = i * n + j;
+            if self.grid.cell_kind[index] == AIR_CELL {
+                self.grid.cell_kind[index] = FLUID_CELL;
+            }
+        }
+    }
+
+    fn transfer_to_grid(&mut self) {
+        let h2 = 0.5 * self.grid.h;
+
+        self.prev_u.copy_from_slice(&self.grid.velocities_x);
+        self.prev_v.copy_from_slice(&self.grid.velocities_y);
+
+        self.grid.velocities_x.fill(0.0);
+        self.grid.velocities_y.fill(0.0);
+        self.weight_u.fill(0.0);
+        self.weight_v.fill(0.0);
+
+        self.classify_cells();
+
+        for k in 0..self.positions.len() {
+            let pos = self.positions[k];
+            let vel = self.velocities[k];
+
+            let (cells, weights) = self.stencil(pos, 0.0, h2);
+            for (&c, &w) in cells.iter().zip(&weights) {
+                self.grid.velocities_x[c] += vel.x * w;
+                self.weight_u[c] += w;
+            }
+
+            let (cells, weights) = self.stencil(pos, h2, 0.0);
+            for (&c, &w) in cells.iter().zip(&weights) {
+                self.grid.velocities_y[c] += vel.y * w;
+                self.weight_v[c] += w;
+            }
+        }
+
+        for (u, w) in self.grid.velocities_x.iter_mut().zip(&self.weight_u) {
+            if *w > 0.0 {
+                *u /= w;
+            }
+        }
+        for (v, w) in self.grid.velocities_y.iter_mut().zip(&self.weight_v) {
+            if *w > 0.0 {
+                *v /= w;
+            }
+        }
+
+        // Faces touching a solid cell keep their previous (zero) velocity
+        let n = self.grid.grid_width;
+        for i in 0..self.grid.grid_width {
+            for j in 0..self.grid.grid_height {
+                let solid = self.solid[i * n + j];
+                if solid || (i > 0 && self.solid[(i - 1) * n + j]) {
+                    self.grid.velocities_x[i * n + j] = self.prev_u[i * n + j];
+                }
+                if solid || (j > 0 && self.solid[i * n + j - 1]) {
+                    self.grid.velocities_y[i * n + j] = self.prev_v[i * n + j];
+                }
+            }
+        }
+
+        // Remember the pre-solve grid so FLIP can transfer back only the change
+        self.prev_u.copy_from_slice(&self.grid.velocities_x);
+        self.prev_v.copy_from_slice(&self.grid.velocities_y);
+    }
+
+    fn update_particle_density(&mut self) {
+        let h2 = 0.5 * self.grid.h;
+        self.particle_density.fill(0.0);
+        for k in 0..self.positions.len() {
+            let (cells, weights) = self.stencil(self.positions[k], h2, h2);
+            for (&c, &w) in cells.iter().zip(&weights) {
+                self.particle_density[c] += w;
+            }
+        }
+
+        // The density of the initial block is what the solver tries to maintain from then on
+        if self.rest_density == 0.0 {
+            let (sum, count) = self.grid.cell_kind.iter().zip(&self.particle_density)
+                .filter(|(kind, _)| **kind == FLUID_CELL)
+                .fold((0.0, 0), |(sum, count), (_, d)| (sum + d, count + 1));
+            if count > 0 {
+                self.rest_density = sum / count as f32;
+            }
+        }
+    }
+
+    fn transfer_to_particles(&mut self) {
+        let n = self.grid.grid_width;
+        let h2 = 0.5 * self.grid.h;
+        let kind = &self.grid.cell_kind;
+
+        for k in 0..self.positions.len() {
+            let pos = self.positions[k];
+
+            for (component, offset, stride) in [(0, Vec2::new(0.0, h2), n), (1, Vec2::new(h2, 0.0), 1)] {
+                let (cells, weights) = self.stencil(pos, offset.x, offset.y);
+                let (field, prev) = if component == 0 {
+                    (&self.grid.velocities_x, &self.prev_u)
+                } else {
+                    (&self.grid.velocities_y, &self.prev_v)
+                };
+
+                let mut weight_sum = 0.0;
+                let mut pic = 0.0;
+                let mut correction = 0.0;
+                for (&c, &w) in cells.iter().zip(&weights) {
+                    // A face only carries a meaningful velocity if one of its cells is not air
+                    let valid = kind[c] != AIR_CELL || (c >= stride && kind[c - stride] != AIR_CELL);
+                    if !valid {
+                        continue;
+                    }
+                    weight_sum += w;
+                    pic += w * field[c];
+                    correction += w * (field[c] - prev[c]);
+                }
+                if weight_sum <= 0.0 {
+                    continue;
+                }
+
+                let pic = pic / weight_sum;
+                let flip = self.velocities[k][component] + correction / weight_sum;
+                self.velocities[k][component] = (1.0 - self.flip_ratio) * pic + self.flip_ratio * flip;
+            }
+        }
+    }
+
+    pub fn num_particles(&self) -> u32 {
+        self.positions.len() as u32
+    }
+
+    /// Writes the particles into a point buffer for [`crate::renderer::Renderer`],
+    /// centered on the origin in the XY plane and scaled to fit the unit square.
+    pub fn write_points(&self, queue: &Queue, point_buffer: &Arc<Buffer>) {
+        let extent = self.grid.grid_width as f32 * self.grid.h;
+        let points = self.positions.iter().map(|p| {
+            let p = (*p / extent) * 2.0 - Vec2::ONE;
+            [p.x, p.y, 0.0, 1.0]
+        }).collect::<Vec<_>>();
+        queue.write_buffer(point_buffer, 0, bytemuck::cast_slice(points.as_slice()));
+    }
+}
@@ -1,26 +1,102 @@
 use glam::{Vec2, Vec3};
 
-pub struct FluidSim {
-    grid_width: usize,
-    grid_height: usize,
+use crate::vector_field::VectorField;
 
-    cell_kind: Vec<u32>,
-    smoke: Vec<f32>,
-    velocities_x: Vec<f32>,
-    velocities_y: Vec<f32>,
+pub struct FluidSim {
+    pub(crate) grid_width: usize,
+    pub(crate) grid_height: usize,
+
+    pub(crate) cell_kind: Vec<u32>,
+    scalars: Vec<ScalarField>,
+    temperature: ScalarField,
+    pub(crate) velocities_x: Vec<f32>,
+    pub(crate) velocities_y: Vec<f32>,
     pressures: Vec<f32>,
     density: f32,
-    h: f32, // Cell size or spacing
+    pub(crate) h: f32, // Cell size or spacing
+
+    pub buoyancy: Buoyancy,
+}
+
+/// A passive quantity carried along by the flow (smoke, dye, ...).
+pub struct ScalarField {
+    pub name: String,
+    pub values: Vec<f32>,
+    pub sources: Vec<ScalarSource>,
+    /// Exponential decay rate per second towards `ambient`.
+    pub decay: f32,
+    pub ambient: f32,
+}
+
+/// Keeps every fluid cell within `radius` cells of (`x`, `y`) at least at `value`.
+#[derive(Debug, Copy, Clone)]
+pub struct ScalarSource {
+    pub x: usize,
+    pub y: usize,
+    pub radius: usize,
+    pub value: f32,
+}
+
+/// Boussinesq approximation: smoke makes the fluid heavier, heat makes it lighter.
+/// The resulting force acts against gravity, scaled by
+/// `beta * (T - ambient_temperature) - alpha * sum(scalars)`.
+#[derive(Debug, Copy, Clone)]
+pub struct Buoyancy {
+    pub alpha: f32,
+    pub beta: f32,
 }
 
 const DT: f32 = 0.001;
-const GRAVITY_VEC: Vec2 = Vec2::new(0.0, -9.8);
+pub(crate) const SOLID_CELL: u32 = 0;
+pub(crate) const FLUID_CELL: u32 = 1;
+/// Only used by free-surface solvers such as [`crate::flip::FlipSim`].
+pub(crate) const AIR_CELL: u32 = 2;
+
+pub const GRAVITY_VEC: Vec2 = Vec2::new(0.0, -9.8);
 const OVER_RELAXATION: f32 = 1.9;
+const AMBIENT_TEMPERATURE: f32 = 0.0;
 
-enum FieldType {
+enum FieldType<'a> {
     U,
     V,
-    S,
+    S(&'a [f32]),
+}
+
+impl ScalarField {
+    fn new(name: &str, cells: usize, decay: f32, ambient: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            values: vec![ambient; cells],
+            sources: Vec::new(),
+            decay,
+            ambient,
+        }
+    }
+
+    fn apply_sources(&mut self, grid_width: usize, grid_height: usize, cell_kind: &[u32]) {
+        for source in &self.sources {
+            let x_range = source.x.saturating_sub(source.radius)..(source.x + source.radius + 1).min(grid_width);
+            for i in x_range {
+                let y_range = source.y.saturating_sub(source.radius)..(source.y + source.radius + 1).min(grid_height);
+                for j in y_range {
+                    let index = i * grid_width + j;
+                    if cell_kind[index] == 1 {
+                        self.values[index] = self.values[index].max(source.value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply_decay(&mut self, dt: f32) {
+        if self.decay <= 0.0 {
+            return;
+        }
+        let k = (-self.decay * dt).exp();
+        for v in self.values.iter_mut() {
+            *v = self.ambient + (*v - self.ambient) * k;
+        }
+    }
 }
 
 impl FluidSim {
@@ -41,8 +117,8 @@ impl FluidSim {
             cell_kind[y * grid_width + grid_width - 1] = 0; // Right edge
         }
 
-        // Initialize smoke, velocities_x, velocities_y, and pressures with zeroes
-        let smoke = vec![0.0; grid_width * grid_height];
+        // Initialize velocities_x, velocities_y, and pressures with zeroes
+        let temperature = ScalarField::new("temperature", grid_width * grid_height, 0.0, AMBIENT_TEMPERATURE);
         let velocities_x = vec![0.0; grid_width * grid_height];
         let velocities_y = vec![0.0; grid_width * grid_height];
         let pressures = vec![0.0; grid_width * grid_height];
@@ -51,15 +127,48 @@ impl FluidSim {
             grid_width,
             grid_height,
             cell_kind,
-            smoke,
+            scalars: Vec::new(),
+            temperature,
             velocities_x,
             velocities_y,
             pressures,
             density,
             h: cell_size, // Set the cell size (spacing) based on the provided value
+            buoyancy: Buoyancy {
+                alpha: 0.1,
+                beta: 1.0,
+            },
         }
     }
 
+    /// Adds a new named scalar field and returns its index.
+    pub fn add_scalar(&mut self, name: &str, decay: f32) -> usize {
+        self.scalars.push(ScalarField::new(name, self.grid_width * self.grid_height, decay, 0.0));
+        self.scalars.len() - 1
+    }
+
+    pub fn add_scalar_source(&mut self, scalar: usize, source: ScalarSource) {
+        self.scalars[scalar].sources.push(source);
+    }
+
+    /// The scalar field called `name`, the temperature included.
+    pub fn scalar(&self, name: &str) -> Option<&ScalarField> {
+        std::iter::once(&self.temperature).chain(&self.scalars).find(|s| s.name == name)
+    }
+
+    pub fn scalars(&self) -> &[ScalarField] {
+        &self.scalars
+    }
+
+    pub fn temperature(&self) -> &ScalarField {
+        &self.temperature
+    }
+
+    /// Heat sources drive the buoyancy, see [`Buoyancy`].
+    pub fn add_heat_source(&mut self, source: ScalarSource) {
+        self.temperature.sources.push(source);
+    }
+
     pub fn setup_wind_tunnel(&mut self, inflow_velocity: f32, smoke_radius: usize) {
         // Define left boundary (inlet)
         for j in 0..self.grid_height {
@@ -93,24 +202,18 @@ impl FluidSim {
         self.cell_kind[(center_x + 1) * self.grid_width + self.grid_width - 10] = 0;
         self.cell_kind[(center_x + 2) * self.grid_width + self.grid_width - 10] = 0;
 
-        self.add_smoke();
-    }
-
-    pub fn add_smoke(&mut self){
-        let center_x = self.grid_width / 2;
-        let center_y = self.grid_height / 2;
-
-        let smoke_radius = 5;
-
-        for i in (center_x.saturating_sub(smoke_radius))..(center_x + smoke_radius) {
-                let index = i * self.grid_width + self.grid_height - 3;
-                if i < self.grid_width {
-                    self.smoke[index] = 1.0; // Set smoke concentration
-                }
-        }
+        let smoke = self.add_scalar("smoke", 0.0);
+        self.add_scalar_source(smoke, ScalarSource {
+            x: center_x,
+            y: self.grid_height - 3,
+            radius: smoke_radius,
+            value: 1.0,
+        });
     }
 
     pub fn simulate(&mut self, dt: f32, gravity: Vec2, num_iters: usize) {
+        self.apply_sources();
+
         self.integrate(dt, gravity);
 
         self.pressures.fill(0.0);
@@ -120,24 +223,58 @@ impl FluidSim {
 
         self.advect_vel(dt);
 
-        self.advect_smoke(dt);
+        self.advect_scalars(dt);
+    }
+
+    pub fn apply_sources(&mut self) {
+        let (w, h) = (self.grid_width, self.grid_height);
+        self.temperature.apply_sources(w, h, &self.cell_kind);
+        for scalar in self.scalars.iter_mut() {
+            scalar.apply_sources(w, h, &self.cell_kind);
+        }
+    }
+
+    /// Boussinesq buoyancy at the center of cell `index`, along the up axis (against gravity).
+    fn buoyancy_at(&self, index: usize) -> f32 {
+        let density: f32 = self.scalars.iter().map(|s| s.values[index]).sum();
+        let temperature = self.temperature.values[index] - self.temperature.ambient;
+        self.buoyancy.beta * temperature - self.buoyancy.alpha * density
     }
 
     pub fn integrate(&mut self, dt: f32, gravity: Vec2) {
         let n = self.grid_width;
+        let g = gravity.length();
+        if g == 0.0 {
+            return;
+        }
+        let up = -gravity / g;
 
         for i in 1..self.grid_width {
             for j in 1..self.grid_height - 1 {
                 let index = i * n + j;
 
+                // v lives on the face between (i, j - 1) and (i, j)
                 if self.cell_kind[index] == 1 && self.cell_kind[i * n + j - 1] == 1 {
-                    self.velocities_y[index] += gravity.y * dt;
+                    let b = 0.5 * (self.buoyancy_at(index) + self.buoyancy_at(i * n + j - 1));
+                    self.velocities_y[index] += up.y * g * b * dt;
+                }
+
+                // u lives on the face between (i - 1, j) and (i, j)
+                if up.x != 0.0 && self.cell_kind[index] == 1 && self.cell_kind[(i - 1) * n + j] == 1 {
+                    let b = 0.5 * (self.buoyancy_at(index) + self.buoyancy_at((i - 1) * n + j));
+                    self.velocities_x[index] += up.x * g * b * dt;
                 }
             }
         }
     }
 
     pub fn solve_incompressibility(&mut self, num_iters: usize) {
+        self.solve_pressure(num_iters, None);
+    }
+
+    /// Pressure projection. `drift` optionally holds a per-cell particle density and its rest value,
+    /// over-dense cells then get pushed apart to counter the volume loss of particle methods.
+    pub(crate) fn solve_pressure(&mut self, num_iters: usize, drift: Option<(&[f32], f32)>) {
         let n = self.grid_width;
         let cp = self.density * self.h / DT;
 
@@ -146,25 +283,31 @@ impl FluidSim {
                 for j in 1..self.grid_height - 1 {
                     let index = i * n + j;
 
-                    // Skip if the cell is not fluid
-                    if self.cell_kind[index] != 1 {
+                    // Skip if the cell is not fluid, air cells keep zero pressure
+                    if self.cell_kind[index] != FLUID_CELL {
                         continue;
                     }
 
-                    let s = self.cell_kind[index] as f32;
-                    let sx0 = self.cell_kind[(i - 1) * n + j] as f32;
-                    let sx1 = self.cell_kind[(i + 1) * n + j] as f32;
-                    let sy0 = self.cell_kind[i * n + j - 1] as f32;
-                    let sy1 = self.cell_kind[i * n + j + 1] as f32;
+                    let sx0 = self.openness((i - 1) * n + j);
+                    let sx1 = self.openness((i + 1) * n + j);
+                    let sy0 = self.openness(i * n + j - 1);
+                    let sy1 = self.openness(i * n + j + 1);
 
                     let s_sum = sx0 + sx1 + sy0 + sy1;
                     if s_sum == 0.0 {
                         continue;
                     }
 
-                    let div = self.velocities_x[(i + 1) * n + j] - self.velocities_x[i * n + j]
+                    let mut div = self.velocities_x[(i + 1) * n + j] - self.velocities_x[i * n + j]
                         + self.velocities_y[i * n + j + 1] - self.velocities_y[i * n + j];
 
+                    if let Some((particle_density, rest_density)) = drift {
+                        let compression = particle_density[index] - rest_density;
+                        if rest_density > 0.0 && compression > 0.0 {
+                            div -= compression;
+                        }
+                    }
+
                     let p = -div / s_sum;
                     let p = p * OVER_RELAXATION;
                     self.pressures[index] += cp * p;
@@ -178,6 +321,11 @@ impl FluidSim {
         }
     }
 
+    /// 0 for solid cells, 1 for anything the flow can pass through.
+    fn openness(&self, index: usize) -> f32 {
+        if self.cell_kind[index] == SOLID_CELL { 0.0 } else { 1.0 }
+    }
+
     fn sample_field(&self, x: f32, y: f32, field: FieldType) -> f32 {
         let n = self.grid_height;
         let h = self.h;
@@ -199,10 +347,10 @@ impl FluidSim {
                 dx = h2;
                 &self.velocities_y
             }
-            FieldType::S => {
+            FieldType::S(values) => {
                 dx = h2;
                 dy = h2;
-                &self.smoke
+                values
             }
         };
 
@@ -271,8 +419,19 @@ impl FluidSim {
         self.velocities_y = new_v;
     }
 
-    pub fn advect_smoke(&mut self, dt: f32) {
-        let mut new_m = self.smoke.clone(); // Assuming `smoke` is a Vec<f32> field
+    pub fn advect_scalars(&mut self, dt: f32) {
+        self.temperature.values = self.advect_scalar(&self.temperature.values, dt);
+        self.temperature.apply_decay(dt);
+
+        for k in 0..self.scalars.len() {
+            let advected = self.advect_scalar(&self.scalars[k].values, dt);
+            self.scalars[k].values = advected;
+            self.scalars[k].apply_decay(dt);
+        }
+    }
+
+    fn advect_scalar(&self, values: &[f32], dt: f32) -> Vec<f32> {
+        let mut new_m = values.to_vec();
         let n = self.grid_width;
         let h = self.h;
         let h2 = 0.5 * h;
@@ -284,51 +443,56 @@ impl FluidSim {
                     let v = (self.velocities_y[i * n + j] + self.velocities_y[i * n + j + 1]) * 0.5;
                     let mut x = i as f32 * h + h2 ;
                     let mut y = j as f32 * h + h2;
-                    // println!("{x}, {y}");
                     x -= dt * u;
                     y -= dt * v;
-                    // println!("{x}, {y}");
-                    let test = self.sample_field(x, y, FieldType::S);
-                    if test != 0.0{
-                        // println!("{test}, {x}, {y}");
-                        new_m[i * n + j] = test;
-                    }
+                    new_m[i * n + j] = self.sample_field(x, y, FieldType::S(values));
                 }
             }
         }
-        self.smoke = new_m;
+        new_m
     }
 
-    pub fn to_vectors(&self) -> Vec<crate::vector::Vector> {
-        let mut vectors = Vec::with_capacity(self.grid_width * self.grid_height);
+    /// Cell-centered velocities laid out x fastest, ready for [`crate::field_texture::FieldTexture::write`].
+    pub fn velocity_field_data(&self) -> Vec<[f32; 4]> {
+        let n = self.grid_width;
+        let mut data = Vec::with_capacity(self.grid_width * self.grid_height);
 
-        for i in 0..self.grid_width {
-            for j in 0..self.grid_height {
-                let index = i * self.grid_width + j;
-                let start = Vec3::new(
-                    i as f32 * self.h,
-                    0.0,
-                    j as f32 * self.h,
-                );
-
-                if self.cell_kind[index] != 1 {
-                    let direction = Vec3::NEG_Y;
-                    vectors.push(crate::vector::Vector::new(start, direction, 5.0));
+        for j in 0..self.grid_height {
+            for i in 0..self.grid_width {
+                if self.cell_kind[i * n + j] == SOLID_CELL {
+                    data.push([0.0; 4]);
                     continue;
                 }
+                let u = if i + 1 < self.grid_width { self.avg_u(i, j) } else { self.velocities_x[i * n + j] };
+                let v = if j + 1 < self.grid_height { self.avg_v(i, j) } else { self.velocities_y[i * n + j] };
+                data.push([u, v, 0.0, 0.0]);
+            }
+        }
 
-                let direction = Vec3::new(
-                    self.velocities_x[index],
-                    0.0,
-                    self.velocities_y[index],
-                );
-                let magnitude = self.smoke[index];
+        data
+    }
 
-                vectors.push(crate::vector::Vector::new(start, direction.normalize(), magnitude));
-            }
+    /// Values of `scalar` laid out x fastest like [`FluidSim::velocity_field_data`].
+    pub fn scalar_field_data(&self, scalar: &ScalarField) -> Vec<f32> {
+        let n = self.grid_width;
+        (0..self.grid_height)
+            .flat_map(|j| (0..self.grid_width).map(move |i| scalar.values[i * n + j]))
+            .collect()
+    }
+
+    /// The cell-centered velocities as a planar [`VectorField`] in the XY plane.
+    pub fn to_vector_field(&self) -> VectorField {
+        let data = self.velocity_field_data().into_iter().map(|v| Vec3::new(v[0], v[1], 0.0)).collect();
+        VectorField {
+            dims: [self.grid_width, self.grid_height, 1],
+            origin: Vec3::new(0.5 * self.h, 0.5 * self.h, 0.0),
+            spacing: Vec3::new(self.h, self.h, self.h),
+            data,
         }
+    }
 
-        vectors
+    pub fn grid_size(&self) -> (usize, usize) {
+        (self.grid_width, self.grid_height)
     }
 
     pub fn extrapolate(&mut self) {
@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use egui_wgpu::wgpu::{Buffer, BufferDescriptor, BufferUsages, COPY_BYTES_PER_ROW_ALIGNMENT, CommandEncoder, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, Texture, TextureAspect, TextureFormat};
+
+struct PendingFrame {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    bgra: bool,
+}
+
+/// Writes rendered frames to a numbered PNG sequence, e.g. for turning a flythrough into a video.
+/// The surface has to be created with `COPY_SRC`.
+pub struct FrameCapture {
+    pub directory: PathBuf,
+    pub frame: u32,
+    pending: Option<PendingFrame>,
+}
+
+impl FrameCapture {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            frame: 0,
+            pending: None,
+        }
+    }
+
+    /// Records a copy of `texture` into `encoder`, call [`FrameCapture::save`] once it has been submitted.
+    /// Copying before the UI pass keeps the windows out of the video.
+    pub fn copy(&mut self, device: &Device, encoder: &mut CommandEncoder, texture: &Texture) {
+        let size = texture.size();
+        let bytes_per_row = (size.width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Frame Capture Buffer"),
+            size: (bytes_per_row * size.height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            Extent3d { width: size.width, height: size.height, depth_or_array_layers: 1 },
+        );
+        self.pending = Some(PendingFrame {
+            buffer,
+            width: size.width,
+            height: size.height,
+            bytes_per_row,
+            bgra: matches!(texture.format(), TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb),
+        });
+    }
+
+    /// Waits for the copied frame and writes it as `frame_00000.png` and onwards.
+    pub fn save(&mut self, device: &Device) -> anyhow::Result<PathBuf> {
+        let pending = self.pending.take().context("No frame was copied")?;
+        let slice = pending.buffer.slice(..);
+        slice.map_async(MapMode::Read, |_| {});
+        device.poll(Maintain::Wait);
+
+        let data = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((pending.width * pending.height * 4) as usize);
+        for row in data.chunks(pending.bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..(pending.width * 4) as usize]);
+        }
+        if pending.bgra {
+            pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+        }
+        let image = image::RgbaImage::from_raw(pending.width, pending.height, pixels).context("Frame has the wrong size")?;
+
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("frame_{:05}.png", self.frame));
+        image.save(&path).with_context(|| format!("Failed to write {}", path.display()))?;
+        self.frame += 1;
+        Ok(path)
+    }
+}
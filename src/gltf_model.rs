@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use anyhow::Context;
+use egui_wgpu::wgpu::{BindGroupLayout, Device, Queue};
+use glam::{Mat3, Mat4, Vec3};
+use image::DynamicImage;
+
+use crate::models::{flat_normals, triangles, Material, MaterialParams, Mesh, Model, ModelVertex};
+use crate::texture::Texture;
+
+/// Triangles of one glTF primitive, moved by the transforms of every node above it. Primitives
+/// without normals get flat ones.
+struct Primitive {
+    name: String,
+    vertices: Vec<ModelVertex>,
+    indices: Vec<u32>,
+    material: Option<usize>,
+}
+
+/// Triangle primitives of the default scene, or the first one if none is marked as default.
+fn read_primitives(document: &gltf::Document, buffers: &[gltf::buffer::Data]) -> anyhow::Result<Vec<Primitive>> {
+    let scene = document.default_scene()
+        .or_else(|| document.scenes().next())
+        .context("The file has no scene")?;
+    let mut primitives = Vec::new();
+    for node in scene.nodes() {
+        read_node(&node, Mat4::IDENTITY, buffers, &mut primitives);
+    }
+    Ok(primitives)
+}
+
+fn read_node(node: &gltf::Node, parent: Mat4, buffers: &[gltf::buffer::Data], primitives: &mut Vec<Primitive>) {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    // Inverse transpose so normals stay perpendicular under non-uniform scales
+    let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+
+    for primitive in node.mesh().iter().flat_map(|mesh| mesh.primitives()) {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            continue;
+        }
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let Some(positions) = reader.read_positions() else {
+            continue;
+        };
+        let positions: Vec<[f32; 3]> = positions.collect();
+        let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+        let tex_coords: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let vertices: Vec<ModelVertex> = positions.iter()
+            .enumerate()
+            .map(|(i, p)| ModelVertex {
+                position: transform.transform_point3(Vec3::from_array(*p)).to_array(),
+                tex_coords: tex_coords.as_ref().map_or([0.0, 0.0], |t| t[i]),
+                normal: normals.as_ref().map_or([0.0; 3], |n| (normal_matrix * Vec3::from_array(n[i])).normalize_or_zero().to_array()),
+            })
+            .collect();
+        let (vertices, indices) = if normals.is_some() { (vertices, indices) } else { flat_normals(&vertices, &indices) };
+
+        primitives.push(Primitive {
+            name: node.mesh().and_then(|m| m.name().map(str::to_string)).unwrap_or_else(|| format!("Node {}", node.index())),
+            vertices,
+            indices,
+            material: primitive.material().index(),
+        });
+    }
+
+    for child in node.children() {
+        read_node(&child, transform, buffers, primitives);
+    }
+}
+
+fn load_texture(device: &Device, queue: &Queue, data: &gltf::image::Data, label: &str, srgb: bool) -> anyhow::Result<Texture> {
+    use gltf::image::Format;
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    let image = match data.format {
+        Format::R8 => image::GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => image::GrayAlphaImage::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => image::RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => image::RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+        format => anyhow::bail!("Texture {label} has unsupported pixel format {format:?}"),
+    };
+    let image = image.with_context(|| format!("Texture {label} is smaller than its size says"))?;
+    Texture::from_image(device, queue, &image, Some(label), srgb)
+}
+
+/// Reads a glTF or GLB file into the same [`Model`] the OBJ loader makes. Node transforms are baked
+/// into the vertices, the metallic-roughness materials are approximated for Blinn-Phong shading.
+pub fn load_model(path: &Path, device: &Device, queue: &Queue, layout: &BindGroupLayout) -> anyhow::Result<Model> {
+    let (document, buffers, images) = gltf::import(path).with_context(|| format!("Failed to load {}", path.display()))?;
+
+    let mut materials = Vec::new();
+    for material in document.materials() {
+        let name = material.name().map_or_else(|| format!("Material {}", materials.len()), str::to_string);
+        let pbr = material.pbr_metallic_roughness();
+        let diffuse_texture = match pbr.base_color_texture() {
+            // The spec has base colors in sRGB and normal maps linear
+            Some(info) => load_texture(device, queue, &images[info.texture().source().index()], &name, true)?,
+            None => Texture::from_color(device, queue, [255; 4], "White Texture", true),
+        };
+        let normal_texture = material.normal_texture()
+            .map(|info| load_texture(device, queue, &images[info.texture().source().index()], &format!("{name} normals"), false))
+            .transpose()?;
+
+        // Dielectrics reflect about 4% and metals their base color, rougher surfaces get broader highlights
+        let base_color = pbr.base_color_factor();
+        let specular = Vec3::splat(0.04).lerp(Vec3::from_slice(&base_color[..3]), pbr.metallic_factor());
+        let roughness = pbr.roughness_factor().max(0.05);
+        let params = MaterialParams {
+            diffuse: base_color,
+            specular: specular.extend((2.0 / roughness.powi(4) - 2.0).clamp(1.0, 512.0)).to_array(),
+            normal_scale: material.normal_texture().map_or(1.0, |n| n.scale()),
+            ..Default::default()
+        };
+        materials.push(Material::new(device, queue, layout, &name, diffuse_texture, normal_texture, params));
+    }
+
+    let primitives = read_primitives(&document, &buffers)?;
+    let triangles: Vec<[Vec3; 3]> = primitives.iter().flat_map(|p| triangles(&p.vertices, &p.indices)).collect();
+    let meshes: Vec<Mesh> = primitives
+        .into_iter()
+        // Materials past the end fall back to the renderer's default
+        .map(|p| Mesh::new(device, &p.name, &p.vertices, &p.indices, p.material.unwrap_or(usize::MAX)))
+        .collect();
+    if triangles.is_empty() {
+        anyhow::bail!("{} has no triangles", path.display());
+    }
+    Ok(Model { meshes, materials, triangles, warnings: Vec::new() })
+}
+
+/// Triangles of the default scene in scene space, the images aren't decoded.
+pub fn load_triangles(path: &Path) -> anyhow::Result<Vec<[Vec3; 3]>> {
+    let gltf = gltf::Gltf::open(path).with_context(|| format!("Failed to load {}", path.display()))?;
+    let buffers = gltf::import_buffers(&gltf.document, path.parent(), gltf.blob.clone())
+        .with_context(|| format!("Failed to load the buffers of {}", path.display()))?;
+
+    let triangles: Vec<[Vec3; 3]> = read_primitives(&gltf.document, &buffers)?
+        .iter()
+        .flat_map(|p| triangles(&p.vertices, &p.indices))
+        .collect();
+    if triangles.is_empty() {
+        anyhow::bail!("{} has no triangles", path.display());
+    }
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn corpus(file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/models").join(file)
+    }
+
+    #[test]
+    fn nodes_place_the_primitives_and_missing_normals_are_generated() {
+        // The buffer is a data URI inside the file
+        let (document, buffers, _) = gltf::import(corpus("transformed.gltf")).unwrap();
+        let primitives = read_primitives(&document, &buffers).unwrap();
+        assert_eq!(primitives.len(), 1);
+
+        let primitive = &primitives[0];
+        assert_eq!(primitive.name, "Triangle");
+        assert_eq!(primitive.material, None);
+        // Scaled by the child node, then lifted by its parent
+        let positions: Vec<[f32; 3]> = primitive.vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, [[0.0, 0.0, 1.0], [2.0, 0.0, 1.0], [0.0, 2.0, 1.0]]);
+        assert!(primitive.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+        assert_eq!(primitive.indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn triangles_match_the_primitives() {
+        let triangles = load_triangles(&corpus("transformed.gltf")).unwrap();
+        assert_eq!(triangles, [[Vec3::new(0.0, 0.0, 1.0), Vec3::new(2.0, 0.0, 1.0), Vec3::new(0.0, 2.0, 1.0)]]);
+    }
+}
@@ -0,0 +1,95 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct GlyphParams {
+    scale: f32,
+    min_magnitude: f32,
+    max_magnitude: f32,
+    colormap: u32,
+    auto_range: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: GlyphParams;
+
+// Bit pattern of the largest magnitude, written with atomicMax by glyph_compute.wgsl
+@group(1) @binding(1)
+var<storage, read> range: u32;
+
+struct VectorInstance {
+    @location(0) start: vec3<f32>,
+    @location(1) magnitude: f32,
+    @location(2) direction: vec3<f32>,
+}
+
+struct GlyphVertex {
+    @location(6) position: vec3<f32>,
+    @location(7) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+// Rotation taking +Y onto `direction`, zero vectors keep the identity
+fn glyph_basis(direction: vec3<f32>) -> mat3x3<f32> {
+    let len = length(direction);
+    if (len < 1e-6) {
+        return mat3x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, 1.0));
+    }
+    let y = direction / len;
+    var helper = vec3<f32>(1.0, 0.0, 0.0);
+    if (abs(y.x) > 0.9) {
+        helper = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let z = normalize(cross(helper, y));
+    let x = cross(y, z);
+    return mat3x3<f32>(x, y, z);
+}
+
+@vertex
+fn vs_main(instance: VectorInstance, vertex: GlyphVertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    var max_magnitude = params.max_magnitude;
+    if (params.auto_range != 0u) {
+        max_magnitude = bitcast<f32>(range);
+    }
+    let t = clamp(instance.magnitude / max(max_magnitude, 1e-6), 0.0, 1.0);
+
+    // Per-instance culling: weak vectors and vectors whose origin is well outside the view collapse
+    // to a point outside the clip volume, so the rasterizer drops all of their triangles
+    let origin_clip = camera.view_proj * vec4<f32>(instance.start, 1.0);
+    let margin = 1.2 * origin_clip.w + params.scale;
+    let outside = origin_clip.w <= -params.scale || any(abs(origin_clip.xy) > vec2<f32>(margin));
+    if (instance.magnitude < params.min_magnitude || outside) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    let rotation = glyph_basis(instance.direction);
+    let local = vertex.position * vec3<f32>(params.scale, params.scale * t, params.scale);
+    let world = instance.start + rotation * local;
+
+    out.clip_position = camera.view_proj * vec4<f32>(world, 1.0);
+    out.normal = rotation * vertex.normal;
+    out.color = colormap(params.colormap, t);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Lines have no normal, keep them unlit
+    if (dot(in.normal, in.normal) < 1e-6) {
+        return vec4<f32>(in.color, 1.0);
+    }
+    let light = normalize(vec3<f32>(0.4, 1.0, 0.6));
+    let diffuse = abs(dot(normalize(in.normal), light));
+    return vec4<f32>(in.color * (0.3 + 0.7 * diffuse), 1.0);
+}
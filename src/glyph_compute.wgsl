@@ -0,0 +1,69 @@
+// Fills the glyph instance buffer from a field, one invocation per grid node.
+// field.wgsl is prepended and the `field_expr` function appended when the pipeline is built.
+
+struct GlyphInstance {
+    start: vec3f,
+    magnitude: f32,
+    direction: vec3f,
+    _padding: f32,
+}
+
+struct GlyphGrid {
+    bounds_min: vec4f,
+    bounds_max: vec4f,
+    dims: vec4u,
+    time: f32,
+    source: u32,
+    field_scale: f32,
+}
+
+const SOURCE_TEXTURE: u32 = 0;
+const SOURCE_EXPRESSION: u32 = 1;
+
+@group(0) @binding(0)
+var<storage, read_write> instances: array<GlyphInstance>;
+
+@group(0) @binding(1)
+var<uniform> grid: GlyphGrid;
+
+@group(0) @binding(2)
+var field: texture_3d<f32>;
+
+@group(0) @binding(3)
+var<uniform> field_params: FieldParams;
+
+@group(0) @binding(4)
+var<storage, read_write> range: atomic<u32>;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let i = global_invocation_id.x;
+    let dims = grid.dims.xyz;
+    if (i >= dims.x * dims.y * dims.z) {
+        return;
+    }
+
+    // Glyphs sit on cell centers of the grid, x fastest
+    let c = vec3u(i % dims.x, (i / dims.x) % dims.y, i / (dims.x * dims.y));
+    let uvw = (vec3f(c) + 0.5) / vec3f(dims);
+    let p = mix(grid.bounds_min.xyz, grid.bounds_max.xyz, uvw);
+
+    var v: vec3f;
+    if (grid.source == SOURCE_EXPRESSION) {
+        v = field_expr(p, grid.time);
+    } else {
+        v = sample_field_texture(field, field_params, p);
+    }
+    v *= grid.field_scale;
+
+    let magnitude = length(v);
+    var direction = vec3f(0.0);
+    if (magnitude > 0.0) {
+        direction = v / magnitude;
+    }
+    instances[i] = GlyphInstance(p, magnitude, direction, 0.0);
+
+    // Non-negative floats order the same as their bit patterns
+    atomicMax(&range, bitcast<u32>(magnitude));
+}
@@ -0,0 +1,471 @@
+use std::sync::Arc;
+
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, CommandEncoder, CompareFunction, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, DepthBiasState, DepthStencilState, Device, ErrorFilter, FragmentState, FrontFace, IndexFormat, LoadOp, Operations, PipelineLayout, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, StoreOp, TextureSampleType, TextureView, TextureViewDimension, VertexState};
+use egui_wgpu::wgpu::TextureFormat::Bgra8UnormSrgb;
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+
+use crate::expr::Expr;
+use crate::field_texture::FieldTexture;
+use crate::models::{GlyphVertex, Vertex};
+use crate::vector::Vector;
+
+pub const COLORMAP_VIRIDIS: u32 = 0;
+pub const COLORMAP_MAGMA: u32 = 1;
+
+/// Glyphs sample the bound field texture.
+pub const SOURCE_TEXTURE: u32 = 0;
+/// Glyphs evaluate the expression set with [`GlyphRenderer::set_expression`].
+pub const SOURCE_EXPRESSION: u32 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GlyphShape {
+    Arrow,
+    Cone,
+    Line,
+}
+
+/// Mirrors `GlyphParams` in glyph.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlyphParams {
+    /// World space length of a glyph at `max_magnitude`.
+    pub scale: f32,
+    /// Vectors weaker than this are culled.
+    pub min_magnitude: f32,
+    /// Magnitude mapped to full length and the top of the colormap.
+    pub max_magnitude: f32,
+    pub colormap: u32,
+    /// Non-zero to use the largest magnitude found by the last [`GlyphRenderer::generate`] instead of `max_magnitude`.
+    pub auto_range: u32,
+    pub _padding: [u32; 3],
+}
+
+/// Where the glyphs of [`GlyphRenderer::generate`] are placed and what they sample,
+/// mirrors `GlyphGrid` in glyph_compute.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlyphGrid {
+    pub bounds_min: [f32; 4],
+    pub bounds_max: [f32; 4],
+    pub dims: [u32; 4],
+    pub time: f32,
+    pub source: u32,
+    pub field_scale: f32,
+    pub _padding: f32,
+}
+
+impl GlyphGrid {
+    /// One glyph per cell of a `dims` grid spanning the box, sampling the field texture.
+    pub fn new(dims: [u32; 3], bounds_min: Vec3, bounds_max: Vec3) -> Self {
+        Self {
+            bounds_min: bounds_min.extend(0.0).to_array(),
+            bounds_max: bounds_max.extend(0.0).to_array(),
+            dims: [dims[0].max(1), dims[1].max(1), dims[2].max(1), 0],
+            time: 0.0,
+            source: SOURCE_TEXTURE,
+            field_scale: 1.0,
+            _padding: 0.0,
+        }
+    }
+
+    fn len(&self) -> u32 {
+        self.dims[0] * self.dims[1] * self.dims[2]
+    }
+}
+
+struct GlyphMesh {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+}
+
+impl GlyphMesh {
+    fn new(device: &Device, label: &str, (vertices, indices): (Vec<GlyphVertex>, Vec<u32>)) -> Self {
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{label} Vertex Buffer")),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{label} Index Buffer")),
+            contents: bytemuck::cast_slice(&indices),
+            usage: BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+        }
+    }
+}
+
+/// Draws one instanced glyph per [`Vector`] on top of whatever is already in the frame.
+/// The instances are written on the GPU by a compute pass, see [`GlyphRenderer::generate`].
+pub struct GlyphRenderer {
+    mesh_pipeline: RenderPipeline,
+    line_pipeline: RenderPipeline,
+
+    arrow: GlyphMesh,
+    cone: GlyphMesh,
+    line: GlyphMesh,
+    pub shape: GlyphShape,
+
+    camera_bind_group: Arc<BindGroup>,
+
+    pub params: GlyphParams,
+    params_buffer: Buffer,
+    params_bind_group: BindGroup,
+    range_buffer: Buffer,
+
+    compute_pipeline: ComputePipeline,
+    compute_bind_group_layout: BindGroupLayout,
+    compute_pipeline_layout: PipelineLayout,
+    grid_buffer: Buffer,
+
+    instance_buffer: Buffer,
+    instance_capacity: usize,
+    num_instances: u32,
+}
+
+impl GlyphRenderer {
+    pub fn new(device: &Device, camera_bind_group_layout: &BindGroupLayout, camera_bind_group: Arc<BindGroup>) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Glyph Shader"),
+            source: ShaderSource::Wgsl(format!("{}\n{}", include_str!("colormap.wgsl"), include_str!("glyph.wgsl")).into()),
+        });
+
+        let params = GlyphParams {
+            scale: 0.1,
+            min_magnitude: 0.0,
+            max_magnitude: 1.0,
+            colormap: COLORMAP_VIRIDIS,
+            auto_range: 1,
+            _padding: [0; 3],
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Glyph Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let range_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Glyph Range Buffer"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Glyph Params Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let params_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Glyph Params Bind Group"),
+            layout: &params_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: range_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Glyph Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &params_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mesh_pipeline = Self::create_pipeline(device, &layout, &shader, PrimitiveTopology::TriangleList);
+        let line_pipeline = Self::create_pipeline(device, &layout, &shader, PrimitiveTopology::LineList);
+
+        let compute_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Glyph Compute Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let compute_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Glyph Compute Pipeline Layout"),
+            bind_group_layouts: &[&compute_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = Self::create_compute_pipeline(device, &compute_pipeline_layout, crate::compute::DEFAULT_FIELD_EXPR);
+        let grid_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Glyph Grid Buffer"),
+            contents: bytemuck::cast_slice(&[GlyphGrid::new([1; 3], Vec3::ZERO, Vec3::ONE)]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let instance_capacity = 1;
+        let instance_buffer = Self::create_instance_buffer(device, instance_capacity);
+
+        Self {
+            mesh_pipeline,
+            line_pipeline,
+            arrow: GlyphMesh::new(device, "Arrow", crate::utils::generate_arrow(12, 0.05, 0.12, 0.7)),
+            cone: GlyphMesh::new(device, "Cone", crate::utils::generate_cone(12, 0.15)),
+            line: GlyphMesh::new(device, "Line", crate::utils::generate_line()),
+            shape: GlyphShape::Arrow,
+            camera_bind_group,
+            params,
+            params_buffer,
+            params_bind_group,
+            range_buffer,
+            compute_pipeline,
+            compute_bind_group_layout,
+            compute_pipeline_layout,
+            grid_buffer,
+            instance_buffer,
+            instance_capacity,
+            num_instances: 0,
+        }
+    }
+
+    fn create_pipeline(device: &Device, layout: &PipelineLayout, shader: &ShaderModule, topology: PrimitiveTopology) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Glyph Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[Vector::DESC, GlyphVertex::desc()],
+            },
+            primitive: PrimitiveState {
+                topology,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: crate::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: Default::default(),
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: Bgra8UnormSrgb,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            multiview: None,
+        })
+    }
+
+    fn create_instance_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Glyph Instance Buffer"),
+            size: (capacity * std::mem::size_of::<Vector>()) as u64,
+            usage: BufferUsages::VERTEX | BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// glyph_compute.wgsl with `field_expr` spliced in at the end, like the particle shader.
+    fn create_compute_pipeline(device: &Device, layout: &PipelineLayout, field_expr: &str) -> ComputePipeline {
+        let source = format!("{}\n{}\n{}\n{}", include_str!("field.wgsl"), include_str!("rng.wgsl"), include_str!("glyph_compute.wgsl"), field_expr);
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("glyph_compute.wgsl"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Glyph Compute Pipeline"),
+            layout: Some(layout),
+            module: &shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+        })
+    }
+
+    /// Recompiles the glyph compute shader around `expr`, keeping the old pipeline if that fails.
+    pub fn set_expression(&mut self, device: &Device, expr: &Expr) -> anyhow::Result<()> {
+        device.push_error_scope(ErrorFilter::Validation);
+        let pipeline = Self::create_compute_pipeline(device, &self.compute_pipeline_layout, &expr.to_wgsl());
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            anyhow::bail!("{error}");
+        }
+        self.compute_pipeline = pipeline;
+        Ok(())
+    }
+
+    /// Records a compute pass that writes one glyph per cell of `grid` into the instance buffer.
+    /// `field` is only read when the grid samples [`SOURCE_TEXTURE`].
+    pub fn generate(&mut self, device: &Device, encoder: &mut CommandEncoder, queue: &Queue, field: &FieldTexture, grid: GlyphGrid) {
+        let count = grid.len();
+        if count as usize > self.instance_capacity {
+            self.instance_capacity = (count as usize).next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&[grid]));
+        queue.write_buffer(&self.range_buffer, 0, bytemuck::cast_slice(&[0_u32]));
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Glyph Compute Bind Group"),
+            layout: &self.compute_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.instance_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.grid_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&field.view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: field.params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: self.range_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Glyph Compute Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(count.div_ceil(64), 1, 1);
+        drop(compute_pass);
+
+        self.num_instances = count;
+    }
+
+    pub fn update_params(&self, queue: &Queue) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+    }
+
+    pub fn render(&self, encoder: &mut CommandEncoder, surface_view: &TextureView, depth_view: &TextureView) {
+        if self.num_instances == 0 {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Glyph Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        let (pipeline, mesh) = match self.shape {
+            GlyphShape::Arrow => (&self.mesh_pipeline, &self.arrow),
+            GlyphShape::Cone => (&self.mesh_pipeline, &self.cone),
+            GlyphShape::Line => (&self.line_pipeline, &self.line),
+        };
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.params_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.num_indices, 0, 0..self.num_instances);
+    }
+}
@@ -0,0 +1,517 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+use egui_wgpu::wgpu::{AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferBindingType, BufferUsages, ColorTargetState, CommandEncoder, CompareFunction, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, DepthBiasState, DepthStencilState, Device, Extent3d, FilterMode, FragmentState, FrontFace, ImageCopyTexture, ImageDataLayout, LoadOp, Operations, Origin3d, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, StorageTextureAccess, StoreOp, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexState};
+use egui_wgpu::wgpu::TextureFormat::Bgra8UnormSrgb;
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+
+use crate::glyph_renderer::COLORMAP_VIRIDIS;
+use crate::rng::Rng;
+use crate::vector_field::VectorField;
+
+/// Mirrors `LicParams` in lic.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LicParams {
+    pub field_dims: [u32; 2],
+    pub extent: [f32; 2],
+    /// Kernel half length in steps.
+    pub steps: u32,
+    /// Step length in output pixels.
+    pub step_size: f32,
+    pub max_speed: f32,
+    pub _padding: f32,
+}
+
+/// Where the LIC texture is drawn, mirrors `LicPlane` in lic_plane.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LicPlane {
+    pub origin: [f32; 4],
+    pub u: [f32; 4],
+    pub v: [f32; 4],
+    /// Non-zero to tint the convolution with the speed.
+    pub overlay: u32,
+    pub colormap: u32,
+    pub _padding: [u32; 2],
+}
+
+/// Line integral convolution of a 2D slice, computed on the GPU and shown as a textured plane.
+pub struct Lic {
+    resolution: u32,
+    noise_texture: Texture,
+    noise_view: TextureView,
+    output_view: TextureView,
+
+    field_texture: Texture,
+    field_view: TextureView,
+
+    pub params: LicParams,
+    params_buffer: Buffer,
+    pub plane: LicPlane,
+    plane_buffer: Buffer,
+
+    compute_pipeline: ComputePipeline,
+    compute_bind_group_layout: BindGroupLayout,
+    compute_bind_group: BindGroup,
+
+    render_pipeline: RenderPipeline,
+    render_bind_group: BindGroup,
+    camera_bind_group: Arc<BindGroup>,
+}
+
+impl Lic {
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        camera_bind_group_layout: &BindGroupLayout,
+        camera_bind_group: Arc<BindGroup>,
+        resolution: u32,
+        seed: u32,
+    ) -> Self {
+        let size = Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 1,
+        };
+
+        let noise_texture = device.create_texture(&TextureDescriptor {
+            label: Some("LIC Noise Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        Self::write_noise(queue, &noise_texture, resolution, seed);
+        let noise_view = noise_texture.create_view(&TextureViewDescriptor::default());
+
+        let output_texture = device.create_texture(&TextureDescriptor {
+            label: Some("LIC Output Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&TextureViewDescriptor::default());
+
+        let field_texture = Self::create_field_texture(device, [1, 1]);
+        let field_view = field_texture.create_view(&TextureViewDescriptor::default());
+
+        let params = LicParams {
+            field_dims: [1, 1],
+            extent: [1.0, 1.0],
+            steps: 20,
+            step_size: 1.0,
+            max_speed: 1.0,
+            _padding: 0.0,
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("LIC Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let plane = LicPlane {
+            origin: [-1.0, -1.0, 0.0, 0.0],
+            u: [2.0, 0.0, 0.0, 0.0],
+            v: [0.0, 2.0, 0.0, 0.0],
+            overlay: 1,
+            colormap: COLORMAP_VIRIDIS,
+            _padding: [0; 2],
+        };
+        let plane_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("LIC Plane Buffer"),
+            contents: bytemuck::cast_slice(&[plane]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let non_filterable = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let compute_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("LIC Compute Bind Group Layout"),
+            entries: &[
+                non_filterable(0),
+                non_filterable(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: TextureFormat::Rgba8Unorm,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let compute_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("lic.wgsl"),
+            source: ShaderSource::Wgsl(include_str!("lic.wgsl").into()),
+        });
+        let compute_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("LIC Compute Pipeline Layout"),
+            bind_group_layouts: &[&compute_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("LIC Compute Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &compute_shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+        });
+        let compute_bind_group = Self::create_compute_bind_group(
+            device,
+            &compute_bind_group_layout,
+            &noise_view,
+            &field_view,
+            &output_view,
+            &params_buffer,
+        );
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("LIC Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+        let render_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("LIC Plane Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let render_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("LIC Plane Bind Group"),
+            layout: &render_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&output_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: plane_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let render_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("lic_plane.wgsl"),
+            source: ShaderSource::Wgsl(format!("{}\n{}", include_str!("colormap.wgsl"), include_str!("lic_plane.wgsl")).into()),
+        });
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("LIC Plane Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &render_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("LIC Plane Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: VertexState {
+                module: &render_shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: crate::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: Default::default(),
+            fragment: Some(FragmentState {
+                module: &render_shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: Bgra8UnormSrgb,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            resolution,
+            noise_texture,
+            noise_view,
+            output_view,
+            field_texture,
+            field_view,
+            params,
+            params_buffer,
+            plane,
+            plane_buffer,
+            compute_pipeline,
+            compute_bind_group_layout,
+            compute_bind_group,
+            render_pipeline,
+            render_bind_group,
+            camera_bind_group,
+        }
+    }
+
+    /// Binary white noise from `seed`, it gives the most contrast after averaging.
+    fn write_noise(queue: &Queue, texture: &Texture, resolution: u32, seed: u32) {
+        let mut rng = Rng::new(seed);
+        let noise: Vec<f32> = (0..resolution * resolution)
+            .map(|_| if rng.next_bool() { 1.0 } else { 0.0 })
+            .collect();
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(&noise),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(resolution * std::mem::size_of::<f32>() as u32),
+                rows_per_image: Some(resolution),
+            },
+            Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Regenerates the noise, call [`Lic::compute`] afterwards to see it.
+    pub fn set_seed(&self, queue: &Queue, seed: u32) {
+        Self::write_noise(queue, &self.noise_texture, self.resolution, seed);
+    }
+
+    fn create_field_texture(device: &Device, dims: [u32; 2]) -> Texture {
+        device.create_texture(&TextureDescriptor {
+            label: Some("LIC Field Texture"),
+            size: Extent3d {
+                width: dims[0],
+                height: dims[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    fn create_compute_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        noise_view: &TextureView,
+        field_view: &TextureView,
+        output_view: &TextureView,
+        params_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("LIC Compute Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(noise_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(field_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(output_view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Uploads a 2D field, i.e. one with exactly one axis of a single sample such as
+    /// [`crate::fluid_vec::FluidSim::to_vector_field`] or a [`VectorField::slice`].
+    /// The plane is placed where the slice sits in world space.
+    pub fn set_field(&mut self, device: &Device, queue: &Queue, field: &VectorField) -> anyhow::Result<()> {
+        let flat: Vec<usize> = (0..3).filter(|&a| field.dims[a] == 1).collect();
+        if flat.len() != 1 {
+            bail!("LIC needs a 2D slice, got a {}x{}x{} field", field.dims[0], field.dims[1], field.dims[2]);
+        }
+        let normal = flat[0];
+        let (a, b) = ((normal + 1) % 3, (normal + 2) % 3);
+        // Keep the plane axes in x, y, z order so xy slices aren't transposed
+        let (a, b) = (a.min(b), a.max(b));
+        let dims = [field.dims[a] as u32, field.dims[b] as u32];
+
+        let mut data = Vec::with_capacity((dims[0] * dims[1]) as usize);
+        for j in 0..field.dims[b] {
+            for i in 0..field.dims[a] {
+                let mut c = [0; 3];
+                c[a] = i;
+                c[b] = j;
+                let v = field.get(c[0], c[1], c[2]);
+                data.push([v[a], v[b], v.length(), 0.0]);
+            }
+        }
+
+        if dims != self.params.field_dims {
+            self.field_texture = Self::create_field_texture(device, dims);
+            self.field_view = self.field_texture.create_view(&TextureViewDescriptor::default());
+            self.compute_bind_group = Self::create_compute_bind_group(
+                device,
+                &self.compute_bind_group_layout,
+                &self.noise_view,
+                &self.field_view,
+                &self.output_view,
+                &self.params_buffer,
+            );
+        }
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture: &self.field_texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(&data),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(dims[0] * std::mem::size_of::<[f32; 4]>() as u32),
+                rows_per_image: Some(dims[1]),
+            },
+            Extent3d {
+                width: dims[0],
+                height: dims[1],
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let (min, max) = field.texture_bounds();
+        let extent = max - min;
+        let mut origin = min;
+        origin[normal] = field.origin[normal];
+        self.params.field_dims = dims;
+        self.params.extent = [extent[a], extent[b]];
+        self.params.max_speed = data.iter().map(|d| d[2]).fold(0.0, f32::max);
+        self.plane.origin = origin.extend(0.0).to_array();
+        self.plane.u = (Vec3::AXES[a] * extent[a]).extend(0.0).to_array();
+        self.plane.v = (Vec3::AXES[b] * extent[b]).extend(0.0).to_array();
+        Ok(())
+    }
+
+    pub fn compute(&self, encoder: &mut CommandEncoder, queue: &Queue) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+        queue.write_buffer(&self.plane_buffer, 0, bytemuck::cast_slice(&[self.plane]));
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("LIC Compute Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+        let groups = self.resolution.div_ceil(8);
+        compute_pass.dispatch_workgroups(groups, groups, 1);
+    }
+
+    pub fn render(&self, encoder: &mut CommandEncoder, surface_view: &TextureView, depth_view: &TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("LIC Plane Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.render_bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -0,0 +1,78 @@
+// Line integral convolution: every output pixel averages the noise along the streamline through it.
+
+struct LicParams {
+    field_dims: vec2u,
+    // World size of the slice, converts field velocities to texture space
+    extent: vec2f,
+    steps: u32,
+    // In output pixels
+    step_size: f32,
+    max_speed: f32,
+}
+
+@group(0) @binding(0)
+var noise: texture_2d<f32>;
+
+// xy is the in-plane velocity, z the full speed
+@group(0) @binding(1)
+var field: texture_2d<f32>;
+
+@group(0) @binding(2)
+var output: texture_storage_2d<rgba8unorm, write>;
+
+@group(0) @binding(3)
+var<uniform> params: LicParams;
+
+fn field_load(c: vec2i) -> vec3f {
+    return textureLoad(field, clamp(c, vec2i(0), vec2i(params.field_dims) - 1), 0).xyz;
+}
+
+// Bilinear, the field samples sit on texel centers
+fn field_at(uv: vec2f) -> vec3f {
+    let g = uv * vec2f(params.field_dims) - 0.5;
+    let c0 = vec2i(floor(g));
+    let t = g - floor(g);
+    let x0 = mix(field_load(c0), field_load(c0 + vec2i(1, 0)), t.x);
+    let x1 = mix(field_load(c0 + vec2i(0, 1)), field_load(c0 + vec2i(1, 1)), t.x);
+    return mix(x0, x1, t.y);
+}
+
+fn noise_at(uv: vec2f) -> f32 {
+    let size = vec2i(textureDimensions(noise));
+    return textureLoad(noise, clamp(vec2i(uv * vec2f(size)), vec2i(0), size - 1), 0).r;
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if (any(global_invocation_id.xy >= size)) {
+        return;
+    }
+    let pixels = vec2f(size);
+    let start = (vec2f(global_invocation_id.xy) + 0.5) / pixels;
+
+    var sum = noise_at(start);
+    var count = 1.0;
+    for (var direction = -1.0; direction <= 1.0; direction += 2.0) {
+        var uv = start;
+        for (var i = 0u; i < params.steps; i++) {
+            // Step a fixed number of pixels along the field, so the kernel has the same length everywhere
+            let v = field_at(uv).xy / params.extent * pixels;
+            if (dot(v, v) < 1e-12) {
+                break;
+            }
+            uv += direction * normalize(v) * params.step_size / pixels;
+            if (any(uv < vec2f(0.0)) || any(uv > vec2f(1.0))) {
+                break;
+            }
+            sum += noise_at(uv);
+            count += 1.0;
+        }
+    }
+
+    // Averaging flattens the binary noise towards grey, stretch it back out
+    let intensity = clamp(0.5 + (sum / count - 0.5) * sqrt(count) * 0.5, 0.0, 1.0);
+    let speed = clamp(field_at(start).z / max(params.max_speed, 1e-6), 0.0, 1.0);
+    textureStore(output, global_invocation_id.xy, vec4f(intensity, speed, 0.0, 1.0));
+}
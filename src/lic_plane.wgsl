@@ -0,0 +1,53 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct LicPlane {
+    origin: vec4<f32>,
+    u: vec4<f32>,
+    v: vec4<f32>,
+    overlay: u32,
+    colormap: u32,
+}
+
+@group(1) @binding(0)
+var lic: texture_2d<f32>;
+
+@group(1) @binding(1)
+var lic_sampler: sampler;
+
+@group(1) @binding(2)
+var<uniform> plane: LicPlane;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 1.0), vec2<f32>(0.0, 1.0),
+    );
+    let c = corners[index];
+
+    var out: VertexOutput;
+    let world = plane.origin.xyz + c.x * plane.u.xyz + c.y * plane.v.xyz;
+    out.clip_position = camera.view_proj * vec4<f32>(world, 1.0);
+    out.uv = c;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let s = textureSample(lic, lic_sampler, in.uv);
+    var color = vec3<f32>(s.r);
+    if (plane.overlay != 0u) {
+        color = colormap(plane.colormap, s.g) * (0.2 + 0.8 * s.r);
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -1,12 +1,14 @@
 extern crate core;
 
+use std::borrow::Cow;
 use std::f32::consts::PI;
 use std::sync::Arc;
+use std::time::Instant;
 use egui::Slider;
 use egui_wgpu::{ScreenDescriptor, wgpu};
 use egui_wgpu::wgpu::{InstanceDescriptor, PowerPreference, RequestAdapterOptions, TextureFormat};
 use egui_wgpu::wgpu::util::DeviceExt;
-use glam::Vec3;
+use glam::{Vec2, Vec3};
 use winit::dpi::PhysicalSize;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
@@ -20,10 +22,63 @@ mod vector;
 mod camera;
 mod models;
 mod texture;
-mod state;
 mod utils;
 mod fluid_vec;
 mod compute;
+mod flip;
+mod field_texture;
+mod vector_field;
+mod expr;
+mod glyph_renderer;
+mod streamlines;
+mod streamline_renderer;
+mod lic;
+mod scalar_overlay;
+mod topology;
+mod frame_capture;
+mod point_cull;
+mod profiler;
+mod sim_clock;
+mod rng;
+mod emitter;
+mod attributes;
+mod particle_export;
+mod colliders;
+mod mesh_renderer;
+mod gltf_model;
+mod sampling;
+
+/// Field that glyphs and streamlines are built from.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum FieldSource {
+    Fluid,
+    LoadedField,
+    Expression,
+}
+
+/// CPU side copy of the field picked by `source` for the analyses that run on the CPU.
+fn cpu_field<'a>(
+    source: FieldSource,
+    fluid: &fluid_vec::FluidSim,
+    loaded_field: Option<&'a vector_field::VectorField>,
+    expression: Option<&expr::Expr>,
+    time: f32,
+) -> Option<Cow<'a, vector_field::VectorField>> {
+    match source {
+        FieldSource::Fluid => Some(Cow::Owned(fluid.to_vector_field().fit_to_cube(1.0))),
+        FieldSource::LoadedField => loaded_field.map(Cow::Borrowed),
+        FieldSource::Expression => expression.map(|e| {
+            Cow::Owned(e.to_vector_field([32, 32, 32], Vec3::splat(-1.0), Vec3::splat(1.0), time))
+        }),
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum SeedShape {
+    Point,
+    Line,
+    Plane,
+}
 
 #[tokio::main]
 async fn main() {
@@ -52,13 +107,22 @@ async fn main() {
         .await
         .expect("Failed to find an appropriate adapter");
 
-    let features = wgpu::Features::POLYGON_MODE_POINT;
+    // Timestamp queries are optional, the profiler falls back to CPU timings without them
+    let features = wgpu::Features::POLYGON_MODE_POINT | (adapter.features() & profiler::Profiler::FEATURES);
+    // 8M particles' attributes don't fit into the default buffer limits, fewer particles on GPUs that can't bind them
+    let particle_count = compute::particle_count(&adapter.limits());
+    let default_limits = wgpu::Limits::default();
+    let particle_buffer_size = compute::particle_buffer_size(particle_count);
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
                 required_features: features,
-                required_limits: Default::default(),
+                required_limits: wgpu::Limits {
+                    max_buffer_size: default_limits.max_buffer_size.max(particle_buffer_size),
+                    max_storage_buffer_binding_size: default_limits.max_storage_buffer_binding_size.max(particle_buffer_size as u32),
+                    ..default_limits
+                },
             },
             None,
         )
@@ -74,8 +138,14 @@ async fn main() {
         .find(|d| **d == selected_format)
         .expect("failed to select proper surface texture format!");
 
+    // Frame capture copies straight out of the swapchain
+    let can_capture = swapchain_capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC);
     let mut config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        usage: if can_capture {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        },
         format: *swapchain_format,
         width: initial_width,
         height: initial_height,
@@ -92,6 +162,10 @@ async fn main() {
     let (mut camera, camera_bind_group_layout) = crate::camera::CameraBundle::new(camera::Camera {
         pos: Vec3::new(0.0, 2.0, 3.0),
         rotation: (0.0, PI / 6.0),
+        mode: camera::CameraMode::Fly,
+        projection: camera::Projection::Perspective,
+        target: Vec3::ZERO,
+        distance: 13f32.sqrt(),
         up: Vec3::Y,
         aspect_ratio: 1360.0 / 768.0,
         fov_y: 45.0,
@@ -101,17 +175,31 @@ async fn main() {
 
     let (camera_buffer, camera_bind_group) = camera.get_gpu_side();
 
-    let point_buffer_rust: Vec<[f32; 4]> = (0..8_388_608).map(|i| {
-        let phi = std::f32::consts::PI * (3.0 - (5.0f32).sqrt()); // Golden angle
-        let y = 1.0 - (2.0 * i as f32 / 8_388_608.0); // y-coordinate from -1 to 1
-        let radius = (1.0 - y * y).sqrt(); // radius at this y level
-        let theta = phi * i as f32; // azimuthal angle
+    let mut bookmarks: Vec<camera::Bookmark> = Vec::new();
+    let mut bookmark_name = String::from("View 1");
+    let mut bookmarks_path = String::from("camera.json");
+    let mut timeline = camera::Timeline::default();
+    let mut keyframe_time = 0.0_f32;
+    let mut last_frame = Instant::now();
+    let mut frame_capture = frame_capture::FrameCapture::new("frames");
+    let mut capture_directory = String::from("frames");
+    let mut record_frames = false;
+    let mut camera_status = String::new();
 
-        let x = radius * theta.cos();
-        let z = radius * theta.sin();
+    // Bindings and sensitivity, the defaults apply without the file
+    let mut controls_path = String::from("controls.json");
+    if std::path::Path::new(&controls_path).exists() {
+        match camera::Controls::load(&controls_path) {
+            Ok(controls) => camera.controls = controls,
+            Err(e) => camera_status = format!("{e:#}"),
+        }
+    }
 
-        [x, y, z, 1.0]
-    }).collect::<Vec<_>>();
+    // Every random draw derives from this, the same seed reproduces a run from startup on
+    let mut random_seed = 0_u32;
+    let point_buffer_rust: Vec<[f32; 4]> = (0..particle_count)
+        .map(|i| compute::scatter(i, random_seed).0.extend(1.0).to_array())
+        .collect::<Vec<_>>();
 
     //create a buffer that will create a force acting perpendicular to the radius of the sphere
 
@@ -119,10 +207,97 @@ async fn main() {
     let point_buffer = Arc::new(device.create_buffer_init(&egui_wgpu::wgpu::util::BufferInitDescriptor {
         label: Some("Point Buffer"),
         contents: bytemuck::cast_slice(point_buffer_rust.as_slice()),
-        usage: wgpu::BufferUsages::VERTEX | egui_wgpu::wgpu::BufferUsages::STORAGE,
+        usage: wgpu::BufferUsages::VERTEX | egui_wgpu::wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
     }));
 
     let point_buffer_size = point_buffer_rust.len() as u32;
+    let mut glyph_renderer = glyph_renderer::GlyphRenderer::new(&device, &camera_bind_group_layout, camera_bind_group.clone());
+    let mut show_glyphs = false;
+    let mut glyph_source = FieldSource::Fluid;
+    let mut glyph_stride = 2_usize;
+    let mut auto_glyph_range = true;
+
+    let mut streamline_renderer = streamline_renderer::StreamlineRenderer::new(&device, &camera_bind_group_layout, camera_bind_group.clone());
+    let mut streamline_params = streamlines::StreamlineParams::default();
+    let mut show_streamlines = false;
+    let mut streamline_source = FieldSource::Fluid;
+    let mut seed_shape = SeedShape::Line;
+    let mut seed_center = Vec3::ZERO;
+    let mut seed_extent = 0.8_f32;
+    let mut seed_count = 16_usize;
+    let mut streamline_tubes = true;
+    let mut tube_radius = 0.005_f32;
+    let mut retrace_streamlines = false;
+    let mut live_streamlines = false;
+
+    let mut lic = lic::Lic::new(&device, &queue, &camera_bind_group_layout, camera_bind_group.clone(), 512, 0);
+    let mut show_lic = false;
+    let mut lic_source = FieldSource::Fluid;
+    let mut slice_axis = 2_usize;
+    let mut slice_position = 0.5_f32;
+    let mut lic_status = String::new();
+    // What the uploaded slice was cut from, it's only cut again once one of them changes
+    let mut lic_slice: Option<(FieldSource, usize, f32, f32)> = None;
+    let mut lic_stale = true;
+
+    let mut mesh_renderer = mesh_renderer::MeshRenderer::new(&device, &queue, &camera_bind_group_layout, camera_bind_group.clone());
+    let mut scene_meshes: Vec<mesh_renderer::SceneMesh> = Vec::new();
+    let mut show_meshes = false;
+    let mut scene_mesh_path = String::from("model.obj");
+    let mut mesh_status = String::new();
+    let mut mesh_sample_mode = sampling::SampleMode::Surface;
+    let mut mesh_sample_speed = 0.0;
+    // Drawn on a worker thread, uploaded with the particle pass of the frame it's done in
+    let mut mesh_sampling: Option<sampling::SampleJob> = None;
+
+    let mut separatrix_renderer = streamline_renderer::StreamlineRenderer::new(&device, &camera_bind_group_layout, camera_bind_group.clone());
+    separatrix_renderer.style.colormap = glyph_renderer::COLORMAP_MAGMA;
+    let mut show_topology = false;
+    let mut topology_source = FieldSource::Fluid;
+    let mut analyze_topology = false;
+    let mut show_separatrices = true;
+    let mut critical_points: Vec<topology::CriticalPoint> = Vec::new();
+
+    // Rising plume that the particles can trace in tracer mode
+    let mut fluid = fluid_vec::FluidSim::new(64, 64, 1.0 / 64.0, 1000.0);
+    let plume_source = fluid_vec::ScalarSource { x: 32, y: 4, radius: 3, value: 1.0 };
+    fluid.add_heat_source(plume_source);
+    let smoke = fluid.add_scalar("smoke", 0.1);
+    fluid.add_scalar_source(smoke, plume_source);
+
+    let (fluid_width, fluid_height) = fluid.grid_size();
+    let fluid_texture = field_texture::FieldTexture::new(
+        &device,
+        [fluid_width as u32, fluid_height as u32, 1],
+        Vec3::splat(-1.0),
+        Vec3::splat(1.0),
+    );
+    let mut scalar_overlay = scalar_overlay::ScalarOverlay::new(
+        &device,
+        &camera_bind_group_layout,
+        camera_bind_group.clone(),
+        [fluid_width as u32, fluid_height as u32],
+        Vec3::splat(-1.0),
+        Vec3::splat(1.0),
+    );
+    let mut show_scalars = false;
+    let mut overlay_scalar = String::from("smoke");
+    let mut particle_mode = compute::MODE_ATTRACTOR;
+
+    let mut expression_text = String::from("vec3(-y, x, 0.1 * z)");
+    let mut expression: Option<expr::Expr> = None;
+    let mut expression_status = String::new();
+
+    // Field loaded from disk, replaces the fluid as the tracer source while active
+    let mut file_texture: Option<field_texture::FieldTexture> = None;
+    let mut loaded_field: Option<vector_field::VectorField> = None;
+    let mut use_loaded_field = false;
+    let mut fit_loaded_field = true;
+    let mut field_path = String::new();
+    let mut field_status = String::new();
+
+    let mut compute = crate::compute::Compute::new(&device, point_buffer.clone(), point_buffer_size, random_seed, &fluid_texture);
+
     let mut renderer = renderer::Renderer::new(
         &device,
         &config,
@@ -130,10 +305,31 @@ async fn main() {
         camera_buffer,
         camera_bind_group,
         point_buffer.clone(),
-        point_buffer_size,
+        renderer::PointColoring::new(&device, compute.velocity_buffer(), compute.attribute_buffer()),
     );
+    let mut ply_path = String::from("particles.ply");
+    let mut ply_status = String::new();
+
+    let mut flip = flip::FlipSim::new(64, 1.0 / 64.0, 1000.0, Vec2::new(0.6, 0.8));
+    let flip_buffer = Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("FLIP Point Buffer"),
+        size: (flip.num_particles() as usize * std::mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    }));
+    let mut show_flip = false;
 
-    let mut compute = crate::compute::Compute::new(&device, point_buffer.clone(), point_buffer_size);
+    // One step per frame at 60 Hz, the rate everything was tuned at
+    let mut sim_clock = sim_clock::SimClock::new(60.0);
+    let mut reset_particles = false;
+    let mut show_emitters = false;
+    let mut emitter_mesh_path = String::from("emitter.obj");
+    let mut emitter_status = String::new();
+
+    let mut profiler = profiler::Profiler::new(&device, &queue);
+    let mut show_profiler = false;
+    let mut profile_path = String::from("profile.csv");
+    let mut profile_status = String::new();
 
     let mut scale_factor = 1.0;
     let mut process_inputs = true;
@@ -185,7 +381,21 @@ async fn main() {
                     }
                     WindowEvent::RedrawRequested => {
                         if process_inputs {
-                            camera.handle_inputs();
+                            let ctx = egui_renderer.context();
+                            camera.handle_inputs(ctx.is_pointer_over_area() || ctx.wants_pointer_input());
+                        }
+                        if let Some(cursor) = camera.take_focus_request() {
+                            let depth = renderer.read_depth(&device, &queue, cursor.0 as u32, cursor.1 as u32);
+                            camera.focus_at(cursor, depth, &config);
+                        }
+                        let now = Instant::now();
+                        let frame_time = (now - last_frame).as_secs_f32();
+                        last_frame = now;
+                        // Recordings step a fixed amount per frame so they come out the same every time,
+                        // otherwise playback follows the wall clock, capped so stalls don't skip ahead
+                        let recording = record_frames && timeline.playing;
+                        if let Some(pose) = timeline.advance(if recording { 1.0 / 60.0 } else { frame_time.min(0.25) }) {
+                            camera.set_pose(pose);
                         }
                         let surface_texture = surface
                             .get_current_texture()
@@ -200,14 +410,202 @@ async fn main() {
                                 label: None,
                             });
 
-                        compute.compute(&mut encoder, &queue);
+                        profiler.begin_frame(&device);
+                        let steps = sim_clock.tick(recording.then_some(1.0 / 60.0)).min(compute::MAX_SUBSTEPS);
+                        // The fluid solvers dissipate energy and can't be run backwards, they hold while reversed
+                        let cpu_steps = if sim_clock.reversed { 0 } else { steps };
+
+                        let fluid_in_use = (!show_flip && particle_mode == compute::MODE_TRACER && !use_loaded_field)
+                            || (show_glyphs && glyph_source == FieldSource::Fluid)
+                            || (show_streamlines && live_streamlines && streamline_source == FieldSource::Fluid)
+                            || (show_lic && lic_source == FieldSource::Fluid)
+                            || show_scalars;
+                        if fluid_in_use && cpu_steps > 0 {
+                            profiler.begin_scope(&mut encoder, "Fluid");
+                            for _ in 0..cpu_steps {
+                                fluid.simulate(1.0 / 60.0, fluid_vec::GRAVITY_VEC, 40);
+                            }
+                            fluid_texture.write(&queue, &fluid.velocity_field_data());
+                            lic_stale |= lic_source == FieldSource::Fluid;
+                            profiler.end_scope(&mut encoder);
+                        }
+
+                        compute.inputs.c1 = v1;
+                        compute.inputs.c2 = v2;
+                        compute.inputs.mode = particle_mode;
+                        profiler.begin_scope(&mut encoder, "Particles");
+                        if reset_particles {
+                            compute.reset(&mut encoder, &queue, random_seed);
+                            sim_clock.step_count = 0;
+                            reset_particles = false;
+                        }
+                        if let Some(job) = mesh_sampling.take_if(|job| job.is_finished()) {
+                            let seed = job.seed;
+                            match job.join() {
+                                Ok(samples) => {
+                                    compute.reset_to(&device, &queue, seed, &samples);
+                                    sim_clock.step_count = 0;
+                                    mesh_status = format!("Seeded {} particles", samples.positions.len());
+                                }
+                                Err(e) => mesh_status = format!("{e:#}"),
+                            }
+                        }
+                        if show_flip {
+                            for _ in 0..cpu_steps {
+                                flip.simulate(1.0 / 60.0, fluid_vec::GRAVITY_VEC);
+                            }
+                            flip.write_points(&queue, &flip_buffer);
+                            profiler.set_particles(flip.num_particles() * cpu_steps);
+                        } else {
+                            compute.compute(&mut encoder, &queue, steps, sim_clock.direction());
+                            profiler.set_particles(compute.points * steps);
+                        }
+                        profiler.end_scope(&mut encoder);
+                        profiler.begin_scope(&mut encoder, "Points");
                         renderer.render(&mut encoder, &surface_view);
+                        profiler.end_scope(&mut encoder);
+
+                        if !scene_meshes.is_empty() {
+                            profiler.begin_scope(&mut encoder, "Meshes");
+                            mesh_renderer.update_lighting(&queue, Vec3::from_array(camera.pose().pos));
+                            mesh_renderer.render(&mut encoder, &surface_view, renderer.depth_view(), &scene_meshes);
+                            profiler.end_scope(&mut encoder);
+                        }
+
+                        if show_scalars {
+                            if let Some(scalar) = fluid.scalar(&overlay_scalar) {
+                                profiler.begin_scope(&mut encoder, "Scalars");
+                                scalar_overlay.write(&queue, &fluid.scalar_field_data(scalar));
+                                scalar_overlay.render(&mut encoder, &queue, &surface_view, renderer.depth_view());
+                                profiler.end_scope(&mut encoder);
+                            }
+                        }
+
+                        if show_lic {
+                            profiler.begin_scope(&mut encoder, "LIC");
+                            let time = match (lic_source, &expression) {
+                                (FieldSource::Expression, Some(e)) if e.uses_time() => compute.inputs.time,
+                                _ => 0.0,
+                            };
+                            let inputs = (lic_source, slice_axis, slice_position, time);
+                            if lic_stale || lic_slice != Some(inputs) {
+                                // 2D sources are drawn as they are, 3D ones are cut along the slice axis
+                                let slice = match (lic_source, &loaded_field, &expression) {
+                                    (FieldSource::LoadedField, Some(field), _) => {
+                                        let index = (slice_position * (field.dims[slice_axis] - 1) as f32).round() as usize;
+                                        Some(field.slice(slice_axis, index))
+                                    }
+                                    (FieldSource::Expression, _, Some(e)) => {
+                                        let mut dims = [128; 3];
+                                        dims[slice_axis] = 1;
+                                        let (mut min, mut max) = (Vec3::splat(-1.0), Vec3::splat(1.0));
+                                        min[slice_axis] = 2.0 * slice_position - 1.0;
+                                        max[slice_axis] = min[slice_axis];
+                                        Some(e.to_vector_field(dims, min, max, time))
+                                    }
+                                    (FieldSource::Fluid, _, _) => Some(fluid.to_vector_field().fit_to_cube(1.0)),
+                                    _ => None,
+                                };
+                                lic_slice = None;
+                                match slice.map(|slice| lic.set_field(&device, &queue, &slice)) {
+                                    Some(Ok(())) => {
+                                        lic_status.clear();
+                                        lic_slice = Some(inputs);
+                                    }
+                                    Some(Err(e)) => lic_status = format!("{e:#}"),
+                                    None => {}
+                                }
+                                lic_stale = false;
+                            }
+                            if lic_slice.is_some() {
+                                lic.compute(&mut encoder, &queue);
+                                lic.render(&mut encoder, &surface_view, renderer.depth_view());
+                            }
+                            profiler.end_scope(&mut encoder);
+                        }
+
+                        if show_glyphs {
+                            profiler.begin_scope(&mut encoder, "Glyphs");
+                            let stride = glyph_stride as u32;
+                            let (field, mut grid) = match (glyph_source, &file_texture) {
+                                (FieldSource::LoadedField, Some(texture)) => {
+                                    let (min, max) = texture.bounds();
+                                    (texture, glyph_renderer::GlyphGrid::new(texture.dims().map(|d| d / stride), min, max))
+                                }
+                                (FieldSource::Expression, _) => {
+                                    let mut grid = glyph_renderer::GlyphGrid::new([32 / stride; 3], Vec3::splat(-1.0), Vec3::splat(1.0));
+                                    grid.source = glyph_renderer::SOURCE_EXPRESSION;
+                                    grid.time = compute.inputs.time;
+                                    (&fluid_texture, grid)
+                                }
+                                _ => {
+                                    let (min, max) = fluid_texture.bounds();
+                                    (&fluid_texture, glyph_renderer::GlyphGrid::new(fluid_texture.dims().map(|d| d / stride), min, max))
+                                }
+                            };
+                            // Same scale the particles feel, so the glyphs show the field they move through
+                            grid.field_scale = compute.inputs.field_scale;
+                            glyph_renderer.params.auto_range = auto_glyph_range as u32;
+                            glyph_renderer.update_params(&queue);
+                            glyph_renderer.generate(&device, &mut encoder, &queue, field, grid);
+                            glyph_renderer.render(&mut encoder, &surface_view, renderer.depth_view());
+                            profiler.end_scope(&mut encoder);
+                        }
+
+                        if show_streamlines {
+                            profiler.begin_scope(&mut encoder, "Streamlines");
+                        }
+                        if show_streamlines && (retrace_streamlines || live_streamlines) {
+                            retrace_streamlines = false;
+                            if let Some(field) = cpu_field(streamline_source, &fluid, loaded_field.as_ref(), expression.as_ref(), compute.inputs.time) {
+                                let seeds = match seed_shape {
+                                    SeedShape::Point => streamlines::Seeds::Point(seed_center),
+                                    SeedShape::Line => streamlines::Seeds::Line {
+                                        start: seed_center - seed_extent * Vec3::X,
+                                        end: seed_center + seed_extent * Vec3::X,
+                                        count: seed_count,
+                                    },
+                                    SeedShape::Plane => streamlines::Seeds::Plane {
+                                        origin: seed_center - seed_extent * (Vec3::X + Vec3::Y),
+                                        u: 2.0 * seed_extent * Vec3::X,
+                                        v: 2.0 * seed_extent * Vec3::Y,
+                                        counts: [seed_count, seed_count],
+                                    },
+                                };
+                                let lines = streamlines::trace_all(&field, &seeds, &streamline_params);
+                                streamline_renderer.set_streamlines(&device, &queue, &lines, streamline_tubes.then_some(tube_radius));
+                            }
+                        }
+                        if show_streamlines {
+                            streamline_renderer.render(&mut encoder, &surface_view, renderer.depth_view());
+                            profiler.end_scope(&mut encoder);
+                        }
+
+                        if analyze_topology {
+                            analyze_topology = false;
+                            profiler.begin_scope(&mut encoder, "Topology");
+                            if let Some(field) = cpu_field(topology_source, &fluid, loaded_field.as_ref(), expression.as_ref(), compute.inputs.time) {
+                                critical_points = topology::find_critical_points(&field);
+                                let lines = topology::separatrices(&field, &critical_points, &streamline_params);
+                                separatrix_renderer.set_streamlines(&device, &queue, &lines, None);
+                            }
+                            profiler.end_scope(&mut encoder);
+                        }
+                        if show_topology && show_separatrices {
+                            separatrix_renderer.render(&mut encoder, &surface_view, renderer.depth_view());
+                        }
+                        let view_proj = camera.view_projection();
 
                         let screen_descriptor = ScreenDescriptor {
                             size_in_pixels: [config.width, config.height],
                             pixels_per_point: window.scale_factor() as f32 * scale_factor,
                         };
 
+                        if recording {
+                            frame_capture.copy(&device, &mut encoder, &surface_texture.texture);
+                        }
+
+                        profiler.begin_scope(&mut encoder, "UI");
                         egui_renderer.draw(
                             &device,
                             &queue,
@@ -216,6 +614,187 @@ async fn main() {
                             &surface_view,
                             screen_descriptor,
                             |ctx| {
+                                if show_topology {
+                                    // Labels for the critical points, drawn over the scene but under the windows
+                                    let painter = ctx.layer_painter(egui::LayerId::background());
+                                    let screen = ctx.screen_rect();
+                                    for point in &critical_points {
+                                        let clip = view_proj * point.position.extend(1.0);
+                                        if clip.w <= 0.0 {
+                                            continue;
+                                        }
+                                        let ndc = clip.truncate() / clip.w;
+                                        let pos = egui::pos2(
+                                            screen.left() + (0.5 + 0.5 * ndc.x) * screen.width(),
+                                            screen.top() + (0.5 - 0.5 * ndc.y) * screen.height(),
+                                        );
+                                        let [r, g, b] = point.kind.color();
+                                        let color = egui::Color32::from_rgb(r, g, b);
+                                        painter.circle_filled(pos, 5.0, color);
+                                        painter.text(pos + egui::vec2(8.0, 0.0), egui::Align2::LEFT_CENTER, point.kind.label(), egui::FontId::proportional(13.0), color);
+                                    }
+                                }
+
+                                if show_emitters {
+                                    egui::Window::new("Emitters")
+                                        .resizable(true)
+                                        .vscroll(true)
+                                        .show(ctx, |ui| {
+                                            let emitters = &mut compute.emitters;
+                                            if ui.checkbox(&mut emitters.enabled, "Spawn particles from emitters").changed() {
+                                                reset_particles = true;
+                                            }
+                                            ui.horizontal(|ui| {
+                                                ui.label("Mesh file");
+                                                ui.text_edit_singleline(&mut emitter_mesh_path);
+                                            });
+
+                                            let mut remove = None;
+                                            let mut meshes_changed = false;
+                                            for (i, emitter) in emitters.emitters.iter_mut().enumerate() {
+                                                ui.separator();
+                                                ui.horizontal(|ui| {
+                                                    ui.label(format!("Emitter {}", i + 1));
+                                                    if ui.button("Load mesh").clicked() {
+                                                        match emitter::EmitterMesh::load(&emitter_mesh_path) {
+                                                            Ok(mesh) => {
+                                                                emitter_status = format!("Loaded {} triangles from {}", mesh.triangles.len(), mesh.name);
+                                                                emitter.mesh = Some(mesh);
+                                                                emitter.shape = emitter::EmitterShape::Mesh;
+                                                                meshes_changed = true;
+                                                            }
+                                                            Err(e) => emitter_status = format!("{e:#}"),
+                                                        }
+                                                    }
+                                                    if ui.button("Remove").clicked() {
+                                                        remove = Some(i);
+                                                    }
+                                                });
+                                                ui.push_id(i, |ui| emitter.ui(ui));
+                                            }
+                                            // Mesh ranges are stored per emitter index
+                                            if let Some(i) = remove {
+                                                emitters.emitters.remove(i);
+                                                meshes_changed = true;
+                                            }
+                                            if meshes_changed {
+                                                emitters.upload_meshes(&device);
+                                            }
+
+                                            ui.separator();
+                                            if ui.add_enabled(emitters.emitters.len() < emitter::MAX_EMITTERS, egui::Button::new("Add emitter")).clicked() {
+                                                emitters.emitters.push(emitter::Emitter::default());
+                                            }
+                                            if !emitter_status.is_empty() {
+                                                ui.label(&emitter_status);
+                                            }
+                                        });
+                                }
+
+                                if show_meshes {
+                                    egui::Window::new("Meshes")
+                                        .resizable(true)
+                                        .vscroll(true)
+                                        .show(ctx, |ui| {
+                                            let colliders = &mut compute.colliders;
+                                            let mut rebake = false;
+                                            ui.horizontal(|ui| {
+                                                ui.label("OBJ or glTF file");
+                                                ui.text_edit_singleline(&mut scene_mesh_path);
+                                                if ui.button("Load mesh").clicked() {
+                                                    match mesh_renderer::SceneMesh::load(&device, &queue, &mesh_renderer, &scene_mesh_path) {
+                                                        Ok(mesh) => {
+                                                            mesh_status = format!("Loaded {} triangles from {}", mesh.triangle_count(), mesh.name);
+                                                            for warning in mesh.warnings() {
+                                                                mesh_status += &format!("\n{warning}");
+                                                            }
+                                                            scene_meshes.push(mesh);
+                                                            rebake = true;
+                                                        }
+                                                        Err(e) => mesh_status = format!("{e:#}"),
+                                                    }
+                                                }
+                                            });
+
+                                            let mut remove = None;
+                                            for (i, mesh) in scene_meshes.iter_mut().enumerate() {
+                                                ui.separator();
+                                                ui.horizontal(|ui| {
+                                                    ui.label(&mesh.name);
+                                                    if ui.button("Remove").clicked() {
+                                                        remove = Some(i);
+                                                    }
+                                                });
+                                                if ui.push_id(i, |ui| mesh.ui(ui)).inner {
+                                                    mesh.update_instances(&device);
+                                                    rebake = true;
+                                                }
+                                            }
+                                            if let Some(i) = remove {
+                                                scene_meshes.remove(i);
+                                                rebake = true;
+                                            }
+                                            if rebake {
+                                                let triangles: Vec<_> = scene_meshes.iter().flat_map(|m| m.world_triangles()).collect();
+                                                colliders.bake(&device, &queue, &triangles);
+                                            }
+
+                                            ui.separator();
+                                            mesh_renderer.ui(ui);
+
+                                            ui.separator();
+                                            let mut changed = ui.checkbox(&mut colliders.collide, "Particles collide with the meshes").changed();
+                                            changed |= ui.add(Slider::new(&mut colliders.params.restitution, 0.0..=1.0).text("Restitution")).changed();
+                                            changed |= ui.add(Slider::new(&mut colliders.params.friction, 0.0..=1.0).text("Friction")).changed();
+                                            changed |= ui.add(Slider::new(&mut colliders.params.thickness, 0.0..=0.1).text("Surface thickness")).changed();
+                                            if changed {
+                                                colliders.update_params(&queue);
+                                            }
+                                            ui.label(format!("Collision field of {0}x{0}x{0} voxels", colliders::SDF_RESOLUTION));
+
+                                            ui.separator();
+                                            ui.horizontal(|ui| {
+                                                ui.label("Seed particles");
+                                                ui.radio_value(&mut mesh_sample_mode, sampling::SampleMode::Surface, "On the surface");
+                                                ui.radio_value(&mut mesh_sample_mode, sampling::SampleMode::Volume, "Inside");
+                                            });
+                                            ui.add(Slider::new(&mut mesh_sample_speed, -2.0..=2.0).text("Speed along the normals"));
+                                            let seed_button = egui::Button::new("Seed particles");
+                                            if ui.add_enabled(!scene_meshes.is_empty() && mesh_sampling.is_none(), seed_button).clicked() {
+                                                if compute.emitters.enabled {
+                                                    mesh_status = String::from("Turn the emitters off to seed particles from the meshes");
+                                                } else {
+                                                    let triangles: Vec<_> = scene_meshes.iter().flat_map(|m| m.world_triangles()).collect();
+                                                    mesh_status = format!("Sampling {} triangles...", triangles.len());
+                                                    mesh_sampling = Some(sampling::SampleJob::spawn(triangles, mesh_sample_mode, compute.points as usize, mesh_sample_speed, random_seed));
+                                                }
+                                            }
+                                            if !mesh_status.is_empty() {
+                                                ui.label(&mesh_status);
+                                            }
+                                        });
+                                }
+
+                                if show_profiler {
+                                    egui::Window::new("Profiler")
+                                        .resizable(true)
+                                        .show(ctx, |ui| {
+                                            profiler.ui(ui);
+                                            ui.horizontal(|ui| {
+                                                ui.text_edit_singleline(&mut profile_path);
+                                                if ui.button("Export CSV").clicked() {
+                                                    profile_status = match profiler.export_csv(&profile_path) {
+                                                        Ok(()) => format!("Wrote {} frames to {profile_path}", profiler.history.len()),
+                                                        Err(e) => format!("{e:#}"),
+                                                    };
+                                                }
+                                            });
+                                            if !profile_status.is_empty() {
+                                                ui.label(&profile_status);
+                                            }
+                                        });
+                                }
+
                                 egui::Window::new("")
                                     .resizable(true)
                                     .vscroll(true)
@@ -230,17 +809,497 @@ async fn main() {
                                             .text("Lower bound of threshold")
                                             .drag_value_speed(0.01);
                                         ui.add(v2);
-                                    });
-                            },
-                        );
+                                        ui.checkbox(&mut show_profiler, "Profiler");
 
-                        compute.inputs.c1 = v1;
-                        compute.inputs.c2 = v2;
+                                        ui.separator();
+                                        ui.horizontal(|ui| {
+                                            if ui.button(if sim_clock.paused { "Resume" } else { "Pause" }).clicked() {
+                                                sim_clock.paused = !sim_clock.paused;
+                                            }
+                                            if ui.add_enabled(sim_clock.paused, egui::Button::new("Step")).clicked() {
+                                                sim_clock.step_once();
+                                            }
+                                            ui.checkbox(&mut sim_clock.reversed, "Reverse");
+                                            ui.label(format!("t = {:.2}, step {}", compute.inputs.time, sim_clock.step_count));
+                                        });
+                                        ui.add(Slider::new(&mut sim_clock.speed, 0.05..=8.0).logarithmic(true).text("Simulation speed"));
+                                        ui.add(Slider::new(&mut sim_clock.max_steps, 1..=compute::MAX_SUBSTEPS).text("Max steps per frame"));
+                                        if sim_clock.reversed {
+                                            ui.label("Only the particles run backwards, the fluid and FLIP hold. Emitters pause and particles stop aging");
+                                        }
+                                        ui.horizontal(|ui| {
+                                            ui.label("Seed");
+                                            if ui.add(egui::DragValue::new(&mut random_seed)).changed() {
+                                                lic.set_seed(&queue, random_seed);
+                                            }
+                                            reset_particles |= ui.button("Reset particles").clicked();
+                                        });
+                                        ui.add(Slider::new(&mut compute.inputs.diffusion, 0.0..=0.1).logarithmic(true).text("Diffusion"));
+                                        ui.add(Slider::new(&mut compute.inputs.magnetic_field, -20.0..=20.0).text("Magnetic field"));
+                                        ui.horizontal(|ui| {
+                                            ui.checkbox(&mut show_emitters, "Emitters");
+                                            ui.checkbox(&mut show_meshes, "Meshes");
+                                        });
+
+                                        ui.separator();
+                                        let mut camera_mode = camera.mode();
+                                        ui.horizontal(|ui| {
+                                            ui.label("Camera");
+                                            ui.radio_value(&mut camera_mode, camera::CameraMode::Fly, "Fly");
+                                            ui.radio_value(&mut camera_mode, camera::CameraMode::Orbit, "Orbit");
+                                        });
+                                        if camera_mode != camera.mode() {
+                                            camera.set_mode(camera_mode);
+                                        }
+                                        if camera_mode == camera::CameraMode::Orbit {
+                                            ui.label("Drag to orbit, middle drag to pan, scroll to zoom, double click to focus");
+                                        }
+                                        let mut orthographic = camera.projection() == camera::Projection::Orthographic;
+                                        if ui.checkbox(&mut orthographic, "Orthographic (numpad 5)").changed() {
+                                            camera.set_projection(if orthographic { camera::Projection::Orthographic } else { camera::Projection::Perspective });
+                                        }
+                                        ui.horizontal(|ui| {
+                                            for (view, label) in [
+                                                (camera::SnapView::Top, "Top (7)"),
+                                                (camera::SnapView::Front, "Front (1)"),
+                                                (camera::SnapView::Side, "Side (3)"),
+                                                (camera::SnapView::Isometric, "Isometric (9)"),
+                                            ] {
+                                                if ui.button(label).clicked() {
+                                                    camera.snap(view);
+                                                }
+                                            }
+                                        });
+
+                                        ui.add(Slider::new(&mut camera.controls.speed, 0.1..=50.0).logarithmic(true).text("Speed"));
+                                        ui.add(Slider::new(&mut camera.controls.mouse_sensitivity, 0.0001..=0.01).logarithmic(true).text("Mouse sensitivity"));
+                                        ui.add(Slider::new(&mut camera.controls.orbit_sensitivity, 0.0005..=0.05).logarithmic(true).text("Orbit sensitivity"));
+                                        ui.checkbox(&mut camera.controls.invert_y, "Invert Y");
+                                        ui.horizontal(|ui| {
+                                            ui.label("Controls file");
+                                            ui.text_edit_singleline(&mut controls_path);
+                                        });
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Save controls").clicked() {
+                                                camera_status = match camera.controls.save(&controls_path) {
+                                                    Ok(()) => format!("Saved controls to {controls_path}"),
+                                                    Err(e) => format!("{e:#}"),
+                                                };
+                                            }
+                                            if ui.button("Load controls").clicked() {
+                                                camera_status = match camera::Controls::load(&controls_path) {
+                                                    Ok(controls) => {
+                                                        camera.controls = controls;
+                                                        format!("Loaded controls from {controls_path}")
+                                                    }
+                                                    Err(e) => format!("{e:#}"),
+                                                };
+                                            }
+                                        });
+
+                                        ui.horizontal(|ui| {
+                                            ui.text_edit_singleline(&mut bookmark_name);
+                                            if ui.button("Add bookmark").clicked() {
+                                                bookmarks.push(camera::Bookmark { name: bookmark_name.clone(), pose: camera.pose() });
+                                                bookmark_name = format!("View {}", bookmarks.len() + 1);
+                                            }
+                                        });
+                                        let mut removed_bookmark = None;
+                                        for (i, bookmark) in bookmarks.iter().enumerate() {
+                                            ui.horizontal(|ui| {
+                                                if ui.button("Go").clicked() {
+                                                    timeline.playing = false;
+                                                    camera.set_pose(bookmark.pose);
+                                                }
+                                                if ui.button("Remove").clicked() {
+                                                    removed_bookmark = Some(i);
+                                                }
+                                                ui.label(&bookmark.name);
+                                            });
+                                        }
+                                        if let Some(i) = removed_bookmark {
+                                            bookmarks.remove(i);
+                                        }
+
+                                        ui.horizontal(|ui| {
+                                            ui.add(egui::DragValue::new(&mut keyframe_time).speed(0.1).range(0.0..=f32::MAX).suffix(" s"));
+                                            if ui.button("Add keyframe").clicked() {
+                                                timeline.insert(camera::Keyframe { time: keyframe_time, pose: camera.pose() });
+                                                keyframe_time += 2.0;
+                                            }
+                                        });
+                                        let mut removed_keyframe = None;
+                                        for (i, keyframe) in timeline.keyframes().iter().enumerate() {
+                                            ui.horizontal(|ui| {
+                                                if ui.button("Remove").clicked() {
+                                                    removed_keyframe = Some(i);
+                                                }
+                                                ui.label(format!("{:.2} s", keyframe.time));
+                                            });
+                                        }
+                                        if let Some(i) = removed_keyframe {
+                                            timeline.remove(i);
+                                        }
+                                        if timeline.keyframes().len() > 1 {
+                                            let duration = timeline.duration();
+                                            ui.horizontal(|ui| {
+                                                if timeline.playing {
+                                                    if ui.button("Stop").clicked() {
+                                                        timeline.playing = false;
+                                                    }
+                                                } else if ui.button("Play").clicked() {
+                                                    timeline.play();
+                                                    if record_frames {
+                                                        timeline.time = 0.0;
+                                                        frame_capture = frame_capture::FrameCapture::new(&capture_directory);
+                                                    }
+                                                }
+                                                ui.checkbox(&mut timeline.looping, "Loop");
+                                            });
+                                            if ui.add(Slider::new(&mut timeline.time, 0.0..=duration).text("Time")).changed() {
+                                                if let Some(pose) = timeline.sample(timeline.time) {
+                                                    camera.set_pose(pose);
+                                                }
+                                            }
+                                            ui.add_enabled_ui(can_capture && !timeline.playing, |ui| {
+                                                ui.horizontal(|ui| {
+                                                    ui.checkbox(&mut record_frames, "Record frames to");
+                                                    ui.text_edit_singleline(&mut capture_directory);
+                                                });
+                                            });
+                                            // A looping recording would never finish
+                                            if record_frames {
+                                                timeline.looping = false;
+                                            }
+                                        }
+
+                                        ui.horizontal(|ui| {
+                                            ui.label("Camera file");
+                                            ui.text_edit_singleline(&mut bookmarks_path);
+                                        });
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Save").clicked() {
+                                                let file = camera::CameraBookmarks { bookmarks: bookmarks.clone(), keyframes: timeline.keyframes().to_vec() };
+                                                camera_status = match file.save(&bookmarks_path) {
+                                                    Ok(()) => format!("Saved {} bookmarks and {} keyframes", bookmarks.len(), timeline.keyframes().len()),
+                                                    Err(e) => format!("{e:#}"),
+                                                };
+                                            }
+                                            if ui.button("Load").clicked() {
+                                                camera_status = match camera::CameraBookmarks::load(&bookmarks_path) {
+                                                    Ok(file) => {
+                                                        bookmarks = file.bookmarks;
+                                                        timeline.playing = false;
+                                                        timeline.set_keyframes(file.keyframes);
+                                                        keyframe_time = timeline.duration() + 2.0;
+                                                        format!("Loaded {} bookmarks and {} keyframes", bookmarks.len(), timeline.keyframes().len())
+                                                    }
+                                                    Err(e) => format!("{e:#}"),
+                                                };
+                                            }
+                                        });
+                                        if !camera_status.is_empty() {
+                                            ui.label(&camera_status);
+                                        }
+
+                                        ui.separator();
+                                        if ui.checkbox(&mut show_flip, "FLIP liquid").changed() {
+                                            if show_flip {
+                                                renderer.set_point_buffer(&device, &queue, flip_buffer.clone(), flip.num_particles(), false);
+                                            } else {
+                                                renderer.set_point_buffer(&device, &queue, point_buffer.clone(), point_buffer_size, true);
+                                            }
+                                        }
+                                        ui.add(Slider::new(&mut flip.flip_ratio, 0.0..=1.0).text("FLIP ratio"));
+
+                                        ui.separator();
+                                        ui.checkbox(&mut renderer.culling, "Frustum culling");
+                                        ui.add_enabled_ui(renderer.culling, |ui| {
+                                            let cull = &mut renderer.cull.params;
+                                            let mut lod = cull.lod != 0;
+                                            let mut changed = ui.checkbox(&mut lod, "Thin out distant points").changed();
+                                            cull.lod = lod as u32;
+                                            if lod {
+                                                changed |= ui.add(Slider::new(&mut cull.lod_near, 0.0..=50.0).text("Full detail up to")).changed();
+                                                changed |= ui.add(Slider::new(&mut cull.lod_far, 0.0..=100.0).text("Lowest detail from")).changed();
+                                                changed |= ui.add(Slider::new(&mut cull.lod_min_fraction, 0.0..=1.0).text("Fraction kept far away")).changed();
+                                                cull.lod_far = cull.lod_far.max(cull.lod_near + 0.01);
+                                            }
+                                            if changed {
+                                                renderer.cull.update_params(&queue);
+                                            }
+                                        });
 
+                                        ui.separator();
+                                        let color = &mut renderer.coloring.color;
+                                        let mut changed = false;
+                                        ui.horizontal(|ui| {
+                                            ui.label("Color by");
+                                            changed |= ui.radio_value(&mut color.mode, renderer::COLOR_DISTANCE, "Distance").changed();
+                                            changed |= ui.radio_value(&mut color.mode, renderer::COLOR_SPEED, "Speed").changed();
+                                            changed |= ui.radio_value(&mut color.mode, renderer::COLOR_ATTRIBUTE, "Attribute").changed();
+                                            changed |= ui.radio_value(&mut color.mode, renderer::COLOR_RGBA, "Color attribute").changed();
+                                        });
+                                        if color.mode == renderer::COLOR_ATTRIBUTE {
+                                            ui.horizontal_wrapped(|ui| {
+                                                for attribute in attributes::floats() {
+                                                    let offset = attributes::offset(attribute.name).unwrap();
+                                                    changed |= ui.radio_value(&mut color.attribute_offset, offset, attribute.name).changed();
+                                                }
+                                            });
+                                        }
+                                        if color.mode == renderer::COLOR_SPEED || color.mode == renderer::COLOR_ATTRIBUTE {
+                                            ui.horizontal(|ui| {
+                                                ui.label("Range");
+                                                changed |= ui.add(egui::DragValue::new(&mut color.range[0]).speed(0.01)).changed();
+                                                changed |= ui.add(egui::DragValue::new(&mut color.range[1]).speed(0.01)).changed();
+                                                changed |= ui.radio_value(&mut color.colormap, glyph_renderer::COLORMAP_VIRIDIS, "Viridis").changed();
+                                                changed |= ui.radio_value(&mut color.colormap, glyph_renderer::COLORMAP_MAGMA, "Magma").changed();
+                                            });
+                                        }
+                                        if changed {
+                                            renderer.update_color(&queue);
+                                        }
+                                        ui.horizontal(|ui| {
+                                            ui.label("Particle file");
+                                            ui.text_edit_singleline(&mut ply_path);
+                                            if ui.button("Export PLY").clicked() {
+                                                let written = particle_export::ParticleSnapshot::read(&device, &queue, &point_buffer, compute.velocity_buffer(), compute.attribute_buffer(), compute.points)
+                                                    .and_then(|snapshot| snapshot.write_ply(&ply_path));
+                                                ply_status = match written {
+                                                    Ok(count) => format!("Wrote {count} particles to {ply_path}"),
+                                                    Err(e) => format!("{e:#}"),
+                                                };
+                                            }
+                                        });
+                                        if !ply_status.is_empty() {
+                                            ui.label(&ply_status);
+                                        }
 
-                        compute.update_inputs(&queue);
+                                        ui.separator();
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut particle_mode, compute::MODE_ATTRACTOR, "Attractor");
+                                            ui.radio_value(&mut particle_mode, compute::MODE_TRACER, "Trace velocity field");
+                                            ui.add_enabled_ui(expression.is_some(), |ui| {
+                                                ui.radio_value(&mut particle_mode, compute::MODE_EXPRESSION, "Trace expression");
+                                            });
+                                        });
+                                        ui.add(Slider::new(&mut compute.inputs.field_scale, 0.0..=100.0).text("Field scale"));
 
+                                        ui.horizontal(|ui| {
+                                            ui.label("Field file");
+                                            ui.text_edit_singleline(&mut field_path);
+                                        });
+                                        ui.checkbox(&mut fit_loaded_field, "Fit loaded field to view");
+                                        if ui.button("Load field").clicked() {
+                                            match vector_field::VectorField::load(&field_path) {
+                                                Ok(field) => {
+                                                    let field = if fit_loaded_field { field.fit_to_cube(1.0) } else { field };
+                                                    let (min, max) = field.texture_bounds();
+                                                    let dims = field.dims.map(|d| d as u32);
+                                                    let texture = field_texture::FieldTexture::new(&device, dims, min, max);
+                                                    texture.write(&queue, &field.to_texture_data());
+                                                    compute.set_field(&device, &texture);
+                                                    field_status = format!("Loaded {}x{}x{} field", dims[0], dims[1], dims[2]);
+                                                    file_texture = Some(texture);
+                                                    loaded_field = Some(field);
+                                                    lic_stale = true;
+                                                    use_loaded_field = true;
+                                                }
+                                                Err(e) => field_status = format!("{e:#}"),
+                                            }
+                                        }
+                                        if !field_status.is_empty() {
+                                            ui.label(&field_status);
+                                        }
+                                        if let Some(texture) = &file_texture {
+                                            if ui.checkbox(&mut use_loaded_field, "Trace loaded field instead of fluid").changed() {
+                                                compute.set_field(&device, if use_loaded_field { texture } else { &fluid_texture });
+                                            }
+                                        }
+
+                                        ui.separator();
+                                        ui.horizontal(|ui| {
+                                            ui.label("Field expression");
+                                            ui.text_edit_singleline(&mut expression_text);
+                                        });
+                                        if ui.button("Apply expression").clicked() {
+                                            let parsed = expr::Expr::parse(&expression_text)
+                                                .and_then(|e| compute.set_expression(&device, &e).map(|_| e))
+                                                .and_then(|e| glyph_renderer.set_expression(&device, &e).map(|_| e));
+                                            match parsed {
+                                                Ok(e) => {
+                                                    expression = Some(e);
+                                                    lic_stale = true;
+                                                    expression_status.clear();
+                                                    particle_mode = compute::MODE_EXPRESSION;
+                                                }
+                                                Err(e) => expression_status = format!("{e:#}"),
+                                            }
+                                        }
+                                        if !expression_status.is_empty() {
+                                            ui.label(&expression_status);
+                                        }
+
+                                        ui.separator();
+                                        ui.checkbox(&mut show_glyphs, "Vector glyphs");
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut glyph_source, FieldSource::Fluid, "Fluid");
+                                            ui.add_enabled_ui(loaded_field.is_some(), |ui| {
+                                                ui.radio_value(&mut glyph_source, FieldSource::LoadedField, "Loaded field");
+                                            });
+                                            ui.add_enabled_ui(expression.is_some(), |ui| {
+                                                ui.radio_value(&mut glyph_source, FieldSource::Expression, "Expression");
+                                            });
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut glyph_renderer.shape, glyph_renderer::GlyphShape::Arrow, "Arrows");
+                                            ui.radio_value(&mut glyph_renderer.shape, glyph_renderer::GlyphShape::Cone, "Cones");
+                                            ui.radio_value(&mut glyph_renderer.shape, glyph_renderer::GlyphShape::Line, "Lines");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut glyph_renderer.params.colormap, glyph_renderer::COLORMAP_VIRIDIS, "Viridis");
+                                            ui.radio_value(&mut glyph_renderer.params.colormap, glyph_renderer::COLORMAP_MAGMA, "Magma");
+                                        });
+                                        ui.add(Slider::new(&mut glyph_renderer.params.scale, 0.01..=0.5).text("Glyph length"));
+                                        ui.add(Slider::new(&mut glyph_stride, 1..=16).text("Glyph stride"));
+                                        ui.add(Slider::new(&mut glyph_renderer.params.min_magnitude, 0.0..=1.0).text("Hide weaker than"));
+                                        ui.checkbox(&mut auto_glyph_range, "Scale to strongest vector");
+                                        ui.add_enabled(
+                                            !auto_glyph_range,
+                                            Slider::new(&mut glyph_renderer.params.max_magnitude, 0.001..=100.0).logarithmic(true).text("Full length at"),
+                                        );
+
+                                        ui.separator();
+                                        ui.checkbox(&mut show_lic, "Line integral convolution");
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut lic_source, FieldSource::Fluid, "Fluid");
+                                            ui.add_enabled_ui(loaded_field.is_some(), |ui| {
+                                                ui.radio_value(&mut lic_source, FieldSource::LoadedField, "Loaded field");
+                                            });
+                                            ui.add_enabled_ui(expression.is_some(), |ui| {
+                                                ui.radio_value(&mut lic_source, FieldSource::Expression, "Expression");
+                                            });
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Slice normal");
+                                            ui.radio_value(&mut slice_axis, 0, "X");
+                                            ui.radio_value(&mut slice_axis, 1, "Y");
+                                            ui.radio_value(&mut slice_axis, 2, "Z");
+                                        });
+                                        ui.add(Slider::new(&mut slice_position, 0.0..=1.0).text("Slice position"));
+                                        ui.add(Slider::new(&mut lic.params.steps, 1..=100).text("Kernel length"));
+                                        ui.horizontal(|ui| {
+                                            let mut overlay = lic.plane.overlay != 0;
+                                            ui.checkbox(&mut overlay, "Color by speed");
+                                            lic.plane.overlay = overlay as u32;
+                                            ui.radio_value(&mut lic.plane.colormap, glyph_renderer::COLORMAP_VIRIDIS, "Viridis");
+                                            ui.radio_value(&mut lic.plane.colormap, glyph_renderer::COLORMAP_MAGMA, "Magma");
+                                        });
+                                        if !lic_status.is_empty() {
+                                            ui.label(&lic_status);
+                                        }
+
+                                        ui.separator();
+                                        ui.checkbox(&mut show_scalars, "Fluid scalars");
+                                        ui.horizontal(|ui| {
+                                            for scalar in std::iter::once(fluid.temperature()).chain(fluid.scalars()) {
+                                                ui.radio_value(&mut overlay_scalar, scalar.name.clone(), &scalar.name);
+                                            }
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut scalar_overlay.plane.colormap, glyph_renderer::COLORMAP_VIRIDIS, "Viridis");
+                                            ui.radio_value(&mut scalar_overlay.plane.colormap, glyph_renderer::COLORMAP_MAGMA, "Magma");
+                                        });
+                                        ui.add(Slider::new(&mut scalar_overlay.plane.opacity, 0.0..=1.0).text("Opacity"));
+
+                                        ui.separator();
+                                        ui.checkbox(&mut show_topology, "Topology");
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut topology_source, FieldSource::Fluid, "Fluid");
+                                            ui.add_enabled_ui(loaded_field.is_some(), |ui| {
+                                                ui.radio_value(&mut topology_source, FieldSource::LoadedField, "Loaded field");
+                                            });
+                                            ui.add_enabled_ui(expression.is_some(), |ui| {
+                                                ui.radio_value(&mut topology_source, FieldSource::Expression, "Expression");
+                                            });
+                                        });
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Find critical points").clicked() {
+                                                analyze_topology = true;
+                                                show_topology = true;
+                                            }
+                                            ui.checkbox(&mut show_separatrices, "Separatrices");
+                                        });
+                                        if show_topology {
+                                            ui.collapsing(format!("{} critical points", critical_points.len()), |ui| {
+                                                for point in &critical_points {
+                                                    let eigenvalues: Vec<String> = point.eigenvalues.iter()
+                                                        .map(|(re, im)| if *im == 0.0 { format!("{re:.3}") } else { format!("{re:.3}{im:+.3}i") })
+                                                        .collect();
+                                                    ui.label(format!(
+                                                        "{} at ({:.3}, {:.3}, {:.3}), λ = {}",
+                                                        point.kind.label(), point.position.x, point.position.y, point.position.z, eigenvalues.join(", "),
+                                                    ));
+                                                }
+                                            });
+                                        }
+
+                                        ui.separator();
+                                        ui.checkbox(&mut show_streamlines, "Streamlines");
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut streamline_source, FieldSource::Fluid, "Fluid");
+                                            ui.add_enabled_ui(loaded_field.is_some(), |ui| {
+                                                ui.radio_value(&mut streamline_source, FieldSource::LoadedField, "Loaded field");
+                                            });
+                                            ui.add_enabled_ui(expression.is_some(), |ui| {
+                                                ui.radio_value(&mut streamline_source, FieldSource::Expression, "Expression");
+                                            });
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Seed");
+                                            ui.radio_value(&mut seed_shape, SeedShape::Point, "Point");
+                                            ui.radio_value(&mut seed_shape, SeedShape::Line, "Line");
+                                            ui.radio_value(&mut seed_shape, SeedShape::Plane, "Plane");
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Seed center");
+                                            ui.add(egui::DragValue::new(&mut seed_center.x).speed(0.01));
+                                            ui.add(egui::DragValue::new(&mut seed_center.y).speed(0.01));
+                                            ui.add(egui::DragValue::new(&mut seed_center.z).speed(0.01));
+                                        });
+                                        ui.add(Slider::new(&mut seed_extent, 0.0..=1.0).text("Seed extent"));
+                                        ui.add(Slider::new(&mut seed_count, 1..=64).text("Seeds per side"));
+                                        ui.add(Slider::new(&mut streamline_params.max_points, 10..=5000).text("Max points"));
+                                        ui.add(Slider::new(&mut streamline_params.tolerance, 1e-6..=1e-2).logarithmic(true).text("Step tolerance"));
+                                        ui.checkbox(&mut streamline_params.both_directions, "Trace upstream too");
+                                        ui.horizontal(|ui| {
+                                            ui.checkbox(&mut streamline_tubes, "Tubes");
+                                            ui.add_enabled(streamline_tubes, Slider::new(&mut tube_radius, 0.001..=0.05).text("Radius"));
+                                        });
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Trace").clicked() {
+                                                retrace_streamlines = true;
+                                            }
+                                            ui.checkbox(&mut live_streamlines, "Retrace every frame");
+                                        });
+                                    });
+                            },
+                        );
+                        profiler.end_scope(&mut encoder);
+
+                        profiler.resolve(&mut encoder);
                         queue.submit(Some(encoder.finish()));
+                        profiler.end_frame();
+                        if recording {
+                            if let Err(e) = frame_capture.save(&device) {
+                                camera_status = format!("{e:#}");
+                                record_frames = false;
+                            } else if !timeline.playing {
+                                camera_status = format!("Recorded {} frames to {}", frame_capture.frame, frame_capture.directory.display());
+                                record_frames = false;
+                            }
+                        }
                         surface_texture.present();
                         window.request_redraw();
                     }
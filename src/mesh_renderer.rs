@@ -0,0 +1,357 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, Buffer, BufferBindingType, BufferUsages, ColorTargetState, CommandEncoder, CompareFunction, DepthBiasState, DepthStencilState, Device, FragmentState, FrontFace, LoadOp, Operations, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, StoreOp, TextureView, VertexState};
+use egui_wgpu::wgpu::TextureFormat::Bgra8UnormSrgb;
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::{EulerRot, Mat3, Mat4, Quat, Vec3};
+
+use crate::models::{DrawModel, InstanceRaw, Material, Model, ModelError, ModelVertex, Vertex};
+
+/// Where one copy of a [`SceneMesh`] sits.
+#[derive(Debug, Copy, Clone)]
+pub struct Instance {
+    pub position: Vec3,
+    /// Euler angles in degrees, applied in XYZ order.
+    pub rotation: Vec3,
+    pub scale: f32,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Instance {
+    fn orientation(&self) -> Quat {
+        let r = self.rotation * std::f32::consts::PI / 180.0;
+        Quat::from_euler(EulerRot::XYZ, r.x, r.y, r.z)
+    }
+
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(Vec3::splat(self.scale), self.orientation(), self.position)
+    }
+
+    fn to_raw(self) -> InstanceRaw {
+        InstanceRaw {
+            model: self.transform().to_cols_array_2d(),
+            // Scaling is uniform, the rotation alone keeps normals perpendicular
+            normal: Mat3::from_quat(self.orientation()).to_cols_array_2d(),
+        }
+    }
+
+    /// Placement controls, returns whether anything changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Position");
+            changed |= ui.add(egui::DragValue::new(&mut self.position.x).speed(0.01)).changed();
+            changed |= ui.add(egui::DragValue::new(&mut self.position.y).speed(0.01)).changed();
+            changed |= ui.add(egui::DragValue::new(&mut self.position.z).speed(0.01)).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            changed |= ui.add(egui::DragValue::new(&mut self.rotation.x).speed(1.0).suffix("°")).changed();
+            changed |= ui.add(egui::DragValue::new(&mut self.rotation.y).speed(1.0).suffix("°")).changed();
+            changed |= ui.add(egui::DragValue::new(&mut self.rotation.z).speed(1.0).suffix("°")).changed();
+        });
+        changed |= ui.add(egui::Slider::new(&mut self.scale, 0.01..=10.0).logarithmic(true).text("Scale")).changed();
+        changed
+    }
+}
+
+/// An OBJ or glTF model placed in the scene one or more times, drawn by [`MeshRenderer`] and baked into the
+/// particles' colliders.
+pub struct SceneMesh {
+    pub name: String,
+    model: Model,
+    pub instances: Vec<Instance>,
+    instance_buffer: Buffer,
+}
+
+impl SceneMesh {
+    pub fn load(device: &Device, queue: &Queue, renderer: &MeshRenderer, path: &str) -> anyhow::Result<Self> {
+        let model = crate::models::load_model(path, device, queue, &renderer.material_bind_group_layout)?;
+        let path = Path::new(path);
+        let instances = vec![Instance::default()];
+
+        Ok(Self {
+            name: path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned()),
+            model,
+            instance_buffer: Self::create_instance_buffer(device, &instances),
+            instances,
+        })
+    }
+
+    fn create_instance_buffer(device: &Device, instances: &[Instance]) -> Buffer {
+        let raw: Vec<InstanceRaw> = instances.iter().map(|i| i.to_raw()).collect();
+        device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Mesh Instance Buffer"),
+            contents: bytemuck::cast_slice(&raw),
+            usage: BufferUsages::VERTEX,
+        })
+    }
+
+    /// Uploads `instances` after they were edited.
+    pub fn update_instances(&mut self, device: &Device) {
+        self.instance_buffer = Self::create_instance_buffer(device, &self.instances);
+    }
+
+    /// What the file was missing, see [`ModelError`].
+    pub fn warnings(&self) -> &[ModelError] {
+        &self.model.warnings
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.model.triangles.len()
+    }
+
+    /// Triangles of every instance, moved to where it is placed.
+    pub fn world_triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.instances.iter().flat_map(|instance| {
+            let transform = instance.transform();
+            self.model.triangles.iter().map(move |t| t.map(|v| transform.transform_point3(v)))
+        })
+    }
+
+    /// Controls for every instance, returns whether any moved or got added or removed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        let mut remove = None;
+        for (i, instance) in self.instances.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("Instance {}", i + 1));
+                if ui.button("Remove instance").clicked() {
+                    remove = Some(i);
+                }
+            });
+            changed |= ui.push_id(i, |ui| instance.ui(ui)).inner;
+        }
+        if let Some(i) = remove {
+            self.instances.remove(i);
+            changed = true;
+        }
+        if ui.button("Add instance").clicked() {
+            // Next to the last copy so it doesn't hide inside of it
+            let mut instance = self.instances.last().copied().unwrap_or_default();
+            instance.position.x += 1.0;
+            self.instances.push(instance);
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// Diffuse only, or with Blinn-Phong highlights.
+pub const LIGHTING_LAMBERT: u32 = 0;
+pub const LIGHTING_BLINN_PHONG: u32 = 1;
+
+/// A directional light, mirrors `Lighting` in model.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Lighting {
+    /// Towards the light, in world space.
+    pub direction: [f32; 4],
+    pub color: [f32; 4],
+    /// Eye position for the highlights, set every frame by [`MeshRenderer::update_lighting`].
+    pub view_position: [f32; 4],
+    pub ambient: f32,
+    pub model: u32,
+    pub _padding: [u32; 2],
+}
+
+/// Draws the [`SceneMesh`]es lit and textured, after the points and against their depth.
+pub struct MeshRenderer {
+    pipeline: RenderPipeline,
+    camera_bind_group: Arc<BindGroup>,
+    /// See [`crate::models::material_bind_group_layout`].
+    pub material_bind_group_layout: BindGroupLayout,
+    /// Plain white, for meshes without a material.
+    default_material: Material,
+
+    pub lighting: Lighting,
+    lighting_buffer: Buffer,
+    lighting_bind_group: BindGroup,
+}
+
+impl MeshRenderer {
+    pub fn new(device: &Device, queue: &Queue, camera_bind_group_layout: &BindGroupLayout, camera_bind_group: Arc<BindGroup>) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("model.wgsl"),
+            source: ShaderSource::Wgsl(include_str!("model.wgsl").into()),
+        });
+
+        let material_bind_group_layout = crate::models::material_bind_group_layout(device);
+        let default_material = Material::white(device, queue, &material_bind_group_layout);
+
+        let lighting = Lighting {
+            direction: Vec3::new(0.4, 1.0, 0.6).normalize().extend(0.0).to_array(),
+            color: [1.0; 4],
+            view_position: [0.0; 4],
+            ambient: 0.2,
+            model: LIGHTING_BLINN_PHONG,
+            _padding: [0; 2],
+        };
+        let lighting_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Lighting Buffer"),
+            contents: bytemuck::cast_slice(&[lighting]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let lighting_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Lighting Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let lighting_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Lighting Bind Group"),
+            layout: &lighting_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: lighting_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Mesh Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &material_bind_group_layout, &lighting_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Mesh Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[ModelVertex::desc(), InstanceRaw::desc()],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: crate::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: Default::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: Bgra8UnormSrgb,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            camera_bind_group,
+            material_bind_group_layout,
+            default_material,
+            lighting,
+            lighting_buffer,
+            lighting_bind_group,
+        }
+    }
+
+    /// Uploads `lighting` with the highlights seen from `eye`.
+    pub fn update_lighting(&mut self, queue: &Queue, eye: Vec3) {
+        self.lighting.view_position = eye.extend(1.0).to_array();
+        queue.write_buffer(&self.lighting_buffer, 0, bytemuck::cast_slice(&[self.lighting]));
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Lighting");
+            ui.radio_value(&mut self.lighting.model, LIGHTING_LAMBERT, "Lambert");
+            ui.radio_value(&mut self.lighting.model, LIGHTING_BLINN_PHONG, "Blinn-Phong");
+        });
+        let mut direction = Vec3::from_slice(&self.lighting.direction[..3]);
+        let mut azimuth = direction.z.atan2(direction.x).to_degrees();
+        let mut elevation = direction.y.clamp(-1.0, 1.0).asin().to_degrees();
+        let mut changed = ui.add(egui::Slider::new(&mut azimuth, -180.0..=180.0).text("Light azimuth")).changed();
+        changed |= ui.add(egui::Slider::new(&mut elevation, -90.0..=90.0).text("Light elevation")).changed();
+        if changed {
+            let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+            direction = Vec3::new(elevation.cos() * azimuth.cos(), elevation.sin(), elevation.cos() * azimuth.sin());
+            self.lighting.direction = direction.extend(0.0).to_array();
+        }
+        ui.horizontal(|ui| {
+            ui.label("Light color");
+            let mut color = [self.lighting.color[0], self.lighting.color[1], self.lighting.color[2]];
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                self.lighting.color = [color[0], color[1], color[2], 1.0];
+            }
+        });
+        ui.add(egui::Slider::new(&mut self.lighting.ambient, 0.0..=1.0).text("Ambient"));
+    }
+
+    pub fn render(&self, encoder: &mut CommandEncoder, surface_view: &TextureView, depth_view: &TextureView, meshes: &[SceneMesh]) {
+        if meshes.is_empty() {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Mesh Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.lighting_bind_group, &[]);
+        for scene_mesh in meshes {
+            if scene_mesh.instances.is_empty() {
+                continue;
+            }
+            render_pass.set_vertex_buffer(1, scene_mesh.instance_buffer.slice(..));
+            let model = &scene_mesh.model;
+            for mesh in &model.meshes {
+                let material = model.materials.get(mesh.material).unwrap_or(&self.default_material);
+                render_pass.set_bind_group(1, &material.bind_group, &[]);
+                render_pass.draw_mesh_instanced(mesh, 0..scene_mesh.instances.len() as u32);
+            }
+        }
+    }
+}
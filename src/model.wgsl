@@ -0,0 +1,123 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct Material {
+    diffuse: vec4<f32>,
+    // Blinn-Phong exponent in w
+    specular: vec4<f32>,
+    normal_scale: f32,
+    normal_map: u32,
+}
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+@group(1) @binding(2)
+var<uniform> material: Material;
+// Tangent space, sampled with s_diffuse
+@group(1) @binding(3)
+var t_normal: texture_2d<f32>;
+
+const LIGHTING_LAMBERT: u32 = 0;
+const LIGHTING_BLINN_PHONG: u32 = 1;
+
+struct Lighting {
+    // Towards the light
+    direction: vec4<f32>,
+    color: vec4<f32>,
+    view_position: vec4<f32>,
+    ambient: f32,
+    model: u32,
+}
+
+@group(2) @binding(0)
+var<uniform> lighting: Lighting;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+    @location(9) normal_0: vec3<f32>,
+    @location(10) normal_1: vec3<f32>,
+    @location(11) normal_2: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let normal_matrix = mat3x3<f32>(instance.normal_0, instance.normal_1, instance.normal_2);
+    let world_position = model * vec4<f32>(in.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.tex_coords = in.tex_coords;
+    out.normal = normal_matrix * in.normal;
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+// Tangent frame from screen space derivatives, so meshes don't need tangents. Christian Schüler,
+// "Normal Mapping Without Precomputed Tangents"
+fn perturb_normal(n: vec3<f32>, dp1: vec3<f32>, dp2: vec3<f32>, duv1: vec2<f32>, duv2: vec2<f32>, sampled: vec3<f32>) -> vec3<f32> {
+    let dp2perp = cross(dp2, n);
+    let dp1perp = cross(n, dp1);
+    let t = dp2perp * duv1.x + dp1perp * duv2.x;
+    let b = dp2perp * duv1.y + dp1perp * duv2.y;
+    let scale = inverseSqrt(max(max(dot(t, t), dot(b, b)), 1e-20));
+    let tangent_normal = (2.0 * sampled - 1.0) * vec3<f32>(material.normal_scale, material.normal_scale, 1.0);
+    return normalize(mat3x3<f32>(t * scale, b * scale, n) * tangent_normal);
+}
+
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    // Samples and derivatives before any branch, they need uniform control flow
+    let base = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.diffuse;
+    let sampled_normal = textureSample(t_normal, s_diffuse, in.tex_coords).xyz;
+    let dp1 = dpdx(in.world_position);
+    let dp2 = dpdy(in.world_position);
+    let duv1 = dpdx(in.tex_coords);
+    let duv2 = dpdy(in.tex_coords);
+
+    // Vertices without a normal stay unlit
+    if (dot(in.normal, in.normal) < 1e-6) {
+        return base;
+    }
+    // Lit from both sides, open meshes show their inside
+    var n = normalize(in.normal);
+    if (!front_facing) {
+        n = -n;
+    }
+    if (material.normal_map != 0u) {
+        n = perturb_normal(n, dp1, dp2, duv1, duv2, sampled_normal);
+    }
+    let l = normalize(lighting.direction.xyz);
+    let diffuse = max(dot(n, l), 0.0);
+    var color = base.rgb * (lighting.ambient + diffuse * lighting.color.rgb);
+
+    if (lighting.model == LIGHTING_BLINN_PHONG && diffuse > 0.0) {
+        let v = normalize(lighting.view_position.xyz - in.world_position);
+        let h = normalize(l + v);
+        let specular = pow(max(dot(n, h), 0.0), material.specular.w);
+        color += material.specular.rgb * lighting.color.rgb * specular;
+    }
+    return vec4<f32>(color, base.a);
+}
@@ -1,8 +1,13 @@
+use std::cell::RefCell;
 use std::fs::File;
-use std::io::{BufReader, Cursor};
+use std::io::BufReader;
 use std::ops::Range;
-use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindingResource, Buffer, BufferAddress, BufferUsages, Device, IndexFormat, Queue, RenderPass, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferAddress, BufferBindingType, BufferUsages, Device, IndexFormat, Queue, RenderPass, SamplerBindingType, ShaderStages, TextureSampleType, TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
 use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+use image::DynamicImage;
 use crate::texture;
 
 
@@ -68,66 +73,284 @@ impl Vertex for CloudPoint {
     }
 }
 
+/// Vertex of a glyph mesh, instanced once per [`crate::vector::Vector`].
+/// Uses locations 6 and up, the instance data occupies the lower ones.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlyphVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+impl Vertex for GlyphVertex {
+    fn desc() -> VertexBufferLayout<'static> {
+        use std::mem;
+        VertexBufferLayout {
+            array_stride: mem::size_of::<GlyphVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 6,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as BufferAddress,
+                    shader_location: 7,
+                    format: VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// Point of a streamline mesh, see [`crate::streamlines`]. Lines leave the normal at zero.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct StreamlineVertex {
+    pub position: [f32; 3],
+    pub speed: f32,
+    pub normal: [f32; 3],
+}
+
+impl Vertex for StreamlineVertex {
+    fn desc() -> VertexBufferLayout<'static> {
+        use std::mem;
+        VertexBufferLayout {
+            array_stride: mem::size_of::<StreamlineVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as BufferAddress,
+                    shader_location: 1,
+                    format: VertexFormat::Float32,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as BufferAddress,
+                    shader_location: 2,
+                    format: VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// Placement of one copy of a model, the matrices of an instance step vertex buffer.
+/// Uses locations 5 to 11, after the [`ModelVertex`] attributes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    /// Rotation part of `model`, for the normals.
+    pub normal: [[f32; 3]; 3],
+}
+
+impl Vertex for InstanceRaw {
+    fn desc() -> VertexBufferLayout<'static> {
+        use std::mem;
+        VertexBufferLayout {
+            array_stride: mem::size_of::<InstanceRaw>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: VertexFormat::Float32x4,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as BufferAddress,
+                    shader_location: 6,
+                    format: VertexFormat::Float32x4,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as BufferAddress,
+                    shader_location: 7,
+                    format: VertexFormat::Float32x4,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as BufferAddress,
+                    shader_location: 8,
+                    format: VertexFormat::Float32x4,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as BufferAddress,
+                    shader_location: 9,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 19]>() as BufferAddress,
+                    shader_location: 10,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 22]>() as BufferAddress,
+                    shader_location: 11,
+                    format: VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// Constant part of a material, mirrors `Material` in model.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialParams {
+    /// Multiplies the diffuse texture.
+    pub diffuse: [f32; 4],
+    /// Specular color with the Blinn-Phong exponent in `w`.
+    pub specular: [f32; 4],
+    /// Strength of the normal map in the tangent plane.
+    pub normal_scale: f32,
+    /// Set by [`Material::new`] when there is a normal map.
+    pub normal_map: u32,
+    pub _padding: [u32; 2],
+}
+
+impl Default for MaterialParams {
+    fn default() -> Self {
+        Self {
+            diffuse: [1.0; 4],
+            specular: [0.5, 0.5, 0.5, 32.0],
+            normal_scale: 1.0,
+            normal_map: 0,
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// Layout of [`Material::bind_group`]: diffuse texture, its sampler, the [`MaterialParams`] and a
+/// tangent space normal map sampled with the same sampler.
+pub fn material_bind_group_layout(device: &Device) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Material Bind Group Layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
 pub(crate) struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
+    /// Every mesh's triangles kept on the CPU, for collisions and sampling.
+    pub triangles: Vec<[Vec3; 3]>,
+    /// What was missing from the file and replaced by a fallback.
+    pub warnings: Vec<ModelError>,
 }
 
-struct Mesh {
-    pub name: String,
+pub(crate) struct Mesh {
     pub vertex_buffer: Buffer,
     pub index_buffer: Buffer,
     pub num_elements: u32,
+    /// Index into [`Model::materials`], out of range for the default material.
     pub material: usize,
 }
 
-struct Material {
-    pub name: String,
-    pub diffuse_texture: crate::texture::Texture,
-    pub bind_group: BindGroup,
+impl Mesh {
+    pub fn new(device: &Device, name: &str, vertices: &[ModelVertex], indices: &[u32], material: usize) -> Self {
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{:?} Vertex Buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{:?} Index Buffer", name)),
+            contents: bytemuck::cast_slice(indices),
+            usage: BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+            material,
+        }
+    }
 }
 
-pub async fn load_texture(
-    file_name: &str,
-    device: &Device,
-    queue: &Queue,
-) -> anyhow::Result<texture::Texture> {
-    let data = std::fs::read(file_name)?;
-    texture::Texture::from_bytes(device, queue, &data, file_name)
+/// Positions of the triangles `indices` make out of `vertices`.
+pub fn triangles<'a>(vertices: &'a [ModelVertex], indices: &'a [u32]) -> impl Iterator<Item = [Vec3; 3]> + 'a {
+    let vertex = |i: u32| Vec3::from_array(vertices[i as usize].position);
+    indices.chunks_exact(3).map(move |t| [vertex(t[0]), vertex(t[1]), vertex(t[2])])
 }
 
-pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
-    let txt = std::fs::read_to_string(file_name)?;
-
-    Ok(txt)
+/// Unshares the vertices so every triangle gets its own face normal, for meshes that come without normals.
+pub fn flat_normals(vertices: &[ModelVertex], indices: &[u32]) -> (Vec<ModelVertex>, Vec<u32>) {
+    let mut flat = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| vertices[triangle[k] as usize]);
+        let [pa, pb, pc] = [a, b, c].map(|v| Vec3::from_array(v.position));
+        let normal = (pb - pa).cross(pc - pa).normalize_or_zero().to_array();
+        flat.extend([a, b, c].map(|v| ModelVertex { normal, ..v }));
+    }
+    let indices = (0..flat.len() as u32).collect();
+    (flat, indices)
 }
 
-pub async fn load_model(
-    file_name: &str,
-    device: &Device,
-    queue: &Queue,
-    layout: &BindGroupLayout,
-) -> anyhow::Result<Model> {
-    let obj_file = File::open(file_name)?;
-    let mut obj_reader = BufReader::new(obj_file);
-
-    let (models, obj_materials) = tobj::load_obj_buf_async(
-        &mut obj_reader,
-        &tobj::LoadOptions {
-            triangulate: true,
-            single_index: true,
-            ..Default::default()
-        },
-        |p| async move {
-            let mat_text = load_string(&p).await.unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
-        },
-    )
-        .await?;
+/// Only the bind group is kept, it holds on to the textures and parameters.
+pub(crate) struct Material {
+    pub bind_group: BindGroup,
+}
 
-    let mut materials = Vec::new();
-    for m in obj_materials? {
-        let diffuse_texture = load_texture(&m.diffuse_texture.unwrap(), device, queue).await?;
+impl Material {
+    /// Without a normal map the surface normals are used as they are.
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        name: &str,
+        diffuse_texture: texture::Texture,
+        normal_texture: Option<texture::Texture>,
+        mut params: MaterialParams,
+    ) -> Self {
+        params.normal_map = normal_texture.is_some() as u32;
+        let normal_texture = normal_texture
+            .unwrap_or_else(|| texture::Texture::from_color(device, queue, [128, 128, 255, 255], "Flat Normal Texture", false));
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{name} Material Buffer")),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: BufferUsages::UNIFORM,
+        });
         let bind_group = device.create_bind_group(&BindGroupDescriptor {
             layout,
             entries: &[
@@ -139,76 +362,270 @@ pub async fn load_model(
                     binding: 1,
                     resource: BindingResource::Sampler(&diffuse_texture.sampler),
                 },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&normal_texture.view),
+                },
             ],
-            label: None,
+            label: Some(name),
         });
+        Self { bind_group }
+    }
 
-        materials.push(Material {
-            name: m.name,
-            diffuse_texture,
-            bind_group,
-        })
+    /// Plain white, for meshes without a material.
+    pub fn white(device: &Device, queue: &Queue, layout: &BindGroupLayout) -> Self {
+        let white = texture::Texture::from_color(device, queue, [255; 4], "White Texture", true);
+        Self::new(device, queue, layout, "Default", white, None, MaterialParams::default())
+    }
+}
+
+/// Something wrong with a model file. Only [`ModelError::Obj`] stops a load, the others get a fallback
+/// and end up in [`Model::warnings`].
+#[derive(Debug)]
+pub enum ModelError {
+    /// The OBJ file itself couldn't be read or parsed.
+    Obj { path: PathBuf, source: tobj::LoadError },
+    /// A material library that couldn't be read, its meshes get the default material.
+    MissingMtl { path: PathBuf },
+    /// A texture that couldn't be read or decoded, diffuse textures fall back to white.
+    MissingTexture { material: String, path: PathBuf },
+    /// A mesh without texture coordinates, they are generated.
+    MissingUvs { mesh: String },
+}
+
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::Obj { path, source } => write!(f, "Failed to load {}: {source}", path.display()),
+            ModelError::MissingMtl { path } => write!(f, "Material library {} couldn't be read, using the default material", path.display()),
+            ModelError::MissingTexture { material, path } => write!(f, "Texture {} of material {material} couldn't be read", path.display()),
+            ModelError::MissingUvs { mesh } => write!(f, "Mesh {mesh} has no texture coordinates, generated planar ones"),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModelError::Obj { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Paths in OBJ and MTL files are relative to the file naming them, and often written on Windows.
+fn resolve_path(dir: &Path, file: &str) -> PathBuf {
+    dir.join(file.replace('\\', "/"))
+}
+
+/// Projects the vertices onto the two axes the mesh is widest along, stretched to fill the texture.
+fn planar_uvs(vertices: &mut [ModelVertex]) {
+    let (min, max) = vertices.iter()
+        .map(|v| Vec3::from_array(v.position))
+        .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), p| (min.min(p), max.max(p)));
+    let size = (max - min).max(Vec3::splat(1e-6));
+    let mut axes = [0, 1, 2];
+    axes.sort_by(|a, b| size[*b].total_cmp(&size[*a]));
+    for v in vertices {
+        let p = (Vec3::from_array(v.position) - min) / size;
+        v.tex_coords = [p[axes[0]], 1.0 - p[axes[1]]];
     }
+}
+
+pub(crate) struct ObjMesh {
+    pub name: String,
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    /// Index into [`ObjData::materials`], out of range for the default material.
+    pub material: usize,
+}
+
+pub(crate) struct ObjMaterial {
+    pub name: String,
+    pub diffuse_texture: Option<DynamicImage>,
+    pub normal_texture: Option<DynamicImage>,
+    pub params: MaterialParams,
+}
+
+/// An OBJ file read into memory with the fallbacks applied, [`load_model`] uploads it.
+pub(crate) struct ObjData {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: Vec<ObjMaterial>,
+    pub warnings: Vec<ModelError>,
+}
+
+/// Reads an OBJ with its material libraries and textures. Missing libraries, textures, UVs and normals
+/// don't fail the load, see [`ModelError`].
+pub(crate) fn read_obj(path: &Path) -> Result<ObjData, ModelError> {
+    let obj_error = |source| ModelError::Obj { path: path.to_path_buf(), source };
+    let obj_dir = path.parent().unwrap_or(Path::new(""));
+    let mut obj_reader = BufReader::new(File::open(path).map_err(|_| obj_error(tobj::LoadError::OpenFileFailed))?);
+
+    // Directory of each material's library for its textures, and the libraries that failed
+    let material_dirs = RefCell::new(Vec::new());
+    let missing_mtls = RefCell::new(Vec::new());
+    let (models, obj_materials) = tobj::load_obj_buf(
+        &mut obj_reader,
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+        |p| {
+            let mtl_path = resolve_path(obj_dir, &p.to_string_lossy());
+            let result = tobj::load_mtl(&mtl_path);
+            match &result {
+                Ok((materials, _)) => {
+                    let dir = mtl_path.parent().unwrap_or(Path::new("")).to_path_buf();
+                    material_dirs.borrow_mut().extend(std::iter::repeat_n(dir, materials.len()));
+                }
+                Err(_) => missing_mtls.borrow_mut().push(mtl_path),
+            }
+            result
+        },
+    ).map_err(obj_error)?;
 
-    let meshes = models
+    let mut warnings: Vec<ModelError> = missing_mtls.into_inner()
         .into_iter()
-        .map(|m| {
-            let vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| {
-                    if m.mesh.normals.is_empty() {
-                        ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]],
-                            normal: [0.0, 0.0, 0.0],
-                        }
-                    } else {
-                        ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]],
-                            normal: [
-                                m.mesh.normals[i * 3],
-                                m.mesh.normals[i * 3 + 1],
-                                m.mesh.normals[i * 3 + 2],
-                            ],
-                        }
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
-                label: Some(&format!("{:?} Vertex Buffer", file_name)),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: BufferUsages::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
-                label: Some(&format!("{:?} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: BufferUsages::INDEX,
-            });
-
-            Mesh {
-                name: file_name.to_string(),
-                vertex_buffer,
-                index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
+        .map(|path| ModelError::MissingMtl { path })
+        .collect();
+
+    // A failed library leaves the result in error but keeps the materials of the others
+    let material_dirs = material_dirs.into_inner();
+    let mut materials = Vec::new();
+    for (m, dir) in obj_materials.unwrap_or_default().into_iter().zip(&material_dirs) {
+        let mut read_texture = |file: &Option<String>| {
+            let path = resolve_path(dir, file.as_deref()?);
+            match image::open(&path) {
+                Ok(image) => Some(image),
+                Err(_) => {
+                    warnings.push(ModelError::MissingTexture { material: m.name.clone(), path });
+                    None
+                }
             }
+        };
+        let diffuse_texture = read_texture(&m.diffuse_texture);
+        let normal_texture = read_texture(&m.normal_texture);
+
+        let diffuse = m.diffuse.unwrap_or([1.0; 3]);
+        let specular = m.specular.unwrap_or([0.5; 3]);
+        materials.push(ObjMaterial {
+            diffuse_texture,
+            normal_texture,
+            params: MaterialParams {
+                diffuse: [diffuse[0], diffuse[1], diffuse[2], m.dissolve.unwrap_or(1.0)],
+                specular: [specular[0], specular[1], specular[2], m.shininess.unwrap_or(32.0)],
+                ..Default::default()
+            },
+            name: m.name,
+        });
+    }
+
+    let mut meshes = Vec::new();
+    for m in models {
+        let mesh = m.mesh;
+        let has_uvs = !mesh.texcoords.is_empty();
+        let mut vertices = (0..mesh.positions.len() / 3)
+            .map(|i| ModelVertex {
+                position: [mesh.positions[i * 3], mesh.positions[i * 3 + 1], mesh.positions[i * 3 + 2]],
+                tex_coords: match mesh.texcoords.get(i * 2..i * 2 + 2) {
+                    Some(uv) => [uv[0], 1.0 - uv[1]],
+                    None => [0.0, 0.0],
+                },
+                normal: match mesh.normals.get(i * 3..i * 3 + 3) {
+                    Some(n) => [n[0], n[1], n[2]],
+                    None => [0.0, 0.0, 0.0],
+                },
+            })
+            .collect::<Vec<_>>();
+        if !has_uvs {
+            planar_uvs(&mut vertices);
+            warnings.push(ModelError::MissingUvs { mesh: m.name.clone() });
+        }
+
+        let (vertices, indices) = if mesh.normals.is_empty() {
+            flat_normals(&vertices, &mesh.indices)
+        } else {
+            (vertices, mesh.indices)
+        };
+        meshes.push(ObjMesh {
+            name: m.name,
+            vertices,
+            indices,
+            material: mesh.material_id.unwrap_or(usize::MAX),
+        });
+    }
+
+    Ok(ObjData { meshes, materials, warnings })
+}
+
+fn is_gltf(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gltf") || e.eq_ignore_ascii_case("glb"))
+}
+
+/// Loads an OBJ, or a glTF/GLB file going by the extension.
+pub fn load_model(
+    file_name: &str,
+    device: &Device,
+    queue: &Queue,
+    layout: &BindGroupLayout,
+) -> anyhow::Result<Model> {
+    let path = Path::new(file_name);
+    if is_gltf(path) {
+        return crate::gltf_model::load_model(path, device, queue, layout);
+    }
+
+    let obj = read_obj(path)?;
+    let materials = obj.materials
+        .into_iter()
+        .map(|m| -> anyhow::Result<Material> {
+            let diffuse_texture = match &m.diffuse_texture {
+                Some(image) => texture::Texture::from_image(device, queue, image, Some(&m.name), true)?,
+                None => texture::Texture::from_color(device, queue, [255; 4], "White Texture", true),
+            };
+            let normal_texture = m.normal_texture.as_ref()
+                .map(|image| texture::Texture::from_image(device, queue, image, Some(&format!("{} normals", m.name)), false))
+                .transpose()?;
+            Ok(Material::new(device, queue, layout, &m.name, diffuse_texture, normal_texture, m.params))
         })
-        .collect::<Vec<_>>();
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let triangles = obj.meshes.iter().flat_map(|m| triangles(&m.vertices, &m.indices)).collect();
+    let meshes = obj.meshes
+        .into_iter()
+        .map(|m| Mesh::new(device, &m.name, &m.vertices, &m.indices, m.material))
+        .collect();
+
+    Ok(Model { meshes, materials, triangles, warnings: obj.warnings })
+}
 
-    Ok(Model { meshes, materials })
+/// Triangles of every shape in an OBJ or glTF file, materials are ignored.
+pub fn load_triangles(path: &Path) -> anyhow::Result<Vec<[Vec3; 3]>> {
+    if is_gltf(path) {
+        return crate::gltf_model::load_triangles(path);
+    }
+    let (models, _) = tobj::load_obj(path, &tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    }).with_context(|| format!("Failed to load {}", path.display()))?;
+
+    let mut triangles = Vec::new();
+    for model in &models {
+        let mesh = &model.mesh;
+        let vertex = |i: u32| Vec3::from_slice(&mesh.positions[3 * i as usize..3 * i as usize + 3]);
+        triangles.extend(mesh.indices.chunks_exact(3).map(|t| [vertex(t[0]), vertex(t[1]), vertex(t[2])]));
+    }
+    if triangles.is_empty() {
+        anyhow::bail!("{} has no triangles", path.display());
+    }
+    Ok(triangles)
 }
 
 pub trait DrawModel<'a> {
-    fn draw_mesh(&mut self, mesh: &'a Mesh);
     fn draw_mesh_instanced(
         &mut self,
         mesh: &'a Mesh,
@@ -219,10 +636,6 @@ impl<'a, 'b> DrawModel<'b> for RenderPass<'a>
 where
     'b: 'a,
 {
-    fn draw_mesh(&mut self, mesh: &'b Mesh) {
-        self.draw_mesh_instanced(mesh, 0..1);
-    }
-
     fn draw_mesh_instanced(
         &mut self,
         mesh: &'b Mesh,
@@ -232,4 +645,82 @@ where
         self.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus(file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/models").join(file)
+    }
+
+    #[test]
+    fn textured_obj_loads_without_warnings() {
+        let obj = read_obj(&corpus("textured.obj")).unwrap();
+        assert!(obj.warnings.is_empty(), "{:?}", obj.warnings);
+        assert_eq!(obj.materials.len(), 1);
+        let material = &obj.materials[0];
+        assert_eq!(material.params.diffuse, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(material.params.specular[3], 64.0);
+        assert!(material.diffuse_texture.is_some());
+
+        let mesh = &obj.meshes[0];
+        assert_eq!(mesh.material, 0);
+        assert_eq!(mesh.indices.len(), 6);
+        // V is flipped for wgpu
+        assert_eq!(mesh.vertices[0].tex_coords, [0.0, 1.0]);
+        assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn missing_mtl_falls_back_to_default_material() {
+        let obj = read_obj(&corpus("missing_mtl.obj")).unwrap();
+        assert!(matches!(&obj.warnings[..], [ModelError::MissingMtl { path }] if path.ends_with("nowhere.mtl")));
+        assert!(obj.materials.is_empty());
+        assert_eq!(obj.meshes[0].material, usize::MAX);
+    }
+
+    #[test]
+    fn missing_texture_keeps_material() {
+        let obj = read_obj(&corpus("missing_texture.obj")).unwrap();
+        assert!(matches!(&obj.warnings[..], [ModelError::MissingTexture { material, path }]
+            if material == "broken" && path.ends_with("nowhere.png")));
+        assert_eq!(obj.materials.len(), 1);
+        assert!(obj.materials[0].diffuse_texture.is_none());
+        assert_eq!(obj.meshes[0].material, 0);
+    }
+
+    #[test]
+    fn missing_uvs_and_normals_are_generated() {
+        let obj = read_obj(&corpus("no_uvs.obj")).unwrap();
+        assert!(matches!(&obj.warnings[..], [ModelError::MissingUvs { mesh }] if mesh == "wedge"));
+
+        let mesh = &obj.meshes[0];
+        assert_eq!(mesh.material, usize::MAX);
+        // Flat normals unshare the vertices
+        assert_eq!(mesh.vertices.len(), 6);
+        for v in &mesh.vertices {
+            assert!(v.tex_coords.iter().all(|t| (0.0..=1.0).contains(t)));
+            assert!((Vec3::from_array(v.normal).length() - 1.0).abs() < 1e-5);
+        }
+        // Widest along x, then y
+        let corner = mesh.vertices.iter().find(|v| v.position == [2.0, 0.0, 0.0]).unwrap();
+        assert_eq!(corner.tex_coords, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_referencing_file() {
+        let obj = read_obj(&corpus("nested/nested.obj")).unwrap();
+        assert!(obj.warnings.is_empty(), "{:?}", obj.warnings);
+        assert!(obj.materials[0].diffuse_texture.is_some());
+    }
+
+    #[test]
+    fn broken_obj_is_an_error() {
+        assert!(matches!(read_obj(&corpus("invalid.obj")), Err(ModelError::Obj { .. })));
+        assert!(matches!(
+            read_obj(&corpus("nowhere.obj")),
+            Err(ModelError::Obj { source: tobj::LoadError::OpenFileFailed, .. })
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use egui_wgpu::wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Maintain, MapMode, Queue};
+
+use crate::attributes::{AttributeKind, ATTRIBUTES};
+
+/// Particles read back per copy, keeps the staging buffer small.
+const CHUNK: u32 = 1 << 20;
+
+/// CPU copy of the particle buffers, for exporting.
+pub struct ParticleSnapshot {
+    pub positions: Vec<[f32; 4]>,
+    pub velocities: Vec<[f32; 4]>,
+    /// Records laid out by [`crate::attributes`].
+    pub attributes: Vec<u32>,
+}
+
+impl ParticleSnapshot {
+    /// Copies the first `count` particles back from the GPU, blocking until done. The buffers need
+    /// `COPY_SRC` usage.
+    pub fn read(device: &Device, queue: &Queue, positions: &Buffer, velocities: &Buffer, attributes: &Buffer, count: u32) -> anyhow::Result<Self> {
+        let vec4 = std::mem::size_of::<[f32; 4]>() as u64;
+        let record = crate::attributes::stride() as u64 * 4;
+        let staging = device.create_buffer(&BufferDescriptor {
+            label: Some("Particle Readback Buffer"),
+            size: CHUNK as u64 * (2 * vec4 + record),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut snapshot = Self {
+            positions: Vec::with_capacity(count as usize),
+            velocities: Vec::with_capacity(count as usize),
+            attributes: Vec::with_capacity(count as usize * crate::attributes::stride() as usize),
+        };
+        for first in (0..count).step_by(CHUNK as usize) {
+            let n = (count - first).min(CHUNK) as u64;
+            let first = first as u64;
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("Particle Readback Encoder") });
+            encoder.copy_buffer_to_buffer(positions, first * vec4, &staging, 0, n * vec4);
+            encoder.copy_buffer_to_buffer(velocities, first * vec4, &staging, n * vec4, n * vec4);
+            encoder.copy_buffer_to_buffer(attributes, first * record, &staging, 2 * n * vec4, n * record);
+            queue.submit(Some(encoder.finish()));
+
+            let slice = staging.slice(..n * (2 * vec4 + record));
+            let (sender, receiver) = std::sync::mpsc::channel();
+            slice.map_async(MapMode::Read, move |result| sender.send(result).unwrap());
+            device.poll(Maintain::Wait);
+            receiver.recv()?.context("Failed to read the particles back")?;
+            {
+                let data = slice.get_mapped_range();
+                let (p, rest) = data.split_at((n * vec4) as usize);
+                let (v, a) = rest.split_at((n * vec4) as usize);
+                snapshot.positions.extend_from_slice(bytemuck::cast_slice(p));
+                snapshot.velocities.extend_from_slice(bytemuck::cast_slice(v));
+                snapshot.attributes.extend_from_slice(bytemuck::cast_slice(a));
+            }
+            staging.unmap();
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the live particles as a binary PLY point cloud with velocities and every attribute, the
+    /// color as the usual `red`, `green`, `blue` and `alpha` bytes. Returns the number written.
+    pub fn write_ply(&self, path: impl AsRef<Path>) -> anyhow::Result<usize> {
+        let path = path.as_ref();
+        let stride = crate::attributes::stride() as usize;
+        // Dead particles are parked with w = 0
+        let alive: Vec<usize> = (0..self.positions.len()).filter(|&i| self.positions[i][3] != 0.0).collect();
+
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+        writeln!(file, "ply\nformat binary_little_endian 1.0\nelement vertex {}", alive.len())?;
+        for name in ["x", "y", "z", "vx", "vy", "vz"] {
+            writeln!(file, "property float {name}")?;
+        }
+        for attribute in ATTRIBUTES {
+            match attribute.kind {
+                AttributeKind::Float => writeln!(file, "property float {}", attribute.name)?,
+                AttributeKind::Color => {
+                    for channel in ["red", "green", "blue", "alpha"] {
+                        writeln!(file, "property uchar {channel}")?;
+                    }
+                }
+            }
+        }
+        writeln!(file, "end_header")?;
+
+        for &i in &alive {
+            for value in self.positions[i][..3].iter().chain(&self.velocities[i][..3]) {
+                file.write_all(&value.to_le_bytes())?;
+            }
+            // Float bits and packed colors are both written as their little endian bytes
+            for word in &self.attributes[i * stride..(i + 1) * stride] {
+                file.write_all(&word.to_le_bytes())?;
+            }
+        }
+        file.flush()?;
+        Ok(alive.len())
+    }
+}
@@ -0,0 +1,173 @@
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages, CommandEncoder, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, PipelineLayoutDescriptor, Queue, ShaderModuleDescriptor, ShaderSource, ShaderStages};
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+/// Mirrors `CullParams` in cull.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CullParams {
+    pub num_points: u32,
+    pub lod: u32,
+    /// View depth up to which every point is kept.
+    pub lod_near: f32,
+    /// View depth past which only `lod_min_fraction` of the points are kept.
+    pub lod_far: f32,
+    pub lod_min_fraction: f32,
+    pub _padding: [u32; 3],
+}
+
+/// Compute pre-pass for the point cloud. Writes the indices of the points inside the frustum into a
+/// compacted index buffer along with the arguments for `draw_indexed_indirect`, so nothing has to be read back.
+pub struct PointCull {
+    pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+
+    pub params: CullParams,
+    params_buffer: Buffer,
+
+    index_buffer: Buffer,
+    index_capacity: u32,
+    indirect_buffer: Buffer,
+}
+
+impl PointCull {
+    pub fn new(device: &Device, camera_buffer: &Buffer, point_buffer: &Buffer, num_points: u32) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Cull Shader"),
+            source: ShaderSource::Wgsl(format!("{}\n{}", include_str!("rng.wgsl"), include_str!("cull.wgsl")).into()),
+        });
+
+        let buffer_entry = |binding: u32, ty: BufferBindingType| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Cull Bind Group Layout"),
+            entries: &[
+                buffer_entry(0, BufferBindingType::Uniform),
+                buffer_entry(1, BufferBindingType::Uniform),
+                buffer_entry(2, BufferBindingType::Storage { read_only: true }),
+                buffer_entry(3, BufferBindingType::Storage { read_only: false }),
+                buffer_entry(4, BufferBindingType::Storage { read_only: false }),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Cull Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Cull Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+        });
+
+        let params = CullParams {
+            num_points,
+            lod: 0,
+            lod_near: 2.0,
+            lod_far: 20.0,
+            lod_min_fraction: 0.1,
+            _padding: [0; 3],
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Cull Params Buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let indirect_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Cull Indirect Buffer"),
+            size: std::mem::size_of::<[u32; 5]>() as u64,
+            usage: BufferUsages::INDIRECT | BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = Self::create_index_buffer(device, num_points);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, camera_buffer, &params_buffer, point_buffer, &index_buffer, &indirect_buffer);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            params,
+            params_buffer,
+            index_buffer,
+            index_capacity: num_points,
+            indirect_buffer,
+        }
+    }
+
+    fn create_index_buffer(device: &Device, num_points: u32) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Cull Index Buffer"),
+            size: (num_points.max(1) as usize * std::mem::size_of::<u32>()) as u64,
+            usage: BufferUsages::INDEX | BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        camera_buffer: &Buffer,
+        params_buffer: &Buffer,
+        point_buffer: &Buffer,
+        index_buffer: &Buffer,
+        indirect_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Cull Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 1, resource: params_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 2, resource: point_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 3, resource: index_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 4, resource: indirect_buffer.as_entire_binding() },
+            ],
+        })
+    }
+
+    /// Culls a different point buffer, the point buffer needs `STORAGE` usage.
+    pub fn set_point_buffer(&mut self, device: &Device, queue: &Queue, camera_buffer: &Buffer, point_buffer: &Buffer, num_points: u32) {
+        if num_points > self.index_capacity {
+            self.index_buffer = Self::create_index_buffer(device, num_points);
+            self.index_capacity = num_points;
+        }
+        self.params.num_points = num_points;
+        self.update_params(queue);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, camera_buffer, &self.params_buffer, point_buffer, &self.index_buffer, &self.indirect_buffer);
+    }
+
+    pub fn update_params(&self, queue: &Queue) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+    }
+
+    pub fn cull(&self, encoder: &mut CommandEncoder) {
+        encoder.clear_buffer(&self.indirect_buffer, 0, None);
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Cull Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.params.num_points.div_ceil(256), 1, 1);
+    }
+
+    pub fn index_buffer(&self) -> &Buffer {
+        &self.index_buffer
+    }
+
+    pub fn indirect_buffer(&self) -> &Buffer {
+        &self.indirect_buffer
+    }
+}
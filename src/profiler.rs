@@ -0,0 +1,289 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Instant;
+
+use anyhow::Context;
+use egui_wgpu::wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Device, Features, Maintain, MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue};
+
+/// Passes that can be timed in one frame.
+const MAX_SCOPES: u32 = 16;
+/// Frames kept for the graphs and the CSV export.
+const HISTORY_LEN: usize = 3600;
+/// Frames that may wait on their timestamps at once.
+const READBACKS: usize = 4;
+
+const READBACK_FREE: u8 = 0;
+const READBACK_MAPPING: u8 = 1;
+const READBACK_MAPPED: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub struct ScopeTiming {
+    pub name: &'static str,
+    /// Time spent on the CPU recording the pass, or running it for CPU simulations.
+    pub cpu_ms: f32,
+    /// Time the GPU spent between the scope's timestamps, filled in a few frames later.
+    pub gpu_ms: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    pub frame: u64,
+    /// Wall time since the previous frame started.
+    pub frame_ms: f32,
+    pub particles: u32,
+    pub scopes: Vec<ScopeTiming>,
+}
+
+struct Readback {
+    buffer: Buffer,
+    frame: u64,
+    state: Arc<AtomicU8>,
+}
+
+struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readbacks: Vec<Readback>,
+    /// Readback used by the frame being recorded, `None` if all were still in flight.
+    current: Option<usize>,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+}
+
+/// Times the passes of a frame. Uses timestamp queries written between the passes when the device
+/// supports them and falls back to CPU timings otherwise.
+pub struct Profiler {
+    gpu: Option<GpuTimer>,
+
+    frame: u64,
+    frame_start: Option<Instant>,
+    current: Option<FrameStats>,
+    open_scope: Option<(&'static str, Instant)>,
+
+    pub history: VecDeque<FrameStats>,
+}
+
+impl Profiler {
+    /// Features the profiler needs for GPU timings, request whatever of these the adapter has.
+    pub const FEATURES: Features = Features::TIMESTAMP_QUERY.union(Features::TIMESTAMP_QUERY_INSIDE_ENCODERS);
+
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let gpu = device.features().contains(Self::FEATURES).then(|| {
+            let size = (2 * MAX_SCOPES as usize * std::mem::size_of::<u64>()) as u64;
+            GpuTimer {
+                query_set: device.create_query_set(&QuerySetDescriptor {
+                    label: Some("Profiler Query Set"),
+                    ty: QueryType::Timestamp,
+                    count: 2 * MAX_SCOPES,
+                }),
+                resolve_buffer: device.create_buffer(&BufferDescriptor {
+                    label: Some("Profiler Resolve Buffer"),
+                    size,
+                    usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readbacks: (0..READBACKS)
+                    .map(|_| Readback {
+                        buffer: device.create_buffer(&BufferDescriptor {
+                            label: Some("Profiler Readback Buffer"),
+                            size,
+                            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        }),
+                        frame: 0,
+                        state: Arc::new(AtomicU8::new(READBACK_FREE)),
+                    })
+                    .collect(),
+                current: None,
+                period: queue.get_timestamp_period(),
+            }
+        });
+
+        Self {
+            gpu,
+            frame: 0,
+            frame_start: None,
+            current: None,
+            open_scope: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn has_gpu_timings(&self) -> bool {
+        self.gpu.is_some()
+    }
+
+    /// Starts recording a frame and collects the timestamps of earlier frames that are ready.
+    pub fn begin_frame(&mut self, device: &Device) {
+        let now = Instant::now();
+        let frame_ms = self.frame_start.map_or(0.0, |start| (now - start).as_secs_f32() * 1000.0);
+        self.frame_start = Some(now);
+        self.frame += 1;
+        self.current = Some(FrameStats {
+            frame: self.frame,
+            frame_ms,
+            particles: 0,
+            scopes: Vec::new(),
+        });
+
+        let Some(gpu) = &mut self.gpu else { return };
+        device.poll(Maintain::Poll);
+        for readback in &gpu.readbacks {
+            if readback.state.load(Ordering::Acquire) != READBACK_MAPPED {
+                continue;
+            }
+            {
+                let data = readback.buffer.slice(..).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data);
+                if let Some(stats) = self.history.iter_mut().find(|s| s.frame == readback.frame) {
+                    for (i, scope) in stats.scopes.iter_mut().enumerate() {
+                        let elapsed = ticks[2 * i + 1].wrapping_sub(ticks[2 * i]);
+                        scope.gpu_ms = Some(elapsed as f32 * gpu.period / 1_000_000.0);
+                    }
+                }
+            }
+            readback.buffer.unmap();
+            readback.state.store(READBACK_FREE, Ordering::Release);
+        }
+        gpu.current = gpu.readbacks.iter().position(|r| r.state.load(Ordering::Acquire) == READBACK_FREE);
+    }
+
+    /// Number of particles advanced this frame, for the throughput.
+    pub fn set_particles(&mut self, particles: u32) {
+        if let Some(stats) = &mut self.current {
+            stats.particles = particles;
+        }
+    }
+
+    pub fn begin_scope(&mut self, encoder: &mut CommandEncoder, name: &'static str) {
+        let Some(stats) = &self.current else { return };
+        let index = stats.scopes.len() as u32;
+        if index >= MAX_SCOPES || self.open_scope.is_some() {
+            return;
+        }
+        if let Some(gpu) = &self.gpu {
+            if gpu.current.is_some() {
+                encoder.write_timestamp(&gpu.query_set, 2 * index);
+            }
+        }
+        self.open_scope = Some((name, Instant::now()));
+    }
+
+    pub fn end_scope(&mut self, encoder: &mut CommandEncoder) {
+        let (Some(stats), Some((name, start))) = (&mut self.current, self.open_scope.take()) else { return };
+        if let Some(gpu) = &self.gpu {
+            if gpu.current.is_some() {
+                encoder.write_timestamp(&gpu.query_set, 2 * stats.scopes.len() as u32 + 1);
+            }
+        }
+        stats.scopes.push(ScopeTiming {
+            name,
+            cpu_ms: start.elapsed().as_secs_f32() * 1000.0,
+            gpu_ms: None,
+        });
+    }
+
+    /// Copies the timestamps out, call after the last scope and before finishing the encoder.
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        let (Some(gpu), Some(stats)) = (&self.gpu, &self.current) else { return };
+        let Some(current) = gpu.current else { return };
+        let count = 2 * stats.scopes.len() as u32;
+        if count == 0 {
+            return;
+        }
+        encoder.resolve_query_set(&gpu.query_set, 0..count, &gpu.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&gpu.resolve_buffer, 0, &gpu.readbacks[current].buffer, 0, count as u64 * 8);
+    }
+
+    /// Finishes the frame, call after submitting it.
+    pub fn end_frame(&mut self) {
+        let Some(stats) = self.current.take() else { return };
+        if let Some(gpu) = &mut self.gpu {
+            if let Some(current) = gpu.current.take() {
+                if !stats.scopes.is_empty() {
+                    let readback = &mut gpu.readbacks[current];
+                    readback.frame = stats.frame;
+                    readback.state.store(READBACK_MAPPING, Ordering::Release);
+                    let state = readback.state.clone();
+                    readback.buffer.slice(..).map_async(MapMode::Read, move |result| {
+                        state.store(if result.is_ok() { READBACK_MAPPED } else { READBACK_FREE }, Ordering::Release);
+                    });
+                }
+            }
+        }
+
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(stats);
+    }
+
+    /// Averages over the last `frames` frames, skipping the first frame which has no frame time.
+    pub fn average_frame_ms(&self, frames: usize) -> f32 {
+        let recent: Vec<f32> = self.history.iter().rev().take(frames).map(|s| s.frame_ms).filter(|&ms| ms > 0.0).collect();
+        if recent.is_empty() { 0.0 } else { recent.iter().sum::<f32>() / recent.len() as f32 }
+    }
+
+    /// One row per scope and frame.
+    pub fn export_csv(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+        writeln!(file, "frame,frame_ms,particles,scope,cpu_ms,gpu_ms")?;
+        for stats in &self.history {
+            for scope in &stats.scopes {
+                let gpu_ms = scope.gpu_ms.map(|ms| ms.to_string()).unwrap_or_default();
+                writeln!(file, "{},{},{},{},{},{}", stats.frame, stats.frame_ms, stats.particles, scope.name, scope.cpu_ms, gpu_ms)?;
+            }
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Frame time graph, throughput and the per pass breakdown of the latest complete frame.
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let average = self.average_frame_ms(60);
+        let particles = self.history.back().map_or(0, |s| s.particles);
+        ui.label(format!("{:.2} ms ({:.0} fps)", average, if average > 0.0 { 1000.0 / average } else { 0.0 }));
+        if average > 0.0 && particles > 0 {
+            ui.label(format!("{:.1} M particles/s", particles as f32 / average / 1000.0));
+        }
+        if !self.has_gpu_timings() {
+            ui.label("Timestamp queries unsupported, showing CPU timings");
+        }
+
+        // Frame times of the last few seconds, the line marks 60 fps
+        let frames: Vec<f32> = self.history.iter().rev().take(240).map(|s| s.frame_ms).collect();
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width().max(240.0), 80.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(120));
+        let max_ms = frames.iter().copied().fold(1000.0 / 30.0, f32::max);
+        let y = |ms: f32| rect.bottom() - ms / max_ms * rect.height();
+        painter.hline(rect.x_range(), y(1000.0 / 60.0), egui::Stroke::new(1.0, egui::Color32::DARK_GREEN));
+        let points: Vec<egui::Pos2> = frames.iter().enumerate()
+            .map(|(i, &ms)| egui::pos2(rect.right() - i as f32 / 239.0 * rect.width(), y(ms)))
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, egui::Color32::LIGHT_YELLOW)));
+        painter.text(rect.left_top() + egui::vec2(4.0, 2.0), egui::Align2::LEFT_TOP, format!("{max_ms:.0} ms"), egui::FontId::monospace(11.0), egui::Color32::GRAY);
+
+        // GPU timings arrive a few frames late
+        let latest = self.history.iter().rev()
+            .find(|s| !self.has_gpu_timings() || s.scopes.iter().all(|scope| scope.gpu_ms.is_some()));
+        if let Some(stats) = latest {
+            egui::Grid::new("profiler_scopes").striped(true).show(ui, |ui| {
+                ui.label("Pass");
+                ui.label("CPU ms");
+                ui.label("GPU ms");
+                ui.end_row();
+                for scope in &stats.scopes {
+                    ui.label(scope.name);
+                    ui.label(format!("{:.3}", scope.cpu_ms));
+                    ui.label(scope.gpu_ms.map_or(String::from("-"), |ms| format!("{ms:.3}")));
+                    ui.end_row();
+                }
+            });
+        }
+    }
+}
@@ -1,11 +1,37 @@
 use std::sync::Arc;
 
-use egui_wgpu::wgpu::{BindGroup, BindGroupLayout, BlendState, Buffer, Color, ColorTargetState, CommandEncoder, CompareFunction, DepthBiasState, DepthStencilState, Device, Face, FragmentState, FrontFace, include_wgsl, IndexFormat, LoadOp, Operations, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, StencilState, StoreOp, SurfaceConfiguration, TextureView, VertexState};
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, BufferBindingType, ShaderModuleDescriptor, ShaderSource, ShaderStages, Buffer, BufferDescriptor, BufferUsages, Color, COPY_BYTES_PER_ROW_ALIGNMENT, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, TextureAspect, ColorTargetState, CommandEncoder, CompareFunction, DepthBiasState, DepthStencilState, Device, Face, FragmentState, FrontFace, IndexFormat, LoadOp, Operations, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, StencilState, StoreOp, SurfaceConfiguration, TextureView, VertexState};
 use egui_wgpu::wgpu::TextureFormat::Bgra8UnormSrgb;
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
 
+use crate::glyph_renderer::COLORMAP_VIRIDIS;
 use crate::models::{CloudPoint, Vertex};
+use crate::point_cull::PointCull;
 use crate::vector::Vector;
 
+/// Colors the points by their distance from the origin.
+pub const COLOR_DISTANCE: u32 = 0;
+/// Maps the speed through the colormap.
+pub const COLOR_SPEED: u32 = 1;
+/// Maps a float attribute through the colormap.
+pub const COLOR_ATTRIBUTE: u32 = 2;
+/// Uses the color attribute as is.
+pub const COLOR_RGBA: u32 = 3;
+
+/// Mirrors `PointColor` in shader.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointColor {
+    pub mode: u32,
+    /// Word offset from [`crate::attributes::offset`].
+    pub attribute_offset: u32,
+    pub colormap: u32,
+    pub _padding: u32,
+    /// Values mapped to the ends of the colormap.
+    pub range: [f32; 2],
+    pub _padding2: [f32; 2],
+}
+
 pub struct Renderer {
     //Rendering
     render_pipeline: RenderPipeline,
@@ -18,9 +44,107 @@ pub struct Renderer {
 
     point_buffer: Arc<Buffer>,
     point_buffer_size: u32,
+
+    pub coloring: PointColoring,
+    /// Whether the drawn points are the simulated particles, other point clouds have no velocities or
+    /// attributes and are colored by distance.
+    particle_data: bool,
+
+    /// Draws only what the cull pre-pass left over instead of every point.
+    pub culling: bool,
+    pub cull: PointCull,
+}
+
+/// How the points are colored and the particle data it reads, the velocities and attribute records.
+/// Bound as the last group of the point pipeline.
+pub struct PointColoring {
+    pub color: PointColor,
+    color_buffer: Buffer,
+    pub bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+}
+
+impl PointColoring {
+    pub fn new(device: &Device, velocity_buffer: &Buffer, attribute_buffer: &Buffer) -> Self {
+        let color = PointColor {
+            mode: COLOR_DISTANCE,
+            attribute_offset: 0,
+            colormap: COLORMAP_VIRIDIS,
+            _padding: 0,
+            range: [0.0, 1.0],
+            _padding2: [0.0; 2],
+        };
+        let color_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Point Color Buffer"),
+            contents: bytemuck::cast_slice(&[color]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let storage = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Particle Data Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Particle Data Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: color_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: velocity_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: attribute_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            color,
+            color_buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Uploads `color`, point clouds without `particle_data` can only be colored by distance.
+    pub fn update(&self, queue: &Queue, particle_data: bool) {
+        let mut color = self.color;
+        if !particle_data {
+            color.mode = COLOR_DISTANCE;
+        }
+        queue.write_buffer(&self.color_buffer, 0, bytemuck::cast_slice(&[color]));
+    }
 }
 
 impl Renderer {
+    /// Draws every point of `point_buffer`, a buffer of [`CloudPoint`]s.
     pub fn new(
         device: &Device,
         config: &SurfaceConfiguration,
@@ -28,17 +152,23 @@ impl Renderer {
         camera_buffer: Arc<Buffer>,
         camera_bind_group: Arc<BindGroup>,
         point_buffer: Arc<Buffer>,
-        point_buffer_size: u32,
+        coloring: PointColoring,
     ) -> Self {
+        let point_buffer_size = (point_buffer.size() / std::mem::size_of::<CloudPoint>() as u64) as u32;
         //+X is R, +Y is U, +Z is B
         let depth_texture = crate::texture::Texture::create_depth_texture(&device, config, "depth_texture");
 
-        let shader = device.create_shader_module(include_wgsl!("shader.wgsl"));
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: ShaderSource::Wgsl(format!("{}\n{}\n{}", include_str!("colormap.wgsl"), crate::attributes::wgsl(), include_str!("shader.wgsl")).into()),
+        });
 
+        let mut layouts = bind_group_layouts.to_vec();
+        layouts.push(&coloring.bind_group_layout);
         let render_pipeline_layout =
             device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts,
+                bind_group_layouts: &layouts,
                 push_constant_ranges: &[],
             });
 
@@ -87,6 +217,8 @@ impl Renderer {
             }
         );
 
+        let cull = PointCull::new(device, &camera_buffer, &point_buffer, point_buffer_size);
+
         Self {
             render_pipeline,
             depth_texture,
@@ -96,10 +228,19 @@ impl Renderer {
 
             point_buffer,
             point_buffer_size,
+
+            coloring,
+            particle_data: true,
+
+            culling: true,
+            cull,
         }
     }
 
     pub fn render(&self, encoder: &mut CommandEncoder, surface_view: &TextureView) {
+        if self.culling {
+            self.cull.cull(encoder);
+        }
         {
             let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: None,
@@ -128,13 +269,89 @@ impl Renderer {
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.coloring.bind_group, &[]);
 
             render_pass.set_vertex_buffer(0, self.point_buffer.slice(..));
 
-            render_pass.draw(0..self.point_buffer_size, 0..1);
+            if self.culling {
+                render_pass.set_index_buffer(self.cull.index_buffer().slice(..), IndexFormat::Uint32);
+                render_pass.draw_indexed_indirect(self.cull.indirect_buffer(), 0);
+            } else {
+                render_pass.draw(0..self.point_buffer_size, 0..1);
+            }
         }
     }
 
+    /// Swaps the point cloud that gets drawn, e.g. for the particles of [`crate::flip::FlipSim`].
+    /// The buffer needs `STORAGE` usage for culling. `particle_data` says whether it lines up with the
+    /// velocity and attribute buffers the renderer was created with.
+    pub fn set_point_buffer(&mut self, device: &Device, queue: &Queue, point_buffer: Arc<Buffer>, point_buffer_size: u32, particle_data: bool) {
+        self.cull.set_point_buffer(device, queue, &self.camera_buffer, &point_buffer, point_buffer_size);
+        self.point_buffer = point_buffer;
+        self.point_buffer_size = point_buffer_size;
+        self.particle_data = particle_data;
+        self.update_color(queue);
+    }
+
+    pub fn update_color(&self, queue: &Queue) {
+        self.coloring.update(queue, self.particle_data);
+    }
+
+    /// Depth of the point pass, later passes load it so they are occluded correctly.
+    pub fn depth_view(&self) -> &TextureView {
+        &self.depth_texture.view
+    }
+
+    /// Nearest depth in a small window around a pixel, so points a few pixels off still count.
+    /// Blocks until the copy is done, which is fine for one-off picking.
+    pub fn read_depth(&self, device: &Device, queue: &Queue, x: u32, y: u32) -> Option<f32> {
+        const WINDOW: u32 = 16;
+        let size = self.depth_texture.texture.size();
+        let x0 = x.saturating_sub(WINDOW / 2).min(size.width.saturating_sub(WINDOW));
+        let y0 = y.saturating_sub(WINDOW / 2).min(size.height.saturating_sub(WINDOW));
+        let width = WINDOW.min(size.width);
+        let height = WINDOW.min(size.height);
+        // One padded row per texel row
+        let bytes_per_row = COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Depth Readback Buffer"),
+            size: (bytes_per_row * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("Depth Readback Encoder") });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &self.depth_texture.texture,
+                mip_level: 0,
+                origin: Origin3d { x: x0, y: y0, z: 0 },
+                aspect: TextureAspect::DepthOnly,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |_| {});
+        device.poll(Maintain::Wait);
+        let data = slice.get_mapped_range();
+        let depths: &[f32] = bytemuck::cast_slice(&data);
+        let nearest = (0..height as usize)
+            .flat_map(|row| &depths[row * bytes_per_row as usize / 4..][..width as usize])
+            .copied()
+            .fold(1.0, f32::min);
+        (nearest < 1.0).then_some(nearest)
+    }
+
     pub fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
         self.depth_texture = crate::texture::Texture::create_depth_texture(device, config, "depth_texture");
     }
@@ -0,0 +1,129 @@
+use glam::Vec3;
+
+/// PCG hash (Jarzynski and Olano, "Hash Functions for GPU Rendering"), `pcg_hash` in rng.wgsl computes the same.
+pub fn pcg_hash(input: u32) -> u32 {
+    let state = input.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// Combines several keys, e.g. particle index, step and seed, into one well mixed value. Matches `rng_seed`.
+pub fn hash3(a: u32, b: u32, c: u32) -> u32 {
+    pcg_hash(a ^ pcg_hash(b ^ pcg_hash(c)))
+}
+
+/// Uniform in `[0, 1)` from the top 24 bits, which is all an `f32` can hold.
+pub fn unit_float(h: u32) -> f32 {
+    (h >> 8) as f32 / 16777216.0
+}
+
+/// Small seedable generator, it steps its state the same way `rng_next` in rng.wgsl does so CPU and
+/// GPU streams from the same state draw the same numbers.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Self { state: pcg_hash(seed) }
+    }
+
+    /// The stream `rng_seed(a, b, c)` starts in rng.wgsl.
+    pub fn from_keys(a: u32, b: u32, c: u32) -> Self {
+        Self { state: hash3(a, b, c) }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state = pcg_hash(self.state);
+        self.state
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        unit_float(self.next_u32())
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        self.next_f32() < 0.5
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    pub fn on_unit_sphere(&mut self) -> Vec3 {
+        let z = self.range(-1.0, 1.0);
+        let phi = self.range(0.0, std::f32::consts::TAU);
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+}
+
+// Twins of the rng.wgsl distributions only the shaders draw from, kept to test the WGSL side against
+#[cfg(test)]
+impl Rng {
+    /// Standard normal, Box-Muller like `rng_gaussian`.
+    fn gaussian(&mut self) -> f32 {
+        let u = 1.0 - self.next_f32();
+        let v = self.next_f32();
+        (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+    }
+
+    fn gaussian3(&mut self) -> Vec3 {
+        let x = self.gaussian();
+        let y = self.gaussian();
+        let z = self.gaussian();
+        Vec3::new(x, y, z)
+    }
+
+    fn in_unit_ball(&mut self) -> Vec3 {
+        let direction = self.on_unit_sphere();
+        direction * self.next_f32().powf(1.0 / 3.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Worked out independently of both implementations, rng.wgsl has to give the same
+    #[test]
+    fn hashes_are_pinned() {
+        assert_eq!(pcg_hash(0), 129708002);
+        assert_eq!(pcg_hash(1), 2831084092);
+        assert_eq!(pcg_hash(0xdeadbeef), 1730779506);
+        assert_eq!(hash3(0, 0, 0), 2145236065);
+        assert_eq!(hash3(1, 2, 3), 1493219802);
+    }
+
+    #[test]
+    fn stream_steps_like_rng_next() {
+        let mut rng = Rng::new(42);
+        assert_eq!([rng.next_u32(), rng.next_u32(), rng.next_u32()], [2785308739, 4004461565, 2892551605]);
+        assert_eq!(Rng::from_keys(1, 2, 3).state, hash3(1, 2, 3));
+    }
+
+    #[test]
+    fn distributions() {
+        let mut rng = Rng::new(7);
+        let n = 100_000;
+        let (mut sum, mut squares) = (0.0, 0.0);
+        let mut squares3 = Vec3::ZERO;
+        for _ in 0..n {
+            let g = rng.gaussian();
+            sum += g;
+            squares += g * g;
+            let g3 = rng.gaussian3();
+            squares3 += g3 * g3;
+            assert!((rng.on_unit_sphere().length() - 1.0).abs() < 1e-5);
+            assert!(rng.in_unit_ball().length() <= 1.0 + 1e-6);
+            let r = rng.range(-2.0, 3.0);
+            assert!((-2.0..3.0).contains(&r));
+        }
+        let mean = sum / n as f32;
+        assert!(mean.abs() < 0.02, "{mean}");
+        assert!((squares / n as f32 - mean * mean - 1.0).abs() < 0.03);
+        // Every axis of gaussian3 is a standard normal of its own
+        assert!((squares3 / n as f32 - Vec3::ONE).abs().max_element() < 0.03, "{squares3}");
+    }
+}
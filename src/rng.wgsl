@@ -0,0 +1,54 @@
+// PCG hash based random numbers, mirrors rng.rs. Prepend to any shader that needs them.
+
+fn pcg_hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Starting state for a stream keyed by e.g. particle index, step and seed
+fn rng_seed(a: u32, b: u32, c: u32) -> u32 {
+    return pcg_hash(a ^ pcg_hash(b ^ pcg_hash(c)));
+}
+
+fn rng_unit_float(h: u32) -> f32 {
+    return f32(h >> 8u) / 16777216.0;
+}
+
+fn rng_next(state: ptr<function, u32>) -> u32 {
+    *state = pcg_hash(*state);
+    return *state;
+}
+
+fn rng_float(state: ptr<function, u32>) -> f32 {
+    return rng_unit_float(rng_next(state));
+}
+
+fn rng_range(state: ptr<function, u32>, min: f32, max: f32) -> f32 {
+    return min + (max - min) * rng_float(state);
+}
+
+fn rng_gaussian(state: ptr<function, u32>) -> f32 {
+    let u = 1.0 - rng_float(state);
+    let v = rng_float(state);
+    return sqrt(-2.0 * log(u)) * cos(6.283185307 * v);
+}
+
+fn rng_gaussian3(state: ptr<function, u32>) -> vec3<f32> {
+    let x = rng_gaussian(state);
+    let y = rng_gaussian(state);
+    let z = rng_gaussian(state);
+    return vec3<f32>(x, y, z);
+}
+
+fn rng_on_unit_sphere(state: ptr<function, u32>) -> vec3<f32> {
+    let z = rng_range(state, -1.0, 1.0);
+    let phi = rng_range(state, 0.0, 6.283185307);
+    let r = sqrt(max(1.0 - z * z, 0.0));
+    return vec3<f32>(r * cos(phi), r * sin(phi), z);
+}
+
+fn rng_in_unit_ball(state: ptr<function, u32>) -> vec3<f32> {
+    let direction = rng_on_unit_sphere(state);
+    return direction * pow(rng_float(state), 1.0 / 3.0);
+}
@@ -0,0 +1,270 @@
+use std::thread::JoinHandle;
+
+use anyhow::{anyhow, bail};
+use glam::{Vec2, Vec3};
+
+use crate::rng::Rng;
+
+/// Where [`sample`] puts the particles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleMode {
+    /// On the triangles, weighted by their area.
+    Surface,
+    /// Inside the mesh, found by counting the triangles crossed along rays.
+    Volume,
+}
+
+/// Initial particle state in the layout of the simulation's buffers.
+pub struct Samples {
+    pub positions: Vec<[f32; 4]>,
+    pub velocities: Vec<[f32; 4]>,
+}
+
+/// Draws `count` particles from `triangles`. With a `normal_speed` they move along the surface normal,
+/// outwards for volume samples, zero leaves them at rest. The same seed always gives the same samples.
+pub fn sample(triangles: &[[Vec3; 3]], mode: SampleMode, count: usize, normal_speed: f32, seed: u32) -> anyhow::Result<Samples> {
+    let mut rng = Rng::new(seed);
+    let points = match mode {
+        SampleMode::Surface => sample_surface(triangles, count, &mut rng)?,
+        SampleMode::Volume => sample_volume(triangles, count, &mut rng)?,
+    };
+    Ok(Samples {
+        positions: points.iter().map(|(p, _)| p.extend(1.0).to_array()).collect(),
+        velocities: points.iter().map(|(_, n)| (*n * normal_speed).extend(1.0).to_array()).collect(),
+    })
+}
+
+/// [`sample`] on a worker thread, drawing millions of points takes seconds.
+pub struct SampleJob {
+    pub seed: u32,
+    handle: JoinHandle<anyhow::Result<Samples>>,
+}
+
+impl SampleJob {
+    pub fn spawn(triangles: Vec<[Vec3; 3]>, mode: SampleMode, count: usize, normal_speed: f32, seed: u32) -> Self {
+        Self {
+            seed,
+            handle: std::thread::spawn(move || sample(&triangles, mode, count, normal_speed, seed)),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the samples, check [`SampleJob::is_finished`] first to not block.
+    pub fn join(self) -> anyhow::Result<Samples> {
+        self.handle.join().map_err(|_| anyhow!("Sampling the meshes panicked"))?
+    }
+}
+
+fn face_normal([a, b, c]: &[Vec3; 3]) -> Vec3 {
+    (*b - *a).cross(*c - *a).normalize_or_zero()
+}
+
+/// Points with the normal of their triangle.
+fn sample_surface(triangles: &[[Vec3; 3]], count: usize, rng: &mut Rng) -> anyhow::Result<Vec<(Vec3, Vec3)>> {
+    // Running total of the areas, summed in f64 so big meshes don't lose their small triangles
+    let mut total = 0.0;
+    let cdf: Vec<f64> = triangles.iter()
+        .map(|[a, b, c]| {
+            total += 0.5 * (*b - *a).cross(*c - *a).length() as f64;
+            total
+        })
+        .collect();
+    if total <= 0.0 {
+        bail!("The meshes have no surface area");
+    }
+
+    let points = (0..count)
+        .map(|_| {
+            let target = rng.next_f32() as f64 * total;
+            let triangle = &triangles[cdf.partition_point(|&c| c <= target).min(triangles.len() - 1)];
+            // Square root warps the unit square onto the triangle without bunching at a corner
+            let r = rng.next_f32().sqrt();
+            let s = rng.next_f32();
+            let [a, b, c] = *triangle;
+            (a * (1.0 - r) + b * (r * (1.0 - s)) + c * (r * s), face_normal(triangle))
+        })
+        .collect();
+    Ok(points)
+}
+
+/// Where a ray along +x passes through a triangle.
+struct Crossing {
+    x: f32,
+    normal: Vec3,
+}
+
+/// Triangles bucketed by their extent in the YZ plane, so a ray along x only tests the ones in its cell.
+struct RayGrid {
+    min: Vec2,
+    cell_size: Vec2,
+    resolution: usize,
+    cells: Vec<Vec<u32>>,
+}
+
+impl RayGrid {
+    fn new(triangles: &[[Vec3; 3]], min: Vec3, max: Vec3) -> Self {
+        let resolution = ((triangles.len() as f32 / 4.0).sqrt() as usize).clamp(1, 256);
+        let min = Vec2::new(min.y, min.z);
+        let cell_size = (Vec2::new(max.y, max.z) - min).max(Vec2::splat(1e-6)) / resolution as f32;
+        let mut grid = Self {
+            min,
+            cell_size,
+            resolution,
+            cells: vec![Vec::new(); resolution * resolution],
+        };
+        for (i, triangle) in triangles.iter().enumerate() {
+            let yz = triangle.map(|v| Vec2::new(v.y, v.z));
+            let (lo, hi) = (grid.cell(yz[0].min(yz[1]).min(yz[2])), grid.cell(yz[0].max(yz[1]).max(yz[2])));
+            for y in lo.0..=hi.0 {
+                for z in lo.1..=hi.1 {
+                    grid.cells[z * resolution + y].push(i as u32);
+                }
+            }
+        }
+        grid
+    }
+
+    fn cell(&self, p: Vec2) -> (usize, usize) {
+        let c = ((p - self.min) / self.cell_size).floor();
+        let last = self.resolution as f32 - 1.0;
+        (c.x.clamp(0.0, last) as usize, c.y.clamp(0.0, last) as usize)
+    }
+
+    /// Every crossing of the ray through `p` in the YZ plane, sorted along x.
+    fn cast(&self, triangles: &[[Vec3; 3]], p: Vec2, crossings: &mut Vec<Crossing>) {
+        crossings.clear();
+        let (y, z) = self.cell(p);
+        for &i in &self.cells[z * self.resolution + y] {
+            let triangle = &triangles[i as usize];
+            let [a, b, c] = *triangle;
+            let (ab, ac, ap) = (Vec2::new(b.y - a.y, b.z - a.z), Vec2::new(c.y - a.y, c.z - a.z), p - Vec2::new(a.y, a.z));
+            let det = ab.perp_dot(ac);
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let s = ap.perp_dot(ac) / det;
+            let t = ab.perp_dot(ap) / det;
+            if s < 0.0 || t < 0.0 || s + t > 1.0 {
+                continue;
+            }
+            crossings.push(Crossing {
+                x: a.x + s * (b.x - a.x) + t * (c.x - a.x),
+                normal: face_normal(triangle),
+            });
+        }
+        crossings.sort_by(|a, b| a.x.total_cmp(&b.x));
+    }
+}
+
+/// Points with the outward normal of the closer wall along their ray. Each ray picks a random line
+/// through the bounds, the pairs of crossings along it are the spans inside. Keeping the ray in
+/// proportion to how much of it is inside makes the points uniform in the volume.
+fn sample_volume(triangles: &[[Vec3; 3]], count: usize, rng: &mut Rng) -> anyhow::Result<Vec<(Vec3, Vec3)>> {
+    let (min, max) = triangles.iter()
+        .flatten()
+        .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), v| (min.min(*v), max.max(*v)));
+    let width = max.x - min.x;
+    if triangles.is_empty() || width <= 0.0 {
+        bail!("The meshes enclose no volume");
+    }
+    let grid = RayGrid::new(triangles, min, max);
+
+    let mut points = Vec::with_capacity(count);
+    let mut crossings = Vec::new();
+    let mut misses = 0;
+    while points.len() < count {
+        let p = Vec2::new(min.y, min.z) + Vec2::new(rng.next_f32(), rng.next_f32()) * Vec2::new(max.y - min.y, max.z - min.z);
+        grid.cast(triangles, p, &mut crossings);
+        // An odd crossing left over means a hole in the mesh, it's ignored
+        let inside: f32 = crossings.chunks_exact(2).map(|span| span[1].x - span[0].x).sum();
+        if rng.next_f32() * width >= inside {
+            misses += 1;
+            if points.is_empty() && misses > 100_000 {
+                bail!("The meshes enclose no volume, are they closed?");
+            }
+            continue;
+        }
+
+        let mut offset = rng.next_f32() * inside;
+        for span in crossings.chunks_exact(2) {
+            let length = span[1].x - span[0].x;
+            if offset > length {
+                offset -= length;
+                continue;
+            }
+            // Entering a wall faces -x and leaving one faces +x, whichever way the triangle is wound
+            let normal = if offset < 0.5 * length {
+                -span[0].normal * span[0].normal.x.signum()
+            } else {
+                span[1].normal * span[1].normal.x.signum()
+            };
+            points.push((Vec3::new(span[0].x + offset, p.x, p.y), normal));
+            break;
+        }
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The closed cube from -1 to 1, wound outwards.
+    fn cube() -> Vec<[Vec3; 3]> {
+        let corner = |i: usize| Vec3::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+        );
+        let quads = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        quads.iter()
+            .flat_map(|[a, b, c, d]| [[corner(*a), corner(*b), corner(*c)], [corner(*a), corner(*c), corner(*d)]])
+            .collect()
+    }
+
+    #[test]
+    fn surface_samples_follow_the_area() {
+        let triangles = [
+            [Vec3::ZERO, Vec3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0)],
+            [Vec3::new(10.0, 0.0, 0.0), Vec3::new(11.0, 0.0, 0.0), Vec3::new(10.0, 2.0, 0.0)],
+        ];
+        let samples = sample(&triangles, SampleMode::Surface, 100_000, 1.0, 7).unwrap();
+        let first = samples.positions.iter().filter(|p| p[0] < 5.0).count() as f32 / 100_000.0;
+        assert!((first - 0.75).abs() < 0.01, "{first}");
+        assert!(samples.velocities.iter().all(|v| v[..3] == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn volume_samples_stay_inside_with_outward_normals() {
+        let samples = sample(&cube(), SampleMode::Volume, 20_000, 1.0, 7).unwrap();
+        assert_eq!(samples.positions.len(), 20_000);
+        for (p, v) in samples.positions.iter().zip(&samples.velocities) {
+            assert!(p[..3].iter().all(|c| c.abs() <= 1.0), "{p:?}");
+            // Rays run along x, so only the ±x faces are crossed, the closer one gives the normal
+            let expected = if p[0] < 0.0 { [-1.0, 0.0, 0.0] } else { [1.0, 0.0, 0.0] };
+            assert_eq!(v[..3], expected, "{p:?}");
+        }
+    }
+
+    #[test]
+    fn open_mesh_has_no_volume() {
+        let triangle = [[Vec3::ZERO, Vec3::X, Vec3::new(0.0, 1.0, 1.0)]];
+        let error = sample(&triangle, SampleMode::Volume, 10, 0.0, 0).err().unwrap();
+        assert!(error.to_string().contains("enclose no volume"), "{error}");
+    }
+
+    #[test]
+    fn same_seed_same_samples() {
+        for mode in [SampleMode::Surface, SampleMode::Volume] {
+            let a = sample(&cube(), mode, 1000, 0.5, 3).unwrap();
+            let b = sample(&cube(), mode, 1000, 0.5, 3).unwrap();
+            let c = sample(&cube(), mode, 1000, 0.5, 4).unwrap();
+            assert_eq!(a.positions, b.positions);
+            assert_eq!(a.velocities, b.velocities);
+            assert_ne!(a.positions, c.positions);
+        }
+    }
+}
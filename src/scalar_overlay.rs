@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferBindingType, BufferUsages, ColorTargetState, CommandEncoder, CompareFunction, DepthBiasState, DepthStencilState, Device, Extent3d, FragmentState, FrontFace, ImageCopyTexture, ImageDataLayout, LoadOp, Operations, Origin3d, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, StoreOp, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexState};
+use egui_wgpu::wgpu::TextureFormat::Bgra8UnormSrgb;
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+use glam::Vec3;
+
+use crate::glyph_renderer::COLORMAP_VIRIDIS;
+
+/// Where the scalar field is drawn and how it's colored, mirrors `ScalarPlane` in scalar_overlay.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ScalarPlane {
+    pub origin: [f32; 4],
+    pub u: [f32; 4],
+    pub v: [f32; 4],
+    /// Values mapped to the bottom and top of the colormap, set to the data by [`ScalarOverlay::write`].
+    pub range: [f32; 2],
+    pub colormap: u32,
+    pub opacity: f32,
+}
+
+/// A 2D scalar field such as the fluid's smoke or temperature, colormapped onto a translucent plane.
+pub struct ScalarOverlay {
+    texture: Texture,
+    dims: [u32; 2],
+    pub plane: ScalarPlane,
+    plane_buffer: Buffer,
+    pipeline: RenderPipeline,
+    bind_group: BindGroup,
+    camera_bind_group: Arc<BindGroup>,
+}
+
+impl ScalarOverlay {
+    /// An overlay for a `dims` grid covering the XY rectangle from `min` to `max`.
+    pub fn new(
+        device: &Device,
+        camera_bind_group_layout: &BindGroupLayout,
+        camera_bind_group: Arc<BindGroup>,
+        dims: [u32; 2],
+        min: Vec3,
+        max: Vec3,
+    ) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Scalar Overlay Texture"),
+            size: Extent3d {
+                width: dims[0],
+                height: dims[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let extent = max - min;
+        let plane = ScalarPlane {
+            origin: min.extend(0.0).to_array(),
+            u: [extent.x, 0.0, 0.0, 0.0],
+            v: [0.0, extent.y, 0.0, 0.0],
+            range: [0.0, 1.0],
+            colormap: COLORMAP_VIRIDIS,
+            opacity: 0.8,
+        };
+        let plane_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Scalar Overlay Plane Buffer"),
+            contents: bytemuck::cast_slice(&[plane]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Scalar Overlay Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Scalar Overlay Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: plane_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("scalar_overlay.wgsl"),
+            source: ShaderSource::Wgsl(format!("{}\n{}", include_str!("colormap.wgsl"), include_str!("scalar_overlay.wgsl")).into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Scalar Overlay Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Scalar Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // Translucent, so it's tested against the depth but doesn't hide what is drawn after it
+            depth_stencil: Some(DepthStencilState {
+                format: crate::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: Default::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: Bgra8UnormSrgb,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: Default::default(),
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            texture,
+            dims,
+            plane,
+            plane_buffer,
+            pipeline,
+            bind_group,
+            camera_bind_group,
+        }
+    }
+
+    /// Uploads one value per cell, x fastest, and stretches the colormap over their range.
+    pub fn write(&mut self, queue: &Queue, values: &[f32]) {
+        let (min, max) = values.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| (min.min(*v), max.max(*v)));
+        self.plane.range = if min < max { [min, max] } else { [min, min + 1.0] };
+        queue.write_texture(
+            ImageCopyTexture {
+                aspect: TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+            },
+            bytemuck::cast_slice(values),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.dims[0] * std::mem::size_of::<f32>() as u32),
+                rows_per_image: Some(self.dims[1]),
+            },
+            Extent3d {
+                width: self.dims[0],
+                height: self.dims[1],
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    pub fn render(&self, encoder: &mut CommandEncoder, queue: &Queue, surface_view: &TextureView, depth_view: &TextureView) {
+        queue.write_buffer(&self.plane_buffer, 0, bytemuck::cast_slice(&[self.plane]));
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Scalar Overlay Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
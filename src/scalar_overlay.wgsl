@@ -0,0 +1,51 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ScalarPlane {
+    origin: vec4<f32>,
+    u: vec4<f32>,
+    v: vec4<f32>,
+    range: vec2<f32>,
+    colormap: u32,
+    opacity: f32,
+}
+
+@group(1) @binding(0)
+var values: texture_2d<f32>;
+
+@group(1) @binding(1)
+var<uniform> plane: ScalarPlane;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 1.0), vec2<f32>(0.0, 1.0),
+    );
+    let c = corners[index];
+
+    var out: VertexOutput;
+    let world = plane.origin.xyz + c.x * plane.u.xyz + c.y * plane.v.xyz;
+    out.clip_position = camera.view_proj * vec4<f32>(world, 1.0);
+    out.uv = c;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // R32Float isn't filterable, every cell is drawn as a flat square
+    let dims = vec2<i32>(textureDimensions(values));
+    let cell = clamp(vec2<i32>(in.uv * vec2<f32>(dims)), vec2<i32>(0), dims - 1);
+    let value = textureLoad(values, cell, 0).r;
+    let x = (value - plane.range.x) / max(plane.range.y - plane.range.x, 1e-6);
+    return vec4<f32>(colormap(plane.colormap, x), plane.opacity);
+}
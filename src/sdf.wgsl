@@ -0,0 +1,60 @@
+// Sampling of the signed distance field baked by colliders.rs, concatenated in front of the shaders that read one.
+// Negative inside of the meshes.
+
+struct SdfParams {
+    bounds_min: vec4f,
+    bounds_max: vec4f,
+    dims: vec4u,
+    // Collision response, the bake ignores these
+    restitution: f32,
+    friction: f32,
+    thickness: f32,
+    enabled: u32,
+}
+
+// Returned outside of the baked bounds, nothing to collide with there
+const SDF_FAR: f32 = 1e6;
+
+fn sdf_load(sdf: texture_3d<f32>, dims: vec3u, c: vec3i) -> f32 {
+    return textureLoad(sdf, clamp(c, vec3i(0), vec3i(dims) - 1), 0).x;
+}
+
+fn sdf_voxel_size(params: SdfParams) -> vec3f {
+    return (params.bounds_max.xyz - params.bounds_min.xyz) / vec3f(params.dims.xyz);
+}
+
+// Voxels sit on cell centers like the field textures
+fn sdf_voxel_center(params: SdfParams, c: vec3u) -> vec3f {
+    return params.bounds_min.xyz + (vec3f(c) + 0.5) * sdf_voxel_size(params);
+}
+
+fn sample_sdf(sdf: texture_3d<f32>, params: SdfParams, p: vec3f) -> f32 {
+    let uvw = (p - params.bounds_min.xyz) / (params.bounds_max.xyz - params.bounds_min.xyz);
+    if (any(uvw < vec3f(0.0)) || any(uvw > vec3f(1.0))) {
+        return SDF_FAR;
+    }
+
+    let dims = params.dims.xyz;
+    let g = uvw * vec3f(dims) - 0.5;
+    let c0 = vec3i(floor(g));
+    let t = g - floor(g);
+
+    let x00 = mix(sdf_load(sdf, dims, c0), sdf_load(sdf, dims, c0 + vec3i(1, 0, 0)), t.x);
+    let x10 = mix(sdf_load(sdf, dims, c0 + vec3i(0, 1, 0)), sdf_load(sdf, dims, c0 + vec3i(1, 1, 0)), t.x);
+    let x01 = mix(sdf_load(sdf, dims, c0 + vec3i(0, 0, 1)), sdf_load(sdf, dims, c0 + vec3i(1, 0, 1)), t.x);
+    let x11 = mix(sdf_load(sdf, dims, c0 + vec3i(0, 1, 1)), sdf_load(sdf, dims, c0 + vec3i(1, 1, 1)), t.x);
+    return mix(mix(x00, x10, t.y), mix(x01, x11, t.y), t.z);
+}
+
+// Central differences one voxel apart, points away from the surface
+fn sdf_gradient(sdf: texture_3d<f32>, params: SdfParams, p: vec3f) -> vec3f {
+    let h = sdf_voxel_size(params);
+    let dx = vec3f(h.x, 0.0, 0.0);
+    let dy = vec3f(0.0, h.y, 0.0);
+    let dz = vec3f(0.0, 0.0, h.z);
+    return vec3f(
+        sample_sdf(sdf, params, p + dx) - sample_sdf(sdf, params, p - dx),
+        sample_sdf(sdf, params, p + dy) - sample_sdf(sdf, params, p - dy),
+        sample_sdf(sdf, params, p + dz) - sample_sdf(sdf, params, p - dz),
+    ) / (2.0 * h);
+}
@@ -0,0 +1,90 @@
+// Brute force bake of the collision field, one dispatch per chunk of triangles. The closest distance and
+// the winding number are carried between chunks, the winding number tells inside from outside even for
+// meshes with small holes.
+
+struct BakeRange {
+    first: u32,
+    count: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: SdfParams;
+@group(0) @binding(1)
+var<uniform> range: BakeRange;
+// Three vertices per triangle, in world space
+@group(0) @binding(2)
+var<storage, read> triangles: array<vec4f>;
+@group(0) @binding(3)
+var<storage, read_write> distances: array<f32>;
+@group(0) @binding(4)
+var<storage, read_write> winding: array<f32>;
+@group(0) @binding(5)
+var sdf_out: texture_storage_3d<r32float, write>;
+
+fn dot2(v: vec3f) -> f32 {
+    return dot(v, v);
+}
+
+// Inigo Quilez, "Triangle - distance"
+fn triangle_distance(p: vec3f, a: vec3f, b: vec3f, c: vec3f) -> f32 {
+    let ba = b - a;
+    let pa = p - a;
+    let cb = c - b;
+    let pb = p - b;
+    let ac = a - c;
+    let pc = p - c;
+    let nor = cross(ba, ac);
+
+    let outside = sign(dot(cross(ba, nor), pa)) + sign(dot(cross(cb, nor), pb)) + sign(dot(cross(ac, nor), pc)) < 2.0;
+    if (outside) {
+        return sqrt(min(min(
+            dot2(ba * clamp(dot(ba, pa) / max(dot2(ba), 1e-20), 0.0, 1.0) - pa),
+            dot2(cb * clamp(dot(cb, pb) / max(dot2(cb), 1e-20), 0.0, 1.0) - pb)),
+            dot2(ac * clamp(dot(ac, pc) / max(dot2(ac), 1e-20), 0.0, 1.0) - pc)));
+    }
+    return abs(dot(nor, pa)) / length(nor);
+}
+
+// Van Oosterom and Strackee, "The Solid Angle of a Plane Triangle"
+fn solid_angle(p: vec3f, a: vec3f, b: vec3f, c: vec3f) -> f32 {
+    let ra = a - p;
+    let rb = b - p;
+    let rc = c - p;
+    let la = length(ra);
+    let lb = length(rb);
+    let lc = length(rc);
+    let numerator = dot(ra, cross(rb, rc));
+    let denominator = la * lb * lc + dot(ra, rb) * lc + dot(rb, rc) * la + dot(rc, ra) * lb;
+    return 2.0 * atan2(numerator, denominator);
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn bake(@builtin(global_invocation_id) id: vec3<u32>) {
+    let dims = params.dims.xyz;
+    if (any(id >= dims)) {
+        return;
+    }
+    let index = id.x + dims.x * (id.y + dims.y * id.z);
+    let p = sdf_voxel_center(params, id);
+
+    var d = SDF_FAR;
+    var w = 0.0;
+    if (range.first > 0u) {
+        d = distances[index];
+        w = winding[index];
+    }
+    for (var t = range.first; t < range.first + range.count; t++) {
+        let a = triangles[3u * t].xyz;
+        let b = triangles[3u * t + 1u].xyz;
+        let c = triangles[3u * t + 2u].xyz;
+        d = min(d, triangle_distance(p, a, b, c));
+        w += solid_angle(p, a, b, c);
+    }
+    distances[index] = d;
+    winding[index] = w;
+
+    // Rewritten by every chunk, the last one leaves the final field. Either orientation counts as inside.
+    let inside = abs(w) / (4.0 * 3.14159265) > 0.5;
+    textureStore(sdf_out, id, vec4f(select(d, -d, inside), 0.0, 0.0, 0.0));
+}
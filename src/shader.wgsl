@@ -3,7 +3,7 @@
 //}
 
 struct VertexOutput{
-    @location(0) world_coord: vec4<f32>,
+    @location(0) color: vec4<f32>,
   @builtin(position) clip_position: vec4<f32>,
 }
 
@@ -14,6 +14,52 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct PointColor {
+    mode: u32,
+    // Word offset of the attribute for COLOR_ATTRIBUTE
+    attribute_offset: u32,
+    colormap: u32,
+    _padding: u32,
+    range: vec2<f32>,
+    _padding2: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> point_color: PointColor;
+@group(1) @binding(1)
+var<storage, read> velocities: array<vec4<f32>>;
+// Records of ATTRIBUTE_STRIDE words, see attributes.rs
+@group(1) @binding(2)
+var<storage, read> attributes: array<u32>;
+
+const COLOR_DISTANCE: u32 = 0;
+const COLOR_SPEED: u32 = 1;
+const COLOR_ATTRIBUTE: u32 = 2;
+const COLOR_RGBA: u32 = 3;
+
+fn color_of(i: u32, pos: vec4<f32>) -> vec4<f32> {
+    let base = i * ATTRIBUTE_STRIDE;
+    var value = 0.0;
+    switch (point_color.mode) {
+        case COLOR_SPEED: {
+            value = length(velocities[i].xyz);
+        }
+        case COLOR_ATTRIBUTE: {
+            value = bitcast<f32>(attributes[base + point_color.attribute_offset]);
+        }
+        case COLOR_RGBA: {
+            let color = unpack4x8unorm(attributes[base + ATTRIBUTE_COLOR]);
+            return vec4<f32>(color.rgb, 0.1 * color.a);
+        }
+        default: {
+            let vec_len = dot(pos.xyz, pos.xyz);
+            return vec4<f32>(magma_quintic(1 - clamp(vec_len, 0.0, 1.0)), 0.1);
+        }
+    }
+    let t = (value - point_color.range.x) / max(point_color.range.y - point_color.range.x, 1e-6);
+    return vec4<f32>(colormap(point_color.colormap, t), 0.1);
+}
+
 @vertex
 fn vs_main(
 //    cloud_point: CloudPoint,
@@ -22,39 +68,20 @@ fn vs_main(
 ) -> VertexOutput{
     var out: VertexOutput;
 
-    out.world_coord = pos;
+    out.color = color_of(index, pos);
 
     // Calculate the clip position by multiplying with the camera's view projection matrix
     out.clip_position = camera.view_proj * pos;
+    // Dead particles have w = 0, move them outside the clip volume
+    if (pos.w == 0.0) {
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
     return out;
 }
 
-fn viridis_quintic(in: f32) -> vec3<f32>{
-        let x = clamp(in, 0.0, 1.0);
-        let x1 = vec4<f32>(1.0, x, x * x, x * x * x );
-        let x2 = x1 * x1.w * x;
-        return vec3<f32>(
-                dot( x1.xyzw, vec4( 0.0280268003, -0.143510503, 2.225793877, -14.815088879 ) ) + dot( x2.xy, vec2( 25.212752309, -11.772589584 ) ),
-                dot( x1.xyzw, vec4( -0.002117546, 1.617109353, -1.909305070, 2.701152864 ) ) + dot( x2.xy, vec2( -1.685288385, 0.178738871 ) ),
-                dot( x1.xyzw, vec4( 0.300805501, 2.614650302, -12.019139090, 28.933559110 ) ) + dot( x2.xy, vec2( -33.491294770, 13.762053843 ) ) );
-
-}
-
-fn magma_quintic(in: f32) -> vec3<f32>{
-        let x = clamp(in, 0.0, 1.0);
-        let x1 = vec4<f32>( 1.0, x, x * x, x * x * x ); // 1 x x2 x3
-        let x2 = x1 * x1.w * x; // x4 x5 x6 x7
-    return vec3(
-        dot( x1.xyzw, vec4( -0.0023226960, 1.087154378, -0.109964741, 6.333665763 ) ) + dot( x2.xy, vec2( -11.640596589, 5.337625354 ) ),
-        dot( x1.xyzw, vec4( 0.010680993,0.176613780, 1.638227448, -6.743522237 ) ) + dot( x2.xy, vec2( 11.426396979, -5.523236379 ) ),
-        dot( x1.xyzw, vec4( -0.008260782,2.244286052, 3.005587601, -24.279769818 ) ) + dot( x2.xy, vec2( 32.484310068, -12.688259703 ) ) );
-}
-
-
 @fragment
 fn fs_main(
     in: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    let vec_len = dot(in.world_coord.xyz, in.world_coord.xyz);
-    return vec4<f32>(magma_quintic(1 - clamp(vec_len, 0.0, 1.0)),0.1);
-}
\ No newline at end of file
+    return in.color;
+}
@@ -0,0 +1,72 @@
+use std::time::Instant;
+
+/// Turns wall time into a whole number of fixed size simulation steps, so a run advances at the same
+/// rate regardless of the refresh rate. Leftover time carries over to the next frame.
+pub struct SimClock {
+    /// Steps per second of wall time at a speed of 1.
+    pub step_rate: f32,
+    pub speed: f32,
+    pub paused: bool,
+    /// Runs the particles backwards in time.
+    pub reversed: bool,
+    /// Caps the steps of one frame, slow frames drop the rest instead of piling up.
+    pub max_steps: u32,
+    /// Steps taken so far, counting down while reversed.
+    pub step_count: i64,
+
+    accumulator: f32,
+    last_tick: Option<Instant>,
+    pending_steps: u32,
+}
+
+impl SimClock {
+    pub fn new(step_rate: f32) -> Self {
+        Self {
+            step_rate,
+            speed: 1.0,
+            paused: false,
+            reversed: false,
+            max_steps: 8,
+            step_count: 0,
+            accumulator: 0.0,
+            last_tick: None,
+            pending_steps: 0,
+        }
+    }
+
+    /// `1.0` forwards and `-1.0` backwards.
+    pub fn direction(&self) -> f32 {
+        if self.reversed { -1.0 } else { 1.0 }
+    }
+
+    /// Queues a single step, meant for stepping through a paused simulation.
+    pub fn step_once(&mut self) {
+        self.pending_steps += 1;
+    }
+
+    /// Steps to run this frame. `frame_time` overrides the measured wall time, e.g. to advance by
+    /// exactly one frame per rendered frame while recording.
+    pub fn tick(&mut self, frame_time: Option<f32>) -> u32 {
+        let now = Instant::now();
+        let elapsed = frame_time.unwrap_or_else(|| self.last_tick.map_or(0.0, |last| (now - last).as_secs_f32()));
+        self.last_tick = Some(now);
+
+        let mut steps = std::mem::take(&mut self.pending_steps);
+        if self.paused {
+            self.accumulator = 0.0;
+        } else {
+            // Long stalls, e.g. dragging the window, shouldn't fast forward the simulation
+            self.accumulator += elapsed.min(0.25) * self.step_rate * self.speed;
+            let whole = self.accumulator.floor();
+            self.accumulator -= whole;
+            steps += whole as u32;
+        }
+        if steps > self.max_steps {
+            steps = self.max_steps;
+            self.accumulator = 0.0;
+        }
+
+        self.step_count += if self.reversed { -(steps as i64) } else { steps as i64 };
+        steps
+    }
+}
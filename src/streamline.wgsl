@@ -0,0 +1,46 @@
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct StreamlineParams {
+    max_speed: f32,
+    colormap: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: StreamlineParams;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) speed: f32,
+    @location(2) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position, 1.0);
+    out.normal = in.normal;
+    out.color = colormap(params.colormap, in.speed / max(params.max_speed, 1e-6));
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Lines have no normal, keep them unlit
+    if (dot(in.normal, in.normal) < 1e-6) {
+        return vec4<f32>(in.color, 1.0);
+    }
+    let light = normalize(vec3<f32>(0.4, 1.0, 0.6));
+    let diffuse = max(dot(normalize(in.normal), light), 0.0);
+    return vec4<f32>(in.color * (0.25 + 0.75 * diffuse), 1.0);
+}
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use egui_wgpu::wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, Buffer, BufferBindingType, BufferUsages, ColorTargetState, CommandEncoder, CompareFunction, DepthBiasState, DepthStencilState, Device, FragmentState, FrontFace, IndexFormat, LoadOp, Operations, PipelineLayout, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, StoreOp, TextureView, VertexState};
+use egui_wgpu::wgpu::TextureFormat::Bgra8UnormSrgb;
+use egui_wgpu::wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::glyph_renderer::COLORMAP_VIRIDIS;
+use crate::models::{StreamlineVertex, Vertex};
+use crate::streamlines::Streamline;
+
+/// Mirrors `StreamlineParams` in streamline.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct StreamlineStyle {
+    /// Speed mapped to the top of the colormap, set to the fastest point by [`StreamlineRenderer::set_streamlines`].
+    pub max_speed: f32,
+    pub colormap: u32,
+    pub _padding: [u32; 2],
+}
+
+struct StreamlineMesh {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+    tubes: bool,
+}
+
+/// Draws traced streamlines as lines or lit tubes, colored by speed, on top of the frame.
+pub struct StreamlineRenderer {
+    line_pipeline: RenderPipeline,
+    tube_pipeline: RenderPipeline,
+
+    camera_bind_group: Arc<BindGroup>,
+
+    pub style: StreamlineStyle,
+    style_buffer: Buffer,
+    style_bind_group: BindGroup,
+
+    mesh: Option<StreamlineMesh>,
+}
+
+impl StreamlineRenderer {
+    pub fn new(device: &Device, camera_bind_group_layout: &BindGroupLayout, camera_bind_group: Arc<BindGroup>) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Streamline Shader"),
+            source: ShaderSource::Wgsl(format!("{}\n{}", include_str!("colormap.wgsl"), include_str!("streamline.wgsl")).into()),
+        });
+
+        let style = StreamlineStyle {
+            max_speed: 1.0,
+            colormap: COLORMAP_VIRIDIS,
+            _padding: [0; 2],
+        };
+        let style_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Streamline Style Buffer"),
+            contents: bytemuck::cast_slice(&[style]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let style_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Streamline Style Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let style_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Streamline Style Bind Group"),
+            layout: &style_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: style_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Streamline Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &style_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            line_pipeline: Self::create_pipeline(device, &layout, &shader, PrimitiveTopology::LineList),
+            tube_pipeline: Self::create_pipeline(device, &layout, &shader, PrimitiveTopology::TriangleList),
+            camera_bind_group,
+            style,
+            style_buffer,
+            style_bind_group,
+            mesh: None,
+        }
+    }
+
+    fn create_pipeline(device: &Device, layout: &PipelineLayout, shader: &ShaderModule, topology: PrimitiveTopology) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Streamline Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[StreamlineVertex::desc()],
+            },
+            primitive: PrimitiveState {
+                topology,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: crate::texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: Default::default(),
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: Bgra8UnormSrgb,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            multiview: None,
+        })
+    }
+
+    /// Rebuilds the mesh, as tubes of `tube_radius` if given and as plain lines otherwise.
+    pub fn set_streamlines(&mut self, device: &Device, queue: &Queue, lines: &[Streamline], tube_radius: Option<f32>) {
+        let (vertices, indices) = match tube_radius {
+            Some(radius) => crate::streamlines::tube_mesh(lines, radius, 8),
+            None => crate::streamlines::line_mesh(lines),
+        };
+        if indices.is_empty() {
+            self.mesh = None;
+            return;
+        }
+
+        self.style.max_speed = lines.iter()
+            .flat_map(|line| line.speeds.iter().copied())
+            .fold(0.0, f32::max);
+        self.update_style(queue);
+
+        self.mesh = Some(StreamlineMesh {
+            vertex_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Streamline Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: BufferUsages::VERTEX,
+            }),
+            index_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Streamline Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: BufferUsages::INDEX,
+            }),
+            num_indices: indices.len() as u32,
+            tubes: tube_radius.is_some(),
+        });
+    }
+
+    pub fn update_style(&self, queue: &Queue) {
+        queue.write_buffer(&self.style_buffer, 0, bytemuck::cast_slice(&[self.style]));
+    }
+
+    pub fn render(&self, encoder: &mut CommandEncoder, surface_view: &TextureView, depth_view: &TextureView) {
+        let Some(mesh) = &self.mesh else { return };
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Streamline Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(if mesh.tubes { &self.tube_pipeline } else { &self.line_pipeline });
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.style_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+    }
+}
@@ -0,0 +1,218 @@
+use glam::Vec3;
+
+use crate::models::StreamlineVertex;
+use crate::vector_field::VectorField;
+
+/// Where streamlines start.
+#[derive(Debug, Copy, Clone)]
+pub enum Seeds {
+    Point(Vec3),
+    /// `count` seeds evenly spaced from `start` to `end`.
+    Line { start: Vec3, end: Vec3, count: usize },
+    /// A `counts[0]` by `counts[1]` grid spanning `origin + s * u + t * v` for `s, t` in `[0, 1]`.
+    Plane { origin: Vec3, u: Vec3, v: Vec3, counts: [usize; 2] },
+}
+
+impl Seeds {
+    pub fn points(&self) -> Vec<Vec3> {
+        // Position of sample `i` out of `n` along [0, 1], a single sample sits in the middle
+        let along = |i: usize, n: usize| if n > 1 { i as f32 / (n - 1) as f32 } else { 0.5 };
+
+        match *self {
+            Seeds::Point(p) => vec![p],
+            Seeds::Line { start, end, count } => (0..count)
+                .map(|i| start.lerp(end, along(i, count)))
+                .collect(),
+            Seeds::Plane { origin, u, v, counts } => (0..counts[1])
+                .flat_map(|j| (0..counts[0]).map(move |i| origin + along(i, counts[0]) * u + along(j, counts[1]) * v))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct StreamlineParams {
+    /// Step lengths are in world units, the integration follows the normalized field.
+    pub initial_step: f32,
+    pub min_step: f32,
+    pub max_step: f32,
+    /// Largest allowed difference between one full step and two half steps.
+    pub tolerance: f32,
+    pub max_points: usize,
+    pub max_length: f32,
+    /// Integration stops once the field gets slower than this.
+    pub stagnation_speed: f32,
+    /// Also trace upstream of the seed.
+    pub both_directions: bool,
+}
+
+impl Default for StreamlineParams {
+    fn default() -> Self {
+        Self {
+            initial_step: 0.01,
+            min_step: 0.0005,
+            max_step: 0.05,
+            tolerance: 1e-4,
+            max_points: 1000,
+            max_length: 10.0,
+            stagnation_speed: 1e-5,
+            both_directions: true,
+        }
+    }
+}
+
+/// A traced curve with the field speed at every point.
+#[derive(Debug, Clone, Default)]
+pub struct Streamline {
+    pub points: Vec<Vec3>,
+    pub speeds: Vec<f32>,
+}
+
+pub fn trace_all(field: &VectorField, seeds: &Seeds, params: &StreamlineParams) -> Vec<Streamline> {
+    seeds.points()
+        .into_iter()
+        .map(|seed| trace(field, seed, params))
+        .filter(|line| line.points.len() > 1)
+        .collect()
+}
+
+pub fn trace(field: &VectorField, seed: Vec3, params: &StreamlineParams) -> Streamline {
+    let mut line = integrate(field, seed, 1.0, params);
+    if params.both_directions {
+        let mut upstream = integrate(field, seed, -1.0, params);
+        upstream.points.reverse();
+        upstream.speeds.reverse();
+        // The seed is the first point of both halves
+        upstream.points.pop();
+        upstream.speeds.pop();
+        upstream.points.append(&mut line.points);
+        upstream.speeds.append(&mut line.speeds);
+        line = upstream;
+    }
+    line
+}
+
+/// Adaptive RK4, the step is halved or doubled by comparing a full step against two half steps.
+/// Stops when leaving the field, at stagnation points, or at the point/length limits.
+pub(crate) fn integrate(field: &VectorField, seed: Vec3, direction: f32, params: &StreamlineParams) -> Streamline {
+    let mut line = Streamline::default();
+    let mut p = seed;
+    let mut h = params.initial_step.clamp(params.min_step, params.max_step);
+    let mut length = 0.0;
+
+    while line.points.len() < params.max_points {
+        let Some(v) = field.sample(p) else { break };
+        let speed = v.length();
+        line.points.push(p);
+        line.speeds.push(speed);
+        if speed < params.stagnation_speed || length >= params.max_length {
+            break;
+        }
+
+        let next = loop {
+            let full = rk4_step(field, p, h, direction);
+            let half = rk4_step(field, p, 0.5 * h, direction)
+                .and_then(|mid| rk4_step(field, mid, 0.5 * h, direction));
+
+            match (full, half) {
+                (Some(full), Some(half)) => {
+                    let error = full.distance(half);
+                    if error > params.tolerance && h > params.min_step {
+                        h = (0.5 * h).max(params.min_step);
+                        continue;
+                    }
+                    if error < params.tolerance / 32.0 {
+                        h = (2.0 * h).min(params.max_step);
+                    }
+                    break Some(half);
+                }
+                // Stepped out of the field or into a zero, creep up to it before giving up
+                _ if h > params.min_step => h = (0.5 * h).max(params.min_step),
+                _ => break None,
+            }
+        };
+
+        let Some(next) = next else { break };
+        length += p.distance(next);
+        p = next;
+    }
+
+    line
+}
+
+fn rk4_step(field: &VectorField, p: Vec3, h: f32, direction: f32) -> Option<Vec3> {
+    let f = |p: Vec3| field.sample(p).and_then(|v| (v * direction).try_normalize());
+
+    let k1 = f(p)?;
+    let k2 = f(p + 0.5 * h * k1)?;
+    let k3 = f(p + 0.5 * h * k2)?;
+    let k4 = f(p + h * k3)?;
+    Some(p + h / 6.0 * (k1 + 2.0 * k2 + 2.0 * k3 + k4))
+}
+
+/// Streamlines as a line list, one vertex per point.
+pub fn line_mesh(lines: &[Streamline]) -> (Vec<StreamlineVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for line in lines {
+        let start = vertices.len() as u32;
+        vertices.extend(line.points.iter().zip(&line.speeds).map(|(p, &speed)| StreamlineVertex {
+            position: p.to_array(),
+            speed,
+            normal: [0.0; 3],
+        }));
+        for i in 1..line.points.len() as u32 {
+            indices.extend_from_slice(&[start + i - 1, start + i]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Streamlines as open tubes, the rings are oriented with parallel transported frames so they don't twist.
+pub fn tube_mesh(lines: &[Streamline], radius: f32, num_sides: u32) -> (Vec<StreamlineVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for line in lines {
+        let points = &line.points;
+        let n = points.len();
+        if n < 2 {
+            continue;
+        }
+
+        let start = vertices.len() as u32;
+        let mut normal = Vec3::ZERO;
+        for i in 0..n {
+            let tangent = (points[(i + 1).min(n - 1)] - points[i.saturating_sub(1)]).normalize_or_zero();
+            normal = if i == 0 {
+                tangent.any_orthonormal_vector()
+            } else {
+                (normal - tangent * normal.dot(tangent)).try_normalize().unwrap_or(normal)
+            };
+            let binormal = tangent.cross(normal);
+
+            for s in 0..num_sides {
+                let (sin, cos) = (s as f32 * 2.0 * std::f32::consts::PI / num_sides as f32).sin_cos();
+                let offset = cos * normal + sin * binormal;
+                vertices.push(StreamlineVertex {
+                    position: (points[i] + radius * offset).to_array(),
+                    speed: line.speeds[i],
+                    normal: offset.to_array(),
+                });
+            }
+        }
+
+        for i in 0..n as u32 - 1 {
+            for s in 0..num_sides {
+                let next = (s + 1) % num_sides;
+                let (a0, a1) = (start + i * num_sides + s, start + i * num_sides + next);
+                let (b0, b1) = (a0 + num_sides, a1 + num_sides);
+                indices.extend_from_slice(&[a0, b0, b1, a0, b1, a1]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
@@ -25,7 +25,8 @@ impl Texture {
             dimension: TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: TextureUsages::RENDER_ATTACHMENT // 3.
-                | TextureUsages::TEXTURE_BINDING,
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
             view_formats: &[],
         };
         let texture = device.create_texture(&desc);
@@ -57,11 +58,19 @@ impl Texture {
         label: &str,
     ) -> Result<Self> {
         let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label))
+        Self::from_image(device, queue, &img, Some(label), true)
     }
 
 
-    pub fn from_image(device: &Device, queue: &Queue, image: &DynamicImage, label: Option<&str>) -> Result<Self> {
+    /// A single texel of `rgba`, stands in for maps a material doesn't have.
+    pub fn from_color(device: &Device, queue: &Queue, rgba: [u8; 4], label: &str, srgb: bool) -> Self {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(rgba)));
+        Self::from_image(device, queue, &image, Some(label), srgb).expect("a single RGBA texel always converts")
+    }
+
+    /// Color maps are authored in sRGB and get decoded when sampled, data such as normal maps is
+    /// uploaded as it is with `srgb` off.
+    pub fn from_image(device: &Device, queue: &Queue, image: &DynamicImage, label: Option<&str>, srgb: bool) -> Result<Self> {
         let image = image.to_rgba8();
         let (w, h) = image.dimensions();
 
@@ -78,7 +87,7 @@ impl Texture {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: TextureDimension::D2,
-                format: TextureFormat::Rgba8Unorm,
+                format: if srgb { TextureFormat::Rgba8UnormSrgb } else { TextureFormat::Rgba8Unorm },
                 usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
                 view_formats: &[],
             });
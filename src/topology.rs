@@ -0,0 +1,289 @@
+use glam::Vec3;
+
+use crate::streamlines::{Streamline, StreamlineParams};
+use crate::vector_field::VectorField;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CriticalKind {
+    Source,
+    Sink,
+    Saddle,
+    Center,
+    RepellingSpiral,
+    AttractingSpiral,
+}
+
+impl CriticalKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CriticalKind::Source => "source",
+            CriticalKind::Sink => "sink",
+            CriticalKind::Saddle => "saddle",
+            CriticalKind::Center => "center",
+            CriticalKind::RepellingSpiral => "repelling spiral",
+            CriticalKind::AttractingSpiral => "attracting spiral",
+        }
+    }
+
+    pub fn color(&self) -> [u8; 3] {
+        match self {
+            CriticalKind::Source => [230, 60, 50],
+            CriticalKind::Sink => [60, 110, 230],
+            CriticalKind::Saddle => [240, 200, 40],
+            CriticalKind::Center => [80, 200, 90],
+            CriticalKind::RepellingSpiral => [240, 130, 40],
+            CriticalKind::AttractingSpiral => [150, 80, 220],
+        }
+    }
+}
+
+/// A zero of the field with its linearization.
+#[derive(Debug, Clone)]
+pub struct CriticalPoint {
+    pub position: Vec3,
+    pub kind: CriticalKind,
+    /// Eigenvalues of the Jacobian as `(re, im)`.
+    pub eigenvalues: Vec<(f32, f32)>,
+    /// World space eigenvectors of the real eigenvalues.
+    pub eigenvectors: Vec<(f32, Vec3)>,
+}
+
+/// Axes the field actually varies along. 2D fields, e.g. from `FluidSim` or a slice, are analyzed in their plane.
+fn active_axes(field: &VectorField) -> Vec<usize> {
+    (0..3).filter(|&a| field.dims[a] > 1).collect()
+}
+
+/// Finds the zeros of the field cell by cell. Cells where every component changes sign between the
+/// corners are refined with Newton's method and classified from the eigenvalues of the Jacobian.
+pub fn find_critical_points(field: &VectorField) -> Vec<CriticalPoint> {
+    let axes = active_axes(field);
+    if axes.len() < 2 {
+        return Vec::new();
+    }
+    let n = axes.len();
+
+    let mut points: Vec<CriticalPoint> = Vec::new();
+    let cells = std::array::from_fn::<usize, 3, _>(|a| field.dims[a].saturating_sub(1).max(1));
+    for k in 0..cells[2] {
+        for j in 0..cells[1] {
+            for i in 0..cells[0] {
+                let corners: Vec<Vec3> = (0..1 << n)
+                    .map(|c| {
+                        let mut idx = [i, j, k];
+                        for (r, &a) in axes.iter().enumerate() {
+                            idx[a] += (c >> r) & 1;
+                        }
+                        field.get(idx[0], idx[1], idx[2])
+                    })
+                    .collect();
+                // Zeros may sit exactly on a sample, but cells touching no-slip walls are zero along whole edges
+                if corners.iter().filter(|v| **v == Vec3::ZERO).count() > 1 {
+                    continue;
+                }
+                let brackets_zero = |a: usize| corners.iter().any(|v| v[a] >= 0.0) && corners.iter().any(|v| v[a] <= 0.0);
+                if !axes.iter().all(|&a| brackets_zero(a)) {
+                    continue;
+                }
+
+                let cell_min = field.position(i, j, k);
+                let cell_max = cell_min + field.spacing;
+                let Some(position) = newton(field, &axes, 0.5 * (cell_min + cell_max)) else { continue };
+                let slack = 1e-3 * field.spacing;
+                let inside = axes.iter().all(|&a| position[a] >= cell_min[a] - slack[a] && position[a] <= cell_max[a] + slack[a]);
+                // Neighboring cells share faces, keep the first hit
+                let duplicate = points.iter().any(|p| axes.iter().all(|&a| (p.position[a] - position[a]).abs() < 0.5 * field.spacing[a]));
+                if !inside || duplicate {
+                    continue;
+                }
+
+                let jacobian = jacobian(field, &axes, position);
+                let eigenvalues = eigenvalues(&jacobian, n);
+                let Some(kind) = classify(&eigenvalues) else { continue };
+                let eigenvectors = eigenvalues.iter()
+                    .filter(|(_, im)| *im == 0.0)
+                    .map(|&(re, _)| {
+                        let local = eigenvector(&jacobian, n, re);
+                        let mut world = Vec3::ZERO;
+                        for (r, &a) in axes.iter().enumerate() {
+                            world[a] = local[r];
+                        }
+                        (re, world.normalize_or_zero())
+                    })
+                    .collect();
+
+                points.push(CriticalPoint {
+                    position,
+                    kind,
+                    eigenvalues,
+                    eigenvectors,
+                });
+            }
+        }
+    }
+    points
+}
+
+/// Traces the separatrices of every saddle: downstream along the unstable and upstream along the stable directions.
+pub fn separatrices(field: &VectorField, points: &[CriticalPoint], params: &StreamlineParams) -> Vec<Streamline> {
+    let offset = 0.5 * active_axes(field).iter().map(|&a| field.spacing[a]).fold(f32::MAX, f32::min);
+
+    let mut lines = Vec::new();
+    for point in points.iter().filter(|p| p.kind == CriticalKind::Saddle) {
+        for &(eigenvalue, eigenvector) in &point.eigenvectors {
+            let direction = eigenvalue.signum();
+            for side in [-1.0, 1.0] {
+                let seed = point.position + side * offset * eigenvector;
+                let mut line = crate::streamlines::integrate(field, seed, direction, params);
+                if line.points.is_empty() {
+                    continue;
+                }
+                line.points.insert(0, point.position);
+                line.speeds.insert(0, 0.0);
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
+/// Projects a field sample onto the active axes, the unused slots stay zero.
+fn reduced(v: Vec3, axes: &[usize]) -> [f32; 3] {
+    std::array::from_fn(|r| axes.get(r).map_or(0.0, |&a| v[a]))
+}
+
+fn clamp_to_field(field: &VectorField, p: Vec3) -> Vec3 {
+    let (min, max) = field.bounds();
+    p.clamp(min, max)
+}
+
+/// Central differences, `jacobian[r][c]` is d(v_r)/d(x_c) in the active axes.
+fn jacobian(field: &VectorField, axes: &[usize], p: Vec3) -> [[f32; 3]; 3] {
+    let mut jacobian = [[0.0; 3]; 3];
+    for (c, &a) in axes.iter().enumerate() {
+        let mut h = Vec3::ZERO;
+        h[a] = 0.5 * field.spacing[a];
+        let p0 = clamp_to_field(field, p - h);
+        let p1 = clamp_to_field(field, p + h);
+        let (Some(v0), Some(v1)) = (field.sample(p0), field.sample(p1)) else { continue };
+        let dv = reduced(v1 - v0, axes);
+        let dx = p1[a] - p0[a];
+        if dx > 0.0 {
+            for r in 0..axes.len() {
+                jacobian[r][c] = dv[r] / dx;
+            }
+        }
+    }
+    jacobian
+}
+
+fn newton(field: &VectorField, axes: &[usize], start: Vec3) -> Option<Vec3> {
+    let n = axes.len();
+    let mut p = start;
+    for _ in 0..20 {
+        let v = reduced(field.sample(p)?, axes);
+        let j = jacobian(field, axes, p);
+
+        // Solve J dx = -v, padding 2D systems with an identity row
+        let m = glam::Mat3::from_cols_array_2d(&std::array::from_fn(|c| {
+            std::array::from_fn(|r| if r < n && c < n { j[r][c] } else if r == c { 1.0 } else { 0.0 })
+        }));
+        if m.determinant().abs() < 1e-12 {
+            return None;
+        }
+        let dx = m.inverse() * -Vec3::from_array(v);
+
+        let mut step = Vec3::ZERO;
+        for (r, &a) in axes.iter().enumerate() {
+            step[a] = dx[r];
+        }
+        p += step;
+        if axes.iter().all(|&a| step[a].abs() < 1e-5 * field.spacing[a]) {
+            return Some(p);
+        }
+    }
+    None
+}
+
+/// Roots of the characteristic polynomial of the leading `n` by `n` block, as `(re, im)`.
+fn eigenvalues(m: &[[f32; 3]; 3], n: usize) -> Vec<(f32, f32)> {
+    if n == 2 {
+        let trace = m[0][0] + m[1][1];
+        let det = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+        let disc = 0.25 * trace * trace - det;
+        return if disc >= 0.0 {
+            vec![(0.5 * trace + disc.sqrt(), 0.0), (0.5 * trace - disc.sqrt(), 0.0)]
+        } else {
+            vec![(0.5 * trace, (-disc).sqrt()), (0.5 * trace, -(-disc).sqrt())]
+        };
+    }
+
+    // λ³ + aλ² + bλ + c with a = -tr, b = sum of principal minors, c = -det
+    let mat = glam::Mat3::from_cols_array_2d(m);
+    let a = -(m[0][0] + m[1][1] + m[2][2]);
+    let b = m[0][0] * m[1][1] - m[0][1] * m[1][0]
+        + m[0][0] * m[2][2] - m[0][2] * m[2][0]
+        + m[1][1] * m[2][2] - m[1][2] * m[2][1];
+    let c = -mat.determinant();
+
+    // Depressed cubic t³ + pt + q with λ = t - a/3
+    let shift = -a / 3.0;
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let disc = 0.25 * q * q + p * p * p / 27.0;
+    if disc > 0.0 {
+        let u = (-0.5 * q + disc.sqrt()).cbrt();
+        let v = (-0.5 * q - disc.sqrt()).cbrt();
+        let im = 0.5 * 3f32.sqrt() * (u - v);
+        vec![(u + v + shift, 0.0), (-0.5 * (u + v) + shift, im), (-0.5 * (u + v) + shift, -im)]
+    } else if p.abs() < f32::EPSILON {
+        vec![(shift, 0.0); 3]
+    } else {
+        let r = 2.0 * (-p / 3.0).sqrt();
+        let phi = (3.0 * q / (p * r)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3)
+            .map(|k| (r * (phi - 2.0 * std::f32::consts::PI * k as f32 / 3.0).cos() + shift, 0.0))
+            .collect()
+    }
+}
+
+fn classify(eigenvalues: &[(f32, f32)]) -> Option<CriticalKind> {
+    let scale = eigenvalues.iter().map(|(re, im)| re.hypot(*im)).fold(0.0, f32::max);
+    if scale == 0.0 {
+        return None;
+    }
+    // Finite differences leave some noise in repeated eigenvalues
+    let eps = 1e-2 * scale;
+    let repelling = eigenvalues.iter().filter(|(re, _)| *re > eps).count();
+    let attracting = eigenvalues.iter().filter(|(re, _)| *re < -eps).count();
+    let rotating = eigenvalues.iter().any(|(_, im)| im.abs() > eps);
+
+    Some(match (repelling, attracting, rotating) {
+        (r, a, _) if r > 0 && a > 0 => CriticalKind::Saddle,
+        (0, 0, true) => CriticalKind::Center,
+        (r, _, true) if r > 0 => CriticalKind::RepellingSpiral,
+        (r, _, false) if r > 0 => CriticalKind::Source,
+        (_, a, true) if a > 0 => CriticalKind::AttractingSpiral,
+        (_, a, false) if a > 0 => CriticalKind::Sink,
+        _ => return None,
+    })
+}
+
+/// Null space of `m - λI` in the leading `n` by `n` block.
+fn eigenvector(m: &[[f32; 3]; 3], n: usize, eigenvalue: f32) -> [f32; 3] {
+    let mut rows = [Vec3::ZERO; 3];
+    for r in 0..n {
+        rows[r] = Vec3::from_array(std::array::from_fn(|c| if c < n { m[r][c] - if r == c { eigenvalue } else { 0.0 } } else { 0.0 }));
+    }
+
+    if n == 2 {
+        // Perpendicular to the longer row
+        let row = if rows[0].length_squared() >= rows[1].length_squared() { rows[0] } else { rows[1] };
+        return [-row.y, row.x, 0.0];
+    }
+
+    [rows[0].cross(rows[1]), rows[0].cross(rows[2]), rows[1].cross(rows[2])]
+        .into_iter()
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .unwrap()
+        .to_array()
+}
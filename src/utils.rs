@@ -1,87 +1,93 @@
-// use crate::models::CloudPoint;
-//
-// pub fn generate_arrow(num_sides: u32, shaft_radius: f32, head_radius: f32, shaft_height: f32) -> (Vec<CloudPoint>, Vec<u32>) {
-//     let angle_step = 2.0 * std::f32::consts::PI / num_sides as f32;
-//
-//     let mut vertices = Vec::new();
-//     let mut indices = Vec::new();
-//
-//     // Bottom vertices (shaft base)
-//     for i in 0..num_sides {
-//         let angle = i as f32 * angle_step;
-//         vertices.push(CloudPoint {
-//             position: [shaft_radius * angle.cos(), 0.0, shaft_radius * angle.sin()],
-//             color: [1.0, 1.0, 1.0, 1.0],
-//         });
-//     }
-//
-//     // Top vertices (shaft top)
-//     for i in 0..num_sides {
-//         let angle = i as f32 * angle_step;
-//         vertices.push(CloudPoint {
-//             position: [shaft_radius * angle.cos(), shaft_height, shaft_radius * angle.sin()],
-//             color: [1.0, 1.0, 1.0, 1.0],
-//         });
-//     }
-//
-//     // Head base vertices (same height as shaft top, larger radius)
-//     for i in 0..num_sides {
-//         let angle = i as f32 * angle_step;
-//         vertices.push(CloudPoint {
-//             position: [head_radius * angle.cos(), shaft_height, head_radius * angle.sin()],
-//             color: [1.0, 0.0, 1.0, 1.0],
-//         });
-//     }
-//
-//     // Head tip vertex (at the very top of the arrow)
-//     vertices.push(CloudPoint {
-//         position: [0.0, shaft_height + (shaft_radius * 2.0), 0.0],
-//         color: [1.0, 0.0, 0.0, 1.0],
-//     });
-//
-//     // Bottom face indices
-//
-//
-//     for i in 0..(num_sides - 1) {
-//         indices.push(i);
-//         indices.push(i + 1);
-//         indices.push((num_sides - 1));
-//     }
-//
-//     // Side faces between bottom and top of the shaft
-//     for i in 0..num_sides {
-//         let bottom_start = i;
-//         let top_start = i + num_sides;
-//         indices.push(bottom_start);
-//         indices.push(top_start);
-//         indices.push((top_start + 1) % num_sides + num_sides);
-//
-//         indices.push(bottom_start);
-//         indices.push((top_start + 1) % num_sides + num_sides);
-//         indices.push((bottom_start + 1) % num_sides);
-//     }
-//
-//     // Side faces between top of shaft and base of head
-//     for i in 0..num_sides {
-//         let top_start = i + num_sides;
-//         let head_start = i + 2 * num_sides;
-//         indices.push(top_start);
-//         indices.push(head_start);
-//         indices.push((head_start + 1) % num_sides + 2 * num_sides);
-//
-//         indices.push(top_start);
-//         indices.push((head_start + 1) % num_sides + 2 * num_sides);
-//         indices.push((top_start + 1) % num_sides + num_sides);
-//     }
-//
-//     // Side faces from base of head to the tip of the arrow
-//     let head_tip_index = vertices.len() as u32 - 1;
-//     for i in 0..num_sides {
-//         let head_base_start = i + 2 * num_sides;
-//         indices.push(head_tip_index);
-//         indices.push((head_base_start + 1) % num_sides + 2 * num_sides);
-//         indices.push(head_base_start);
-//     }
-//
-//     (vertices, indices)
-// }
\ No newline at end of file
+use std::f32::consts::PI;
+
+use crate::models::GlyphVertex;
+
+/// Arrow pointing along +Y with its base at the origin and its tip at y = 1.
+pub fn generate_arrow(num_sides: u32, shaft_radius: f32, head_radius: f32, shaft_height: f32) -> (Vec<GlyphVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    // Base cap
+    disk(&mut vertices, &mut indices, num_sides, shaft_radius, 0.0);
+
+    // Shaft, one ring at the bottom and one at the top
+    let shaft_start = vertices.len() as u32;
+    for height in [0.0, shaft_height] {
+        for i in 0..num_sides {
+            let (sin, cos) = (i as f32 * 2.0 * PI / num_sides as f32).sin_cos();
+            vertices.push(GlyphVertex {
+                position: [shaft_radius * cos, height, shaft_radius * sin],
+                normal: [cos, 0.0, sin],
+            });
+        }
+    }
+    for i in 0..num_sides {
+        let next = (i + 1) % num_sides;
+        let (b0, b1) = (shaft_start + i, shaft_start + next);
+        let (t0, t1) = (b0 + num_sides, b1 + num_sides);
+        indices.extend_from_slice(&[b0, t0, t1, b0, t1, b1]);
+    }
+
+    // Underside of the head
+    disk(&mut vertices, &mut indices, num_sides, head_radius, shaft_height);
+
+    cone(&mut vertices, &mut indices, num_sides, head_radius, shaft_height, 1.0);
+
+    (vertices, indices)
+}
+
+/// Cone pointing along +Y with its base at the origin and its tip at y = 1.
+pub fn generate_cone(num_sides: u32, radius: f32) -> (Vec<GlyphVertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    disk(&mut vertices, &mut indices, num_sides, radius, 0.0);
+    cone(&mut vertices, &mut indices, num_sides, radius, 0.0, 1.0);
+
+    (vertices, indices)
+}
+
+/// Unit segment along +Y, drawn as a line list.
+pub fn generate_line() -> (Vec<GlyphVertex>, Vec<u32>) {
+    let vertices = vec![
+        GlyphVertex { position: [0.0, 0.0, 0.0], normal: [0.0; 3] },
+        GlyphVertex { position: [0.0, 1.0, 0.0], normal: [0.0; 3] },
+    ];
+    (vertices, vec![0, 1])
+}
+
+/// Downward facing disk at `height`.
+fn disk(vertices: &mut Vec<GlyphVertex>, indices: &mut Vec<u32>, num_sides: u32, radius: f32, height: f32) {
+    let center = vertices.len() as u32;
+    vertices.push(GlyphVertex { position: [0.0, height, 0.0], normal: [0.0, -1.0, 0.0] });
+    for i in 0..num_sides {
+        let (sin, cos) = (i as f32 * 2.0 * PI / num_sides as f32).sin_cos();
+        vertices.push(GlyphVertex {
+            position: [radius * cos, height, radius * sin],
+            normal: [0.0, -1.0, 0.0],
+        });
+    }
+    for i in 0..num_sides {
+        indices.extend_from_slice(&[center, center + 1 + i, center + 1 + (i + 1) % num_sides]);
+    }
+}
+
+/// Cone side from a ring at `base` to a tip at `tip`, the tip is duplicated per side for proper normals.
+fn cone(vertices: &mut Vec<GlyphVertex>, indices: &mut Vec<u32>, num_sides: u32, radius: f32, base: f32, tip: f32) {
+    let slope = radius / (tip - base);
+    for i in 0..num_sides {
+        let start = vertices.len() as u32;
+        let a0 = i as f32 * 2.0 * PI / num_sides as f32;
+        let a1 = (i + 1) as f32 * 2.0 * PI / num_sides as f32;
+        let mid = 0.5 * (a0 + a1);
+
+        let normal = |a: f32| {
+            let n = glam::Vec3::new(a.cos(), slope, a.sin()).normalize();
+            n.to_array()
+        };
+        vertices.push(GlyphVertex { position: [radius * a0.cos(), base, radius * a0.sin()], normal: normal(a0) });
+        vertices.push(GlyphVertex { position: [0.0, tip, 0.0], normal: normal(mid) });
+        vertices.push(GlyphVertex { position: [radius * a1.cos(), base, radius * a1.sin()], normal: normal(a1) });
+        indices.extend_from_slice(&[start, start + 1, start + 2]);
+    }
+}
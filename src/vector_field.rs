@@ -0,0 +1,451 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use glam::Vec3;
+use serde::Deserialize;
+
+/// A vector field sampled on a structured grid, the way VTK `STRUCTURED_POINTS` lays it out:
+/// sample `(i, j, k)` sits at `origin + (i, j, k) * spacing`, data is stored x fastest.
+#[derive(Debug, Clone)]
+pub struct VectorField {
+    pub dims: [usize; 3],
+    pub origin: Vec3,
+    pub spacing: Vec3,
+    pub data: Vec<Vec3>,
+}
+
+/// Describes a raw binary file, read from a `.json` file next to it.
+#[derive(Debug, Deserialize)]
+struct RawSidecar {
+    dims: [usize; 3],
+    #[serde(default)]
+    origin: [f32; 3],
+    #[serde(default = "RawSidecar::default_spacing")]
+    spacing: [f32; 3],
+    /// `f32` or `f64`
+    #[serde(default = "RawSidecar::default_format")]
+    format: String,
+    #[serde(default)]
+    big_endian: bool,
+    /// Data file, relative to the sidecar. Defaults to the sidecar path with a `.raw` extension.
+    file: Option<PathBuf>,
+}
+
+impl RawSidecar {
+    fn default_spacing() -> [f32; 3] {
+        [1.0; 3]
+    }
+
+    fn default_format() -> String {
+        "f32".to_string()
+    }
+}
+
+impl VectorField {
+    pub fn new(dims: [usize; 3], origin: Vec3, spacing: Vec3, data: Vec<Vec3>) -> anyhow::Result<Self> {
+        if dims.contains(&0) {
+            bail!("vector field dimensions must be non-zero, got {:?}", dims);
+        }
+        if data.len() != dims[0] * dims[1] * dims[2] {
+            bail!("vector field has {} samples but dimensions {:?} need {}", data.len(), dims, dims[0] * dims[1] * dims[2]);
+        }
+        Ok(Self { dims, origin, spacing, data })
+    }
+
+    /// Picks a loader from the extension: `.vtk`, `.csv`, or `.json`/`.raw` for raw data with a sidecar.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        let field = match extension.as_deref() {
+            Some("vtk") => Self::load_vtk(path),
+            Some("csv") => Self::load_csv(path),
+            Some("json") => Self::load_raw(path),
+            Some("raw") | Some("bin") => Self::load_raw(&path.with_extension("json")),
+            _ => Err(anyhow!("unsupported vector field format")),
+        };
+        field.with_context(|| format!("failed to load vector field from {}", path.display()))
+    }
+
+    /// Legacy VTK `STRUCTURED_POINTS` with a `VECTORS` point attribute, ASCII or BINARY.
+    pub fn load_vtk(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let mut reader = VtkReader { bytes: &bytes, pos: 0 };
+
+        let header = reader.line()?;
+        if !header.starts_with("# vtk DataFile") {
+            bail!("missing VTK header");
+        }
+        let _title = reader.line()?;
+        let binary = match reader.line()?.trim().to_ascii_uppercase().as_str() {
+            "ASCII" => false,
+            "BINARY" => true,
+            other => bail!("unknown VTK encoding {other}"),
+        };
+
+        let mut dims = None;
+        let mut origin = Vec3::ZERO;
+        let mut spacing = Vec3::ONE;
+        let mut points = None;
+
+        loop {
+            let keyword = reader.token()?.to_ascii_uppercase();
+            match keyword.as_str() {
+                "DATASET" => {
+                    let kind = reader.token()?;
+                    if !kind.eq_ignore_ascii_case("STRUCTURED_POINTS") {
+                        bail!("only STRUCTURED_POINTS datasets are supported, got {kind}");
+                    }
+                }
+                "DIMENSIONS" => dims = Some([reader.parse::<usize>()?, reader.parse()?, reader.parse()?]),
+                "ORIGIN" => origin = Vec3::new(reader.parse()?, reader.parse()?, reader.parse()?),
+                "SPACING" | "ASPECT_RATIO" => spacing = Vec3::new(reader.parse()?, reader.parse()?, reader.parse()?),
+                "POINT_DATA" => points = Some(reader.parse::<usize>()?),
+                "SCALARS" => {
+                    let _name = reader.token()?;
+                    let data_type = reader.token()?;
+                    // The component count is optional, the next keyword is LOOKUP_TABLE
+                    let mut components = 1;
+                    let mut next = reader.token()?;
+                    if let Ok(c) = next.parse::<usize>() {
+                        components = c;
+                        next = reader.token()?;
+                    }
+                    if !next.eq_ignore_ascii_case("LOOKUP_TABLE") {
+                        bail!("expected LOOKUP_TABLE, got {next}");
+                    }
+                    let _table = reader.token()?;
+                    let count = points.ok_or_else(|| anyhow!("SCALARS before POINT_DATA"))? * components;
+                    reader.values(count, &data_type, binary)?;
+                }
+                "VECTORS" => {
+                    let _name = reader.token()?;
+                    let data_type = reader.token()?;
+                    let count = points.ok_or_else(|| anyhow!("VECTORS before POINT_DATA"))?;
+                    let values = reader.values(count * 3, &data_type, binary)?;
+                    let data = values.chunks_exact(3).map(|c| Vec3::new(c[0], c[1], c[2])).collect();
+                    let dims = dims.ok_or_else(|| anyhow!("missing DIMENSIONS"))?;
+                    return Self::new(dims, origin, spacing, data);
+                }
+                other => bail!("unsupported VTK keyword {other}"),
+            }
+        }
+    }
+
+    /// Raw little or big endian floats, three per sample, described by a JSON sidecar.
+    pub fn load_raw(sidecar_path: &Path) -> anyhow::Result<Self> {
+        let sidecar: RawSidecar = serde_json::from_str(&std::fs::read_to_string(sidecar_path)?)?;
+        let data_path = match &sidecar.file {
+            Some(file) => sidecar_path.parent().unwrap_or(Path::new("")).join(file),
+            None => sidecar_path.with_extension("raw"),
+        };
+        let bytes = std::fs::read(&data_path)
+            .with_context(|| format!("failed to read raw data {}", data_path.display()))?;
+
+        let values = decode_floats(&bytes, &sidecar.format, sidecar.big_endian)?;
+        let data = values.chunks_exact(3).map(|c| Vec3::new(c[0], c[1], c[2])).collect();
+        Self::new(sidecar.dims, Vec3::from(sidecar.origin), Vec3::from(sidecar.spacing), data)
+    }
+
+    /// One sample per row: `x,y,z,u,v,w`, or `x,y,u,v` for planar fields. A header row is allowed.
+    /// The rows have to cover a full regular grid once each, in any order.
+    pub fn load_csv(path: &Path) -> anyhow::Result<Self> {
+        Self::parse_csv(&std::fs::read_to_string(path)?)
+    }
+
+    fn parse_csv(text: &str) -> anyhow::Result<Self> {
+        let mut rows = Vec::new();
+        let mut header = false;
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values = line.split(',').map(|v| v.trim().parse::<f32>()).collect::<Result<Vec<_>, _>>();
+            let values = match values {
+                Ok(values) => values,
+                Err(_) if rows.is_empty() && !header => {
+                    let columns = line.split(',').count();
+                    if columns != 4 && columns != 6 {
+                        bail!("line {}: header has {columns} columns, expected 4 or 6", line_number + 1);
+                    }
+                    header = true;
+                    continue;
+                }
+                Err(e) => bail!("line {}: {e}", line_number + 1),
+            };
+            let (p, v) = match values.len() {
+                6 => (Vec3::new(values[0], values[1], values[2]), Vec3::new(values[3], values[4], values[5])),
+                4 => (Vec3::new(values[0], values[1], 0.0), Vec3::new(values[2], values[3], 0.0)),
+                n => bail!("line {}: expected 4 or 6 columns, got {n}", line_number + 1),
+            };
+            rows.push((p, v));
+        }
+        if rows.is_empty() {
+            bail!("no samples");
+        }
+
+        let axis = |a: usize| {
+            let mut coords = rows.iter().map(|(p, _)| p[a]).collect::<Vec<_>>();
+            coords.sort_by(f32::total_cmp);
+            coords.dedup_by(|a, b| (*a - *b).abs() <= 1e-5 * b.abs().max(1.0));
+            coords
+        };
+        let coords = [axis(0), axis(1), axis(2)];
+        let dims = [coords[0].len(), coords[1].len(), coords[2].len()];
+        let origin = Vec3::new(coords[0][0], coords[1][0], coords[2][0]);
+        let spacing = Vec3::from_array(std::array::from_fn(|a| {
+            if dims[a] > 1 { (coords[a][dims[a] - 1] - coords[a][0]) / (dims[a] - 1) as f32 } else { 1.0 }
+        }));
+
+        let mut data = vec![Vec3::ZERO; dims[0] * dims[1] * dims[2]];
+        let mut filled = vec![false; data.len()];
+        for (p, v) in rows {
+            let g = (p - origin) / spacing;
+            // Only float noise is rounded away, anything further off is an irregular grid
+            if (g - g.round()).abs().max_element() > 1e-3 {
+                bail!("sample at {p} is not on a regular grid");
+            }
+            let g = g.round();
+            let (i, j, k) = (g.x as usize, g.y as usize, g.z as usize);
+            if i >= dims[0] || j >= dims[1] || k >= dims[2] {
+                bail!("sample at {p} is not on a regular grid");
+            }
+            let index = (k * dims[1] + j) * dims[0] + i;
+            if filled[index] {
+                bail!("more than one sample at {p}");
+            }
+            data[index] = v;
+            filled[index] = true;
+        }
+        if filled.iter().any(|f| !f) {
+            bail!("samples do not cover a full {}x{}x{} grid", dims[0], dims[1], dims[2]);
+        }
+
+        Self::new(dims, origin, spacing, data)
+    }
+
+    pub fn index(&self, i: usize, j: usize, k: usize) -> usize {
+        (k * self.dims[1] + j) * self.dims[0] + i
+    }
+
+    pub fn get(&self, i: usize, j: usize, k: usize) -> Vec3 {
+        self.data[self.index(i, j, k)]
+    }
+
+    pub fn position(&self, i: usize, j: usize, k: usize) -> Vec3 {
+        self.origin + Vec3::new(i as f32, j as f32, k as f32) * self.spacing
+    }
+
+    /// Region covered by the samples themselves.
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        let last = Vec3::new(
+            (self.dims[0] - 1) as f32,
+            (self.dims[1] - 1) as f32,
+            (self.dims[2] - 1) as f32,
+        );
+        (self.origin, self.origin + last * self.spacing)
+    }
+
+    /// Bounds for a [`crate::field_texture::FieldTexture`], whose samples sit on texel centers.
+    pub fn texture_bounds(&self) -> (Vec3, Vec3) {
+        let (min, max) = self.bounds();
+        (min - 0.5 * self.spacing, max + 0.5 * self.spacing)
+    }
+
+    pub fn contains(&self, p: Vec3) -> bool {
+        let (min, max) = self.bounds();
+        // Flat axes only have one sample, treat them as extruded instead of zero thickness
+        (0..3).all(|a| self.dims[a] == 1 || (p[a] >= min[a] && p[a] <= max[a]))
+    }
+
+    /// Trilinear interpolation, `None` outside of [`VectorField::bounds`].
+    pub fn sample(&self, p: Vec3) -> Option<Vec3> {
+        if !self.contains(p) {
+            return None;
+        }
+
+        let g = (p - self.origin) / self.spacing;
+        let mut c0 = [0usize; 3];
+        let mut t = [0.0f32; 3];
+        for a in 0..3 {
+            let max = self.dims[a].saturating_sub(2);
+            let g = g[a].max(0.0);
+            c0[a] = (g.floor() as usize).min(max);
+            t[a] = if self.dims[a] == 1 { 0.0 } else { (g - c0[a] as f32).clamp(0.0, 1.0) };
+        }
+        let c1 = std::array::from_fn::<usize, 3, _>(|a| (c0[a] + 1).min(self.dims[a] - 1));
+
+        let x00 = self.get(c0[0], c0[1], c0[2]).lerp(self.get(c1[0], c0[1], c0[2]), t[0]);
+        let x10 = self.get(c0[0], c1[1], c0[2]).lerp(self.get(c1[0], c1[1], c0[2]), t[0]);
+        let x01 = self.get(c0[0], c0[1], c1[2]).lerp(self.get(c1[0], c0[1], c1[2]), t[0]);
+        let x11 = self.get(c0[0], c1[1], c1[2]).lerp(self.get(c1[0], c1[1], c1[2]), t[0]);
+        Some(x00.lerp(x10, t[1]).lerp(x01.lerp(x11, t[1]), t[2]))
+    }
+
+    /// Uniformly rescales the field so its texture bounds fit `[-half_extent, half_extent]`, centered
+    /// on the origin. Velocities are scaled too, so particles take the same time to cross it.
+    pub fn fit_to_cube(&self, half_extent: f32) -> Self {
+        let (min, max) = self.texture_bounds();
+        let scale = 2.0 * half_extent / (max - min).max_element();
+        let center = 0.5 * (min + max);
+        Self {
+            dims: self.dims,
+            origin: (self.origin - center) * scale,
+            spacing: self.spacing * scale,
+            data: self.data.iter().map(|v| *v * scale).collect(),
+        }
+    }
+
+    /// The axis aligned 2D slice at sample `index` along `axis`, kept 3D with `dims[axis] == 1`.
+    pub fn slice(&self, axis: usize, index: usize) -> Self {
+        let index = index.min(self.dims[axis] - 1);
+        let mut dims = self.dims;
+        dims[axis] = 1;
+        let mut origin = self.origin;
+        origin[axis] += index as f32 * self.spacing[axis];
+
+        let mut data = Vec::with_capacity(dims[0] * dims[1] * dims[2]);
+        for k in 0..dims[2] {
+            for j in 0..dims[1] {
+                for i in 0..dims[0] {
+                    let mut c = [i, j, k];
+                    c[axis] = index;
+                    data.push(self.get(c[0], c[1], c[2]));
+                }
+            }
+        }
+
+        Self {
+            dims,
+            origin,
+            spacing: self.spacing,
+            data,
+        }
+    }
+
+    /// Samples padded to `vec4`, the layout [`crate::field_texture::FieldTexture::write`] expects.
+    pub fn to_texture_data(&self) -> Vec<[f32; 4]> {
+        self.data.iter().map(|v| v.extend(0.0).to_array()).collect()
+    }
+}
+
+fn decode_floats(bytes: &[u8], format: &str, big_endian: bool) -> anyhow::Result<Vec<f32>> {
+    let values = match format {
+        "f32" | "float" => bytes.chunks_exact(4).map(|c| {
+            let c = [c[0], c[1], c[2], c[3]];
+            if big_endian { f32::from_be_bytes(c) } else { f32::from_le_bytes(c) }
+        }).collect(),
+        "f64" | "double" => bytes.chunks_exact(8).map(|c| {
+            let c = [c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]];
+            (if big_endian { f64::from_be_bytes(c) } else { f64::from_le_bytes(c) }) as f32
+        }).collect(),
+        other => bail!("unsupported sample format {other}"),
+    };
+    Ok(values)
+}
+
+/// Cursor over a legacy VTK file, which mixes text keywords with (optionally) binary payloads.
+struct VtkReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl VtkReader<'_> {
+    fn line(&mut self) -> anyhow::Result<String> {
+        if self.pos >= self.bytes.len() {
+            bail!("unexpected end of file");
+        }
+        let rest = &self.bytes[self.pos..];
+        let end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        self.pos += (end + 1).min(rest.len());
+        Ok(String::from_utf8_lossy(&rest[..end]).trim_end_matches('\r').to_string())
+    }
+
+    fn token(&mut self) -> anyhow::Result<String> {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            bail!("unexpected end of file");
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.pos]).to_string())
+    }
+
+    fn parse<T: std::str::FromStr>(&mut self) -> anyhow::Result<T> {
+        let token = self.token()?;
+        token.parse().map_err(|_| anyhow!("invalid number {token}"))
+    }
+
+    fn values(&mut self, count: usize, data_type: &str, binary: bool) -> anyhow::Result<Vec<f32>> {
+        if !binary {
+            return (0..count).map(|_| self.parse::<f32>()).collect();
+        }
+
+        // Binary payload starts after the end of the keyword line and is always big endian
+        self.line()?;
+        let (format, size) = match data_type.to_ascii_lowercase().as_str() {
+            "float" => ("f32", 4),
+            "double" => ("f64", 8),
+            other => bail!("unsupported binary VTK data type {other}"),
+        };
+        let end = self.pos + count * size;
+        if end > self.bytes.len() {
+            bail!("binary data is truncated");
+        }
+        let values = decode_floats(&self.bytes[self.pos..end], format, true)?;
+        self.pos = end;
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fills_the_grid_in_any_order() {
+        let field = VectorField::parse_csv("x,y,u,v\n1,0,2,0\n0,0,1,0\n\n0,2,3,0\n# comment\n1,2,4,1\n").unwrap();
+        assert_eq!(field.dims, [2, 2, 1]);
+        assert_eq!(field.origin, Vec3::ZERO);
+        assert_eq!(field.spacing, Vec3::new(1.0, 2.0, 1.0));
+        assert_eq!(field.data, [Vec3::X, Vec3::new(2.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0), Vec3::new(4.0, 1.0, 0.0)]);
+
+        let field = VectorField::parse_csv("0,0,0,1,2,3\n0,0,1,4,5,6\n").unwrap();
+        assert_eq!(field.dims, [1, 1, 2]);
+        assert_eq!(field.get(0, 0, 1), Vec3::new(4.0, 5.0, 6.0));
+    }
+
+    #[test]
+    fn csv_rejects_bad_headers() {
+        let error = VectorField::parse_csv("x,y,z,u,v\n0,0,1,0\n").unwrap_err();
+        assert!(error.to_string().contains("header has 5 columns"), "{error}");
+        let error = VectorField::parse_csv("x,y,u,v\nx,y,u,v\n0,0,1,0\n").unwrap_err();
+        assert!(error.to_string().starts_with("line 2"), "{error}");
+        let error = VectorField::parse_csv("0,0,1,0\nx,y,u,v\n").unwrap_err();
+        assert!(error.to_string().starts_with("line 2"), "{error}");
+    }
+
+    #[test]
+    fn csv_rejects_wrong_column_counts() {
+        let error = VectorField::parse_csv("0,0,1,0\n1,0,1,0,0\n").unwrap_err();
+        assert!(error.to_string().contains("expected 4 or 6 columns, got 5"), "{error}");
+    }
+
+    #[test]
+    fn csv_rejects_irregular_spacing() {
+        let error = VectorField::parse_csv("0,0,1,0\n0.4,0,1,0\n0.6,0,1,0\n1,0,1,0\n").unwrap_err();
+        assert!(error.to_string().contains("not on a regular grid"), "{error}");
+    }
+
+    #[test]
+    fn csv_rejects_missing_and_duplicate_points() {
+        let error = VectorField::parse_csv("0,0,1,0\n1,0,1,0\n0,1,1,0\n").unwrap_err();
+        assert!(error.to_string().contains("do not cover a full 2x2x1 grid"), "{error}");
+        let error = VectorField::parse_csv("0,0,1,0\n1,0,1,0\n0,0,2,0\n").unwrap_err();
+        assert!(error.to_string().contains("more than one sample"), "{error}");
+    }
+}